@@ -0,0 +1,68 @@
+//! Records the versions of the crates that render with hayro, so that cached renders of
+//! the hayro backend are invalidated when any of them is updated.
+//!
+//! The versions are read from the pinned dependencies in `Cargo.toml`, which is also
+//! available when sitro is built as a dependency, unlike `Cargo.lock`.
+
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+/// The dependencies that decide how hayro renders a PDF.
+const HAYRO_CRATES: &[&str] = &[
+    "hayro",
+    "hayro-ccitt",
+    "hayro-cmap",
+    "hayro-interpret",
+    "hayro-jbig2",
+    "hayro-jpeg2000",
+    "hayro-postscript",
+    "hayro-syntax",
+    "vello_common",
+    "vello_cpu",
+];
+
+fn main() {
+    println!("cargo:rerun-if-changed=build.rs");
+    println!("cargo:rerun-if-changed=Cargo.toml");
+
+    let manifest = PathBuf::from(env::var("CARGO_MANIFEST_DIR").unwrap()).join("Cargo.toml");
+    let manifest = fs::read_to_string(&manifest)
+        .unwrap_or_else(|e| panic!("failed to read {}: {e}", manifest.display()));
+
+    match hayro_engine(&manifest) {
+        Ok(engine) => println!("cargo:rustc-env=SITRO_HAYRO_ENGINE={engine}"),
+        Err(e) => panic!("failed to determine the version of hayro: {e}"),
+    }
+}
+
+/// The names and exact versions of the hayro crates, like `hayro 0.7.1, hayro-ccitt 0.3.0`.
+fn hayro_engine(manifest: &str) -> Result<String, String> {
+    let manifest = manifest.parse::<toml::Table>().map_err(|e| e.to_string())?;
+    let dependencies = manifest
+        .get("dependencies")
+        .and_then(|dependencies| dependencies.as_table())
+        .ok_or("Cargo.toml has no dependencies")?;
+
+    let versions = HAYRO_CRATES
+        .iter()
+        .map(|name| {
+            let dependency = dependencies
+                .get(*name)
+                .ok_or_else(|| format!("`{name}` is not a dependency"))?;
+            let version = match dependency {
+                toml::Value::String(version) => Some(version.as_str()),
+                toml::Value::Table(table) => table.get("version").and_then(|v| v.as_str()),
+                _ => None,
+            };
+
+            // Only an exact requirement determines the version that is built.
+            match version.and_then(|version| version.strip_prefix('=')) {
+                Some(version) => Ok(format!("{name} {}", version.trim())),
+                None => Err(format!("`{name}` must be pinned to an exact version")),
+            }
+        })
+        .collect::<Result<Vec<_>, String>>()?;
+
+    Ok(versions.join(", "))
+}
@@ -0,0 +1,211 @@
+use crate::diff::{diff_pixmaps, Diff, DiffOptions};
+use tiny_skia::{Color, FilterQuality, Pixmap, PixmapPaint, Transform};
+
+/// The options that should be applied when estimating the offset between two rendered pages.
+#[derive(Copy, Clone)]
+pub struct AlignOptions {
+    /// The largest offset in pixels that should be searched for, in each direction.
+    pub max_shift: u32,
+}
+
+impl Default for AlignOptions {
+    fn default() -> Self {
+        Self { max_shift: 4 }
+    }
+}
+
+/// The estimated translation between two rendered pages.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Offset {
+    /// The horizontal offset in pixels of the second page relative to the first one.
+    pub dx: f32,
+    /// The vertical offset in pixels of the second page relative to the first one.
+    pub dy: f32,
+    /// The normalized cross-correlation of both pages at the estimated offset,
+    /// between 0.0 (nothing in common) and 1.0 (identical up to the offset).
+    ///
+    /// Blank pages always have a correlation of 0.0.
+    pub correlation: f32,
+}
+
+impl Offset {
+    /// The length of the offset in pixels.
+    pub fn magnitude(&self) -> f32 {
+        self.dx.hypot(self.dy)
+    }
+}
+
+/// The result of comparing two rendered pages before and after compensating for their offset.
+pub struct AlignedDiff {
+    /// The estimated offset between the two pages.
+    pub offset: Offset,
+    /// The diff of the pages as they were rendered.
+    pub before: Diff,
+    /// The diff of the pages after shifting the second page by the inverse of the offset.
+    pub after: Diff,
+}
+
+/// Estimate by how much the second page is translated relative to the first one.
+///
+/// The integer offset is found by maximizing the normalized cross-correlation of the
+/// inverted luminance of both pages, and refined to sub-pixel precision by fitting a
+/// parabola through the neighboring correlations.
+pub fn estimate_offset(a: &Pixmap, b: &Pixmap, options: &AlignOptions) -> Offset {
+    let a = Ink::new(a);
+    let b = Ink::new(b);
+    let max_shift = options.max_shift as i32;
+    let size = (2 * max_shift + 3) as usize;
+
+    // Also compute a border of one around the search area, so that the
+    // sub-pixel refinement works for offsets at the edge of it.
+    let index =
+        |sx: i32, sy: i32| (sy + max_shift + 1) as usize * size + (sx + max_shift + 1) as usize;
+    let mut scores = vec![0.0; size * size];
+
+    for sy in -max_shift - 1..=max_shift + 1 {
+        for sx in -max_shift - 1..=max_shift + 1 {
+            scores[index(sx, sy)] = a.correlate(&b, sx, sy);
+        }
+    }
+
+    let score = |sx: i32, sy: i32| scores[index(sx, sy)];
+    let mut best = (0, 0);
+
+    for sy in -max_shift..=max_shift {
+        for sx in -max_shift..=max_shift {
+            let (current, best_score) = (score(sx, sy), score(best.0, best.1));
+            // Prefer smaller offsets in case of a tie.
+            let closer = sx.abs() + sy.abs() < best.0.abs() + best.1.abs();
+
+            if current > best_score || (current == best_score && closer) {
+                best = (sx, sy);
+            }
+        }
+    }
+
+    let (sx, sy) = best;
+    let center = score(sx, sy);
+
+    Offset {
+        dx: sx as f32 + refine(score(sx - 1, sy), center, score(sx + 1, sy)),
+        dy: sy as f32 + refine(score(sx, sy - 1), center, score(sx, sy + 1)),
+        correlation: center,
+    }
+}
+
+/// Translate a page by the given offset, filling the uncovered area with white.
+pub fn shift(pixmap: &Pixmap, dx: f32, dy: f32) -> Pixmap {
+    let mut shifted = Pixmap::new(pixmap.width(), pixmap.height()).unwrap();
+    shifted.fill(Color::WHITE);
+
+    let paint = PixmapPaint {
+        quality: FilterQuality::Bilinear,
+        ..PixmapPaint::default()
+    };
+
+    shifted.draw_pixmap(
+        0,
+        0,
+        pixmap.as_ref(),
+        &paint,
+        Transform::from_translate(dx, dy),
+        None,
+    );
+
+    shifted
+}
+
+/// Compare two rendered pages before and after compensating for their offset.
+pub fn diff_aligned(
+    a: &Pixmap,
+    b: &Pixmap,
+    align_options: &AlignOptions,
+    diff_options: &DiffOptions,
+) -> AlignedDiff {
+    let offset = estimate_offset(a, b, align_options);
+    let before = diff_pixmaps(a, b, diff_options);
+    let after = if offset.dx == 0.0 && offset.dy == 0.0 {
+        diff_pixmaps(a, b, diff_options)
+    } else {
+        diff_pixmaps(a, &shift(b, -offset.dx, -offset.dy), diff_options)
+    };
+
+    AlignedDiff {
+        offset,
+        before,
+        after,
+    }
+}
+
+/// Find the sub-pixel position of the peak of a parabola through three samples.
+fn refine(left: f32, center: f32, right: f32) -> f32 {
+    let denominator = left - 2.0 * center + right;
+
+    if denominator >= 0.0 {
+        // Not a maximum, so there is nothing to refine.
+        return 0.0;
+    }
+
+    (0.5 * (left - right) / denominator).clamp(-0.5, 0.5)
+}
+
+/// The inverted luminance of a page, so that the (usually white) background is zero.
+struct Ink {
+    width: i32,
+    height: i32,
+    data: Vec<f32>,
+}
+
+impl Ink {
+    fn new(pixmap: &Pixmap) -> Self {
+        let data = pixmap
+            .data()
+            .chunks_exact(4)
+            .map(|p| {
+                // Premultiplied pixels, so blend towards white for transparent areas.
+                let white = 255.0 - p[3] as f32;
+                let luma = (p[0] as f32 + white) * 0.299
+                    + (p[1] as f32 + white) * 0.587
+                    + (p[2] as f32 + white) * 0.114;
+                (255.0 - luma) / 255.0
+            })
+            .collect();
+
+        Self {
+            width: pixmap.width() as i32,
+            height: pixmap.height() as i32,
+            data,
+        }
+    }
+
+    fn get(&self, x: i32, y: i32) -> f32 {
+        if x < 0 || y < 0 || x >= self.width || y >= self.height {
+            0.0
+        } else {
+            self.data[(y * self.width + x) as usize]
+        }
+    }
+
+    /// The normalized cross-correlation with `other` shifted by the given offset.
+    fn correlate(&self, other: &Ink, sx: i32, sy: i32) -> f32 {
+        let mut product = 0.0;
+        let mut norm_a = 0.0;
+        let mut norm_b = 0.0;
+
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let a = self.get(x, y);
+                let b = other.get(x + sx, y + sy);
+                product += a * b;
+                norm_a += a * a;
+                norm_b += b * b;
+            }
+        }
+
+        if norm_a == 0.0 || norm_b == 0.0 {
+            0.0
+        } else {
+            product / (norm_a * norm_b).sqrt()
+        }
+    }
+}
@@ -0,0 +1,714 @@
+use super::config::{self, Profile, DEFAULT_CONFIG};
+use sitro::{
+    Backend, ConsensusOptions, DiffOptions, DiskCache, Expectations, JunitOptions, Layout,
+};
+use std::collections::VecDeque;
+use std::path::PathBuf;
+use std::time::Duration;
+
+/// A single command-line argument.
+#[derive(Debug, Eq, PartialEq)]
+pub enum Arg {
+    /// A flag like `--scale` or `-s`.
+    Flag(String),
+    /// Any other argument.
+    Positional(String),
+}
+
+/// The remaining command-line arguments.
+pub struct Args {
+    args: VecDeque<String>,
+    /// The value of a `--flag=value` argument that hasn't been consumed yet.
+    inline: Option<String>,
+    /// Whether `--` was encountered, after which everything is positional.
+    positional_only: bool,
+}
+
+impl Args {
+    pub fn new(args: impl IntoIterator<Item = String>) -> Self {
+        Self {
+            args: args.into_iter().collect(),
+            inline: None,
+            positional_only: false,
+        }
+    }
+
+    /// Get the next argument.
+    pub fn next(&mut self) -> Result<Option<Arg>, String> {
+        if let Some(value) = self.inline.take() {
+            return Err(format!("unexpected value `{value}`"));
+        }
+
+        let Some(arg) = self.args.pop_front() else {
+            return Ok(None);
+        };
+
+        if self.positional_only || arg == "-" || !arg.starts_with('-') {
+            return Ok(Some(Arg::Positional(arg)));
+        }
+
+        if arg == "--" {
+            self.positional_only = true;
+            return self.next();
+        }
+
+        match arg.split_once('=') {
+            Some((flag, value)) if flag.starts_with("--") => {
+                self.inline = Some(value.to_string());
+                Ok(Some(Arg::Flag(flag.to_string())))
+            }
+            _ => Ok(Some(Arg::Flag(arg))),
+        }
+    }
+
+    /// Get the value of a flag, either from `--flag=value` or the next argument.
+    pub fn value(&mut self, flag: &str) -> Result<String, String> {
+        self.inline
+            .take()
+            .or_else(|| self.args.pop_front())
+            .ok_or_else(|| format!("`{flag}` requires a value"))
+    }
+
+    /// Parse the value of a flag.
+    pub fn parse<T>(
+        &mut self,
+        flag: &str,
+        parse: impl FnOnce(&str) -> Result<T, String>,
+    ) -> Result<T, String> {
+        let value = self.value(flag)?;
+        parse(&value).map_err(|e| format!("invalid value `{value}` for `{flag}`: {e}"))
+    }
+}
+
+/// A range of pages, as 0-based indices.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct PageRange {
+    /// The index of the first page.
+    pub first: usize,
+    /// The index of the last page, or `None` to include all remaining pages.
+    pub last: Option<usize>,
+}
+
+impl PageRange {
+    /// Parse a 1-based page range like `3`, `2-5`, `2-` or `-5`.
+    pub fn parse(value: &str) -> Result<Self, String> {
+        let number = |s: &str| match s.trim().parse::<usize>() {
+            Ok(0) => Err("pages are numbered from 1".to_string()),
+            Ok(n) => Ok(n - 1),
+            Err(_) => Err(format!("`{s}` is not a page number")),
+        };
+
+        let (first, last) = match value.split_once('-') {
+            Some((first, last)) => (
+                if first.trim().is_empty() {
+                    0
+                } else {
+                    number(first)?
+                },
+                if last.trim().is_empty() {
+                    None
+                } else {
+                    Some(number(last)?)
+                },
+            ),
+            None => {
+                let page = number(value)?;
+                (page, Some(page))
+            }
+        };
+
+        if last.is_some_and(|last| last < first) {
+            return Err("the last page comes before the first one".to_string());
+        }
+
+        Ok(Self { first, last })
+    }
+
+    /// Keep only the pages in the range.
+    pub fn select<T>(&self, mut pages: Vec<T>) -> Vec<T> {
+        if let Some(last) = self.last {
+            pages.truncate(last + 1);
+        }
+
+        pages.drain(..self.first.min(pages.len()));
+        pages
+    }
+}
+
+/// Parse the name of a backend that is available on this platform.
+pub fn parse_backend(name: &str) -> Result<Backend, String> {
+    let backend = Backend::from_name(name).ok_or_else(|| {
+        if let Some(base) = name
+            .split_once('@')
+            .and_then(|(base, _)| Backend::from_name(base))
+        {
+            return match base {
+                Backend::Quartz | Backend::Hayro => {
+                    format!(
+                        "invalid backend `{name}`, {} doesn't run in Docker",
+                        base.name()
+                    )
+                }
+                _ => format!("invalid image label in backend `{name}`"),
+            };
+        }
+
+        let names = Backend::ALL.map(|b| b.name()).join(", ");
+        format!("unknown backend `{name}` (available backends are {names})")
+    })?;
+
+    if !backend.is_available() {
+        return Err(format!(
+            "backend `{name}` is not available on this platform"
+        ));
+    }
+
+    Ok(backend)
+}
+
+/// Parse the maximum difference of a color channel for pixels to be considered equal.
+pub fn parse_tolerance(value: &str) -> Result<u8, String> {
+    value
+        .parse()
+        .map_err(|_| "expected a number from 0 to 255".to_string())
+}
+
+/// Parse a comma-separated list of backend names.
+pub fn parse_backends(value: &str) -> Result<Vec<Backend>, String> {
+    let mut backends = vec![];
+
+    for name in value.split(',').map(str::trim).filter(|n| !n.is_empty()) {
+        let backend = parse_backend(name)?;
+
+        if !backends.contains(&backend) {
+            backends.push(backend);
+        }
+    }
+
+    if backends.is_empty() {
+        return Err("no backends given".to_string());
+    }
+
+    Ok(backends)
+}
+
+/// Parse a positive, finite number.
+pub fn parse_positive(value: &str) -> Result<f32, String> {
+    match value.parse::<f32>() {
+        Ok(n) if n.is_finite() && n > 0.0 => Ok(n),
+        _ => Err("expected a positive number".to_string()),
+    }
+}
+
+/// Parse a composite layout: `auto`, `horizontal`, `vertical` or `grid:<COLUMNS>`.
+pub fn parse_layout(value: &str) -> Result<Layout, String> {
+    match value {
+        "auto" => Ok(Layout::Auto),
+        "horizontal" => Ok(Layout::Horizontal),
+        "vertical" => Ok(Layout::Vertical),
+        _ => value
+            .strip_prefix("grid:")
+            .and_then(|columns| columns.parse::<usize>().ok())
+            .filter(|columns| *columns > 0)
+            .map(Layout::Grid)
+            .ok_or_else(|| "expected auto, horizontal, vertical or grid:<COLUMNS>".to_string()),
+    }
+}
+
+/// Parse a number that is at least zero.
+pub fn parse_non_negative(value: &str) -> Result<f32, String> {
+    match value.parse::<f32>() {
+        Ok(n) if n.is_finite() && n >= 0.0 => Ok(n),
+        _ => Err("expected a number that is at least 0".to_string()),
+    }
+}
+
+/// Where renders are cached.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub enum CacheDir {
+    /// The default cache directory, see [`DiskCache::default_dir`].
+    #[default]
+    Default,
+    /// The given directory.
+    Dir(PathBuf),
+    /// Don't cache renders.
+    Disabled,
+}
+
+impl CacheDir {
+    /// Handle `--cache-dir` and `--no-cache`, returning whether the flag was one of them.
+    pub fn parse_flag(&mut self, flag: &str, args: &mut Args) -> Result<bool, String> {
+        match flag {
+            "--cache-dir" => *self = CacheDir::Dir(PathBuf::from(args.value(flag)?)),
+            "--no-cache" => *self = CacheDir::Disabled,
+            _ => return Ok(false),
+        }
+
+        Ok(true)
+    }
+
+    /// The cache directory, or `None` if caching is disabled or there is no default
+    /// directory on this platform.
+    pub fn dir(&self) -> Option<PathBuf> {
+        match self {
+            CacheDir::Default => DiskCache::default_dir(),
+            CacheDir::Dir(dir) => Some(dir.clone()),
+            CacheDir::Disabled => None,
+        }
+    }
+}
+
+/// The arguments of a corpus run.
+pub struct RunArgs {
+    /// The PDF files and directories to render.
+    pub inputs: Vec<PathBuf>,
+    /// The directory the composites and reports are written to.
+    pub output: PathBuf,
+    /// The backends to render with.
+    pub backends: Vec<Backend>,
+    /// The scale to render at.
+    pub scale: f32,
+    /// The pages to keep.
+    pub pages: PageRange,
+    /// The width of the border around each page in the composites, relative to the
+    /// smaller side of the page.
+    pub border_width: f32,
+    /// Whether to delete the output directory before rendering.
+    pub clean: bool,
+    /// Whether to outline the regions in which backends differ from the consensus.
+    pub annotate: bool,
+    /// Where renders are cached.
+    pub cache: CacheDir,
+    /// Whether to keep watching the inputs and re-render them when they change.
+    pub watch: bool,
+    /// How often the inputs are checked for changes in watch mode.
+    pub poll_interval: Duration,
+    /// How the pages of each backend are arranged in the composites.
+    pub layout: Layout,
+    /// By how much a page may differ from the consensus before it counts as a difference,
+    /// as a fraction of pixels.
+    pub threshold: f32,
+    /// Thresholds that replace `threshold` for specific backends.
+    pub backend_thresholds: Vec<(Backend, f32)>,
+    /// Known divergences of backends, which aren't reported as failures.
+    pub expectations: Expectations,
+}
+
+impl Default for RunArgs {
+    fn default() -> Self {
+        Self {
+            inputs: vec![],
+            output: PathBuf::from("test"),
+            backends: Backend::ALL
+                .into_iter()
+                .filter(Backend::is_available)
+                .collect(),
+            scale: 1.0,
+            pages: PageRange::default(),
+            border_width: 1.0 / 50.0,
+            clean: false,
+            annotate: false,
+            cache: CacheDir::Default,
+            watch: false,
+            poll_interval: Duration::from_secs(1),
+            layout: Layout::Auto,
+            threshold: ConsensusOptions::default().threshold,
+            backend_thresholds: vec![],
+            expectations: Expectations::default(),
+        }
+    }
+}
+
+impl RunArgs {
+    /// Parse the arguments of a run, applying the config file and profile first so that
+    /// flags override them.
+    pub fn parse(args: &mut Args) -> Result<Self, String> {
+        let mut run = Self::default();
+        let mut flags = Profile::default();
+        let mut config = None;
+        let mut profile = None;
+
+        while let Some(arg) = args.next()? {
+            match arg {
+                Arg::Positional(input) => flags
+                    .inputs
+                    .get_or_insert_with(Vec::new)
+                    .push(PathBuf::from(input)),
+                Arg::Flag(flag) => match flag.as_str() {
+                    "-o" | "--output" => flags.output = Some(PathBuf::from(args.value(&flag)?)),
+                    "-b" | "--backends" => {
+                        flags.backends = Some(args.parse(&flag, parse_backends)?)
+                    }
+                    "-s" | "--scale" => flags.scale = Some(args.parse(&flag, parse_positive)?),
+                    "-p" | "--pages" => flags.pages = Some(args.parse(&flag, PageRange::parse)?),
+                    "--border-width" => {
+                        flags.border_width = Some(args.parse(&flag, parse_non_negative)?)
+                    }
+                    "--layout" => flags.layout = Some(args.parse(&flag, parse_layout)?),
+                    "-t" | "--threshold" => {
+                        flags.threshold = Some(args.parse(&flag, parse_non_negative)?)
+                    }
+                    "--annotate" => flags.annotate = Some(true),
+                    "-e" | "--expectations" => {
+                        flags.expectations =
+                            Some(args.parse(&flag, |path| Expectations::load(path))?)
+                    }
+                    "-c" | "--config" => config = Some(PathBuf::from(args.value(&flag)?)),
+                    "--profile" => profile = Some(args.value(&flag)?),
+                    "--clean" => run.clean = true,
+                    "--watch" => run.watch = true,
+                    "--poll-interval" => {
+                        run.poll_interval =
+                            args.parse(&flag, |v| parse_positive(v).map(Duration::from_secs_f32))?
+                    }
+                    _ if run.cache.parse_flag(&flag, args)? => {}
+                    _ => return Err(format!("unknown option `{flag}`")),
+                },
+            }
+        }
+
+        let config = config.or_else(|| {
+            let default = PathBuf::from(DEFAULT_CONFIG);
+            default.is_file().then_some(default)
+        });
+
+        let mut settings = match (&config, &profile) {
+            (Some(config), profile) => config::load(config, profile.as_deref())?,
+            (None, Some(_)) => {
+                return Err(format!(
+                    "`--profile` requires a config file, but there is no {DEFAULT_CONFIG}"
+                ))
+            }
+            (None, None) => Profile::default(),
+        };
+
+        settings.merge(flags);
+        settings.apply(&mut run);
+
+        if run.inputs.is_empty() {
+            return Err("no input files or directories given".to_string());
+        }
+
+        Ok(run)
+    }
+
+    /// The options of the JUnit report.
+    pub fn junit_options(&self) -> JunitOptions {
+        JunitOptions {
+            threshold: self.threshold,
+            backend_thresholds: self.backend_thresholds.clone(),
+            expectations: self.expectations.clone(),
+        }
+    }
+}
+
+/// What the pages of a backend are compared against.
+pub enum Reference {
+    /// The pages of another backend.
+    Backend(Backend),
+    /// Golden images in a directory.
+    Golden(PathBuf),
+}
+
+/// The arguments of `sitro diff`.
+pub struct DiffArgs {
+    /// The PDF file or directory to render.
+    pub input: PathBuf,
+    /// The backend to render with.
+    pub backend: Backend,
+    /// What the backend is compared against.
+    pub reference: Reference,
+    /// The directory the diff images are written to.
+    pub output: PathBuf,
+    /// The scale to render at.
+    pub scale: f32,
+    /// The pages to compare.
+    pub pages: PageRange,
+    /// The fraction of differing pixels above which a page fails.
+    pub threshold: f32,
+    /// The maximum difference of a color channel for pixels to be considered equal.
+    pub tolerance: u8,
+    /// Whether differences caused by anti-aliasing are ignored.
+    pub ignore_anti_aliasing: bool,
+    /// Where renders are cached.
+    pub cache: CacheDir,
+}
+
+impl DiffArgs {
+    pub fn parse(args: &mut Args) -> Result<Self, String> {
+        let mut positional = vec![];
+        let mut output = PathBuf::from("diff");
+        let mut scale = 1.0;
+        let mut pages = PageRange::default();
+        let mut threshold = 0.01;
+        let mut tolerance = 8;
+        let mut ignore_anti_aliasing = false;
+        let mut cache = CacheDir::Default;
+
+        while let Some(arg) = args.next()? {
+            match arg {
+                Arg::Positional(value) => positional.push(value),
+                Arg::Flag(flag) => match flag.as_str() {
+                    "-o" | "--output" => output = PathBuf::from(args.value(&flag)?),
+                    "-s" | "--scale" => scale = args.parse(&flag, parse_positive)?,
+                    "-p" | "--pages" => pages = args.parse(&flag, PageRange::parse)?,
+                    "-t" | "--threshold" => threshold = args.parse(&flag, parse_non_negative)?,
+                    "--tolerance" => tolerance = args.parse(&flag, parse_tolerance)?,
+                    "--ignore-anti-aliasing" => ignore_anti_aliasing = true,
+                    _ if cache.parse_flag(&flag, args)? => {}
+                    _ => return Err(format!("unknown option `{flag}`")),
+                },
+            }
+        }
+
+        let [input, backend, reference] = <[String; 3]>::try_from(positional).map_err(|_| {
+            "expected an input, a backend and a backend or golden directory".to_string()
+        })?;
+
+        let backend = parse_backend(&backend)?;
+        let reference = match Backend::from_name(&reference) {
+            Some(_) => Reference::Backend(parse_backend(&reference)?),
+            None if PathBuf::from(&reference).is_dir() => {
+                Reference::Golden(PathBuf::from(reference))
+            }
+            None => {
+                return Err(format!(
+                    "`{reference}` is neither a backend nor a directory with golden images"
+                ))
+            }
+        };
+
+        Ok(Self {
+            input: PathBuf::from(input),
+            backend,
+            reference,
+            output,
+            scale,
+            pages,
+            threshold,
+            tolerance,
+            ignore_anti_aliasing,
+            cache,
+        })
+    }
+}
+
+/// The arguments of `sitro minimize`.
+#[cfg(feature = "hayro")]
+pub struct MinimizeArgs {
+    /// The PDF file to minimize.
+    pub input: PathBuf,
+    /// The two backends that diverge on the PDF.
+    pub backends: [Backend; 2],
+    /// The path the minimized PDF is written to.
+    pub output: PathBuf,
+    /// The scale to render at.
+    pub scale: f32,
+    /// The fraction of differing pixels above which the backends diverge on a page.
+    pub threshold: f32,
+    /// The maximum difference of a color channel for pixels to be considered equal.
+    pub tolerance: u8,
+    /// Whether differences caused by anti-aliasing are ignored.
+    pub ignore_anti_aliasing: bool,
+    /// Where renders are cached.
+    pub cache: CacheDir,
+}
+
+#[cfg(feature = "hayro")]
+impl MinimizeArgs {
+    pub fn parse(args: &mut Args) -> Result<Self, String> {
+        let mut positional = vec![];
+        let mut output = None;
+        let mut scale = 1.0;
+        let mut threshold = 0.01;
+        let mut tolerance = 8;
+        let mut ignore_anti_aliasing = false;
+        let mut cache = CacheDir::Default;
+
+        while let Some(arg) = args.next()? {
+            match arg {
+                Arg::Positional(value) => positional.push(value),
+                Arg::Flag(flag) => match flag.as_str() {
+                    "-o" | "--output" => output = Some(PathBuf::from(args.value(&flag)?)),
+                    "-s" | "--scale" => scale = args.parse(&flag, parse_positive)?,
+                    "-t" | "--threshold" => threshold = args.parse(&flag, parse_non_negative)?,
+                    "--tolerance" => tolerance = args.parse(&flag, parse_tolerance)?,
+                    "--ignore-anti-aliasing" => ignore_anti_aliasing = true,
+                    _ if cache.parse_flag(&flag, args)? => {}
+                    _ => return Err(format!("unknown option `{flag}`")),
+                },
+            }
+        }
+
+        let [input, first, second] = <[String; 3]>::try_from(positional)
+            .map_err(|_| "expected an input and two backends".to_string())?;
+
+        let backends = [parse_backend(&first)?, parse_backend(&second)?];
+
+        if backends[0] == backends[1] {
+            return Err(format!(
+                "expected two different backends, got `{first}` twice"
+            ));
+        }
+
+        let input = PathBuf::from(input);
+        let output = output.unwrap_or_else(|| {
+            let stem = input.file_stem().unwrap_or_default().to_string_lossy();
+            PathBuf::from(format!("{stem}-minimized.pdf"))
+        });
+
+        Ok(Self {
+            input,
+            backends,
+            output,
+            scale,
+            threshold,
+            tolerance,
+            ignore_anti_aliasing,
+            cache,
+        })
+    }
+}
+
+/// The arguments of `sitro compare`.
+pub struct CompareArgs {
+    /// The output directory of the run before.
+    pub before: PathBuf,
+    /// The output directory of the run after.
+    pub after: PathBuf,
+    /// The directory the report of the changes is written to.
+    pub output: PathBuf,
+    /// The backends to compare, or `None` for all.
+    pub backends: Option<Vec<Backend>>,
+    /// The fraction of differing pixels above which a page counts as changed.
+    pub threshold: f32,
+    /// The maximum difference of a color channel for pixels to be considered equal.
+    pub tolerance: u8,
+}
+
+impl CompareArgs {
+    pub fn parse(args: &mut Args) -> Result<Self, String> {
+        let mut positional = vec![];
+        let mut output = PathBuf::from("changes");
+        let mut backends = None;
+        let mut threshold = 0.0;
+        let mut tolerance = DiffOptions::default().tolerance;
+
+        while let Some(arg) = args.next()? {
+            match arg {
+                Arg::Positional(value) => positional.push(PathBuf::from(value)),
+                Arg::Flag(flag) => match flag.as_str() {
+                    "-o" | "--output" => output = PathBuf::from(args.value(&flag)?),
+                    "-b" | "--backends" => backends = Some(args.parse(&flag, parse_backends)?),
+                    "-t" | "--threshold" => threshold = args.parse(&flag, parse_non_negative)?,
+                    "--tolerance" => tolerance = args.parse(&flag, parse_tolerance)?,
+                    _ => return Err(format!("unknown option `{flag}`")),
+                },
+            }
+        }
+
+        let [before, after] = <[PathBuf; 2]>::try_from(positional)
+            .map_err(|_| "expected the output directories of two runs".to_string())?;
+
+        for run in [&before, &after] {
+            if !run.join("report.json").is_file() {
+                return Err(format!(
+                    "{} is not the output of a run, it has no report.json",
+                    run.display()
+                ));
+            }
+        }
+
+        Ok(Self {
+            before,
+            after,
+            output,
+            backends,
+            threshold,
+            tolerance,
+        })
+    }
+}
+
+/// What `sitro cache` should do.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum CacheAction {
+    /// Print the location and size of the cache.
+    Info,
+    /// Remove all entries from the cache.
+    Clear,
+}
+
+/// The arguments of `sitro cache`.
+pub struct CacheArgs {
+    /// What to do with the cache.
+    pub action: CacheAction,
+    /// The cache directory.
+    pub dir: PathBuf,
+}
+
+impl CacheArgs {
+    pub fn parse(args: &mut Args) -> Result<Self, String> {
+        let mut action = None;
+        let mut dir = None;
+
+        while let Some(arg) = args.next()? {
+            match arg {
+                Arg::Positional(value) if action.is_none() => {
+                    action = Some(match value.as_str() {
+                        "info" => CacheAction::Info,
+                        "clear" => CacheAction::Clear,
+                        _ => return Err(format!("unknown cache command `{value}`")),
+                    })
+                }
+                Arg::Positional(value) => return Err(format!("unexpected argument `{value}`")),
+                Arg::Flag(flag) if flag == "--cache-dir" => {
+                    dir = Some(PathBuf::from(args.value(&flag)?))
+                }
+                Arg::Flag(flag) => return Err(format!("unknown option `{flag}`")),
+            }
+        }
+
+        Ok(Self {
+            action: action.ok_or("expected `info` or `clear`")?,
+            dir: dir
+                .or_else(DiskCache::default_dir)
+                .ok_or("no default cache directory, use `--cache-dir`")?,
+        })
+    }
+}
+
+/// The arguments of `sitro serve`.
+pub struct ServeArgs {
+    /// The port to listen on.
+    pub port: u16,
+    /// Where renders are cached.
+    pub cache: CacheDir,
+}
+
+impl ServeArgs {
+    pub fn parse(args: &mut Args) -> Result<Self, String> {
+        let mut serve = Self {
+            port: 8000,
+            cache: CacheDir::Default,
+        };
+
+        while let Some(arg) = args.next()? {
+            match arg {
+                Arg::Positional(value) => return Err(format!("unexpected argument `{value}`")),
+                Arg::Flag(flag) => match flag.as_str() {
+                    "--port" => {
+                        serve.port = args.parse(&flag, |v| {
+                            v.parse::<u16>()
+                                .map_err(|_| "expected a port number".to_string())
+                        })?
+                    }
+                    _ if serve.cache.parse_flag(&flag, args)? => {}
+                    _ => return Err(format!("unknown option `{flag}`")),
+                },
+            }
+        }
+
+        Ok(serve)
+    }
+}
@@ -0,0 +1,54 @@
+use super::args::{CacheAction, CacheArgs, CacheDir};
+use super::EXIT_SETUP;
+use sitro::{set_render_cache, CacheOptions, DiskCache, Renderer, RENDER_INSTANCE};
+use std::process::ExitCode;
+
+/// Set up `RENDER_INSTANCE` with the render cache in the given directory.
+pub fn renderer(cache: &CacheDir) -> Result<&'static Renderer, String> {
+    let cache = match cache.dir() {
+        Some(dir) => Some(DiskCache::open(dir, CacheOptions::from_env()?)?),
+        None => None,
+    };
+
+    set_render_cache(cache)?;
+    RENDER_INSTANCE.as_ref().map_err(Clone::clone)
+}
+
+/// Print information about the render cache or clear it.
+pub fn cache(args: &CacheArgs) -> ExitCode {
+    let options = CacheOptions { max_size: None };
+    let cache = match DiskCache::open(&args.dir, options) {
+        Ok(cache) => cache,
+        Err(e) => {
+            eprintln!("error: {e}");
+            return ExitCode::from(EXIT_SETUP);
+        }
+    };
+
+    let (len, size) = (cache.len(), megabytes(cache.size()));
+
+    match args.action {
+        CacheAction::Info => {
+            println!("directory: {}", cache.dir().display());
+            println!("entries: {len}");
+            println!("size: {size:.1} MB");
+        }
+        CacheAction::Clear => {
+            if let Err(e) = cache.clear() {
+                eprintln!("error: {e}");
+                return ExitCode::from(EXIT_SETUP);
+            }
+
+            println!(
+                "removed {len} entries ({size:.1} MB) from {}",
+                cache.dir().display()
+            );
+        }
+    }
+
+    ExitCode::SUCCESS
+}
+
+fn megabytes(bytes: u64) -> f64 {
+    bytes as f64 / (1024.0 * 1024.0)
+}
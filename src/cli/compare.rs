@@ -0,0 +1,85 @@
+use super::args::CompareArgs;
+use super::{EXIT_FAILURE, EXIT_USAGE};
+use sitro::{
+    changes_by_backend, compare_runs, write_change_report, Backend, ChangeOptions, DiffOptions,
+    StoredRun,
+};
+use std::process::ExitCode;
+
+/// Compare two stored runs and report how the output of each backend changed.
+pub fn compare(args: &CompareArgs) -> ExitCode {
+    let runs = StoredRun::open(&args.before)
+        .and_then(|before| Ok((before, StoredRun::open(&args.after)?)));
+
+    let (before, after) = match runs {
+        Ok(runs) => runs,
+        Err(e) => {
+            eprintln!("error: {e}");
+            return ExitCode::from(EXIT_USAGE);
+        }
+    };
+
+    let options = ChangeOptions {
+        diff: DiffOptions {
+            tolerance: args.tolerance,
+            ..DiffOptions::default()
+        },
+        threshold: args.threshold,
+    };
+
+    let selected = |backend: &Backend| {
+        args.backends
+            .as_ref()
+            .is_none_or(|backends| backends.contains(backend))
+    };
+
+    let mut changes = match compare_runs(&before, &after, &options) {
+        Ok(changes) => changes,
+        Err(e) => {
+            eprintln!("error: {e}");
+            return ExitCode::from(EXIT_USAGE);
+        }
+    };
+    changes.retain(|change| selected(&change.backend));
+
+    let groups = changes_by_backend(&changes);
+    let mut backends = vec![];
+
+    for file in before.files.iter().chain(&after.files) {
+        for summary in &file.backends {
+            if selected(&summary.backend) && !backends.contains(&summary.backend) {
+                backends.push(summary.backend.clone());
+            }
+        }
+    }
+
+    Backend::sort(&mut backends);
+
+    for backend in backends {
+        match groups.iter().find(|(b, _)| *b == backend) {
+            Some((_, group)) => {
+                let plural = if group.len() == 1 { "" } else { "s" };
+                println!("{}: {} change{plural}", backend.name(), group.len());
+
+                for change in group {
+                    println!("  {change}");
+                }
+            }
+            None => println!("{}: no changes", backend.name()),
+        }
+    }
+
+    match write_change_report(&args.output, &before, &after, &changes, &options) {
+        Ok(path) => println!("\nwrote report to {}", path.display()),
+        Err(e) => {
+            eprintln!("error: {e}");
+            return ExitCode::from(EXIT_FAILURE);
+        }
+    }
+
+    if changes.is_empty() {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::from(EXIT_FAILURE)
+    }
+}
@@ -0,0 +1,303 @@
+use super::args::{
+    parse_backend, parse_layout, parse_non_negative, parse_positive, PageRange, RunArgs,
+};
+use glob::Pattern;
+use sitro::{Backend, Expectation, Expectations, KnownDivergence, Layout};
+use std::fs;
+use std::path::{Path, PathBuf};
+use toml::{Table, Value};
+
+/// The config file that is used if none is given.
+pub const DEFAULT_CONFIG: &str = "sitro.toml";
+
+/// Settings of a corpus run that can come from the config file or the command line.
+///
+/// Unset values fall back to the top level of the config file and then to the defaults
+/// of [`RunArgs`].
+#[derive(Clone, Debug, Default)]
+pub struct Profile {
+    pub inputs: Option<Vec<PathBuf>>,
+    pub output: Option<PathBuf>,
+    pub backends: Option<Vec<Backend>>,
+    pub scale: Option<f32>,
+    pub pages: Option<PageRange>,
+    pub border_width: Option<f32>,
+    pub layout: Option<Layout>,
+    pub annotate: Option<bool>,
+    pub threshold: Option<f32>,
+    pub expectations: Option<Expectations>,
+    pub backend_options: Vec<(Backend, BackendOptions)>,
+}
+
+/// Settings of a single backend.
+#[derive(Clone, Debug, Default)]
+pub struct BackendOptions {
+    /// The threshold of the backend, replacing the global one.
+    pub threshold: Option<f32>,
+    /// Glob patterns of the names of files the backend is known to fail on.
+    pub known_failures: Option<Vec<String>>,
+}
+
+impl Profile {
+    /// Replace the values of this profile with the ones set in `other`.
+    pub fn merge(&mut self, other: Profile) {
+        macro_rules! merge {
+            ($($field:ident),*) => {
+                $(if other.$field.is_some() {
+                    self.$field = other.$field;
+                })*
+            };
+        }
+
+        merge!(
+            inputs,
+            output,
+            backends,
+            scale,
+            pages,
+            border_width,
+            layout,
+            annotate,
+            threshold,
+            expectations
+        );
+
+        for (backend, options) in other.backend_options {
+            match self.backend_options.iter_mut().find(|(b, _)| *b == backend) {
+                Some((_, existing)) => {
+                    if options.threshold.is_some() {
+                        existing.threshold = options.threshold;
+                    }
+                    if options.known_failures.is_some() {
+                        existing.known_failures = options.known_failures;
+                    }
+                }
+                None => self.backend_options.push((backend, options)),
+            }
+        }
+    }
+
+    /// Apply the values that are set to the arguments of a run.
+    pub fn apply(self, run: &mut RunArgs) {
+        macro_rules! apply {
+            ($($field:ident),*) => {
+                $(if let Some(value) = self.$field {
+                    run.$field = value;
+                })*
+            };
+        }
+
+        apply!(
+            inputs,
+            output,
+            backends,
+            scale,
+            pages,
+            border_width,
+            layout,
+            annotate,
+            threshold
+        );
+
+        if let Some(expectations) = self.expectations {
+            run.expectations
+                .divergences
+                .extend(expectations.divergences);
+        }
+
+        for (backend, options) in self.backend_options {
+            if let Some(threshold) = options.threshold {
+                run.backend_thresholds.push((backend.clone(), threshold));
+            }
+
+            for file in options.known_failures.unwrap_or_default() {
+                run.expectations.push(KnownDivergence {
+                    backend: backend.clone(),
+                    file,
+                    page: None,
+                    expectation: Expectation::Failure,
+                    reason: None,
+                });
+            }
+        }
+    }
+}
+
+/// Load a config file and return the profile with the given name, merged with the top
+/// level of the file, or just the top level if no name is given.
+///
+/// Relative paths in the file are resolved relative to the directory of the file.
+pub fn load(path: &Path, name: Option<&str>) -> Result<Profile, String> {
+    let text = fs::read_to_string(path)
+        .map_err(|e| format!("failed to read config {}: {e}", path.display()))?;
+    let table = text
+        .parse::<Table>()
+        .map_err(|e| format!("failed to parse config {}: {e}", path.display()))?;
+    let dir = path.parent().unwrap_or(Path::new(""));
+    let error = |e: String| format!("{}: {e}", path.display());
+
+    let mut profile = parse_profile(&table, "the top level", dir).map_err(error)?;
+
+    let profiles = match table.get("profile") {
+        Some(Value::Table(profiles)) => profiles.clone(),
+        Some(_) => return Err(error("`profile` must be a table".to_string())),
+        None => Table::new(),
+    };
+
+    if let Some(name) = name {
+        let Some(selected) = profiles.get(name) else {
+            let names = profiles.keys().cloned().collect::<Vec<_>>();
+            return Err(error(if names.is_empty() {
+                format!("unknown profile `{name}`, there are no profiles")
+            } else {
+                format!(
+                    "unknown profile `{name}` (available profiles are {})",
+                    names.join(", ")
+                )
+            }));
+        };
+
+        let Value::Table(selected) = selected else {
+            return Err(error(format!("profile `{name}` must be a table")));
+        };
+
+        if selected.contains_key("profile") {
+            return Err(error(format!("profile `{name}` can't contain profiles")));
+        }
+
+        profile.merge(parse_profile(selected, &format!("profile `{name}`"), dir).map_err(error)?);
+    }
+
+    Ok(profile)
+}
+
+fn parse_profile(table: &Table, context: &str, dir: &Path) -> Result<Profile, String> {
+    let mut profile = Profile::default();
+
+    for (key, value) in table {
+        let invalid = |e: String| format!("invalid value for `{key}` in {context}: {e}");
+
+        match key.as_str() {
+            "inputs" => {
+                let inputs = strings(value).map_err(invalid)?;
+                profile.inputs = Some(inputs.iter().map(|input| dir.join(input)).collect());
+            }
+            "output" => profile.output = Some(dir.join(string(value).map_err(invalid)?)),
+            "backends" => {
+                let backends = strings(value)
+                    .and_then(|names| names.iter().map(|name| parse_backend(name)).collect())
+                    .map_err(invalid)?;
+                profile.backends = Some(backends);
+            }
+            "scale" => profile.scale = Some(number(value, parse_positive).map_err(invalid)?),
+            "pages" => {
+                let pages = match value {
+                    Value::Integer(page) => page.to_string(),
+                    _ => string(value).map_err(invalid)?,
+                };
+                profile.pages = Some(PageRange::parse(&pages).map_err(invalid)?);
+            }
+            "border_width" => {
+                profile.border_width = Some(number(value, parse_non_negative).map_err(invalid)?)
+            }
+            "layout" => {
+                profile.layout = Some(
+                    string(value)
+                        .and_then(|layout| parse_layout(&layout))
+                        .map_err(invalid)?,
+                )
+            }
+            "expectations" => {
+                let path = dir.join(string(value).map_err(invalid)?);
+                profile.expectations = Some(Expectations::load(path).map_err(invalid)?);
+            }
+            "annotate" => profile.annotate = Some(boolean(value).map_err(invalid)?),
+            "threshold" => {
+                profile.threshold = Some(number(value, parse_non_negative).map_err(invalid)?)
+            }
+            "backend" => {
+                let Value::Table(backends) = value else {
+                    return Err(invalid("expected a table of backends".to_string()));
+                };
+
+                for (name, options) in backends {
+                    let backend = parse_backend(name).map_err(invalid)?;
+                    let context = format!("backend `{name}` in {context}");
+                    let options = parse_backend_options(options, &context)?;
+                    profile.backend_options.push((backend, options));
+                }
+            }
+            // Profiles are only allowed at the top level, which is checked by the caller.
+            "profile" => {}
+            _ => return Err(format!("unknown key `{key}` in {context}")),
+        }
+    }
+
+    Ok(profile)
+}
+
+fn parse_backend_options(value: &Value, context: &str) -> Result<BackendOptions, String> {
+    let Value::Table(table) = value else {
+        return Err(format!("{context} must be a table"));
+    };
+
+    let mut options = BackendOptions::default();
+
+    for (key, value) in table {
+        let invalid = |e: String| format!("invalid value for `{key}` in {context}: {e}");
+
+        match key.as_str() {
+            "threshold" => {
+                options.threshold = Some(number(value, parse_non_negative).map_err(invalid)?)
+            }
+            "known_failures" => {
+                let patterns = strings(value).map_err(invalid)?;
+
+                for pattern in &patterns {
+                    Pattern::new(pattern)
+                        .map_err(|e| invalid(format!("invalid pattern `{pattern}`: {e}")))?;
+                }
+
+                options.known_failures = Some(patterns);
+            }
+            _ => return Err(format!("unknown key `{key}` in {context}")),
+        }
+    }
+
+    Ok(options)
+}
+
+fn string(value: &Value) -> Result<String, String> {
+    value
+        .as_str()
+        .map(str::to_string)
+        .ok_or_else(|| "expected a string".to_string())
+}
+
+fn strings(value: &Value) -> Result<Vec<String>, String> {
+    value
+        .as_array()
+        .and_then(|array| {
+            array
+                .iter()
+                .map(|v| v.as_str().map(str::to_string))
+                .collect()
+        })
+        .ok_or_else(|| "expected an array of strings".to_string())
+}
+
+fn boolean(value: &Value) -> Result<bool, String> {
+    value
+        .as_bool()
+        .ok_or_else(|| "expected true or false".to_string())
+}
+
+/// Parse a number with the parser of the corresponding command-line flag, so that both
+/// are validated the same way.
+fn number(value: &Value, parse: fn(&str) -> Result<f32, String>) -> Result<f32, String> {
+    match value {
+        Value::Integer(n) => parse(&n.to_string()),
+        Value::Float(n) => parse(&n.to_string()),
+        _ => Err("expected a number".to_string()),
+    }
+}
@@ -0,0 +1,207 @@
+use super::args::{DiffArgs, Reference};
+use super::cache;
+use super::input::{collect_inputs, render, Input};
+use super::{EXIT_FAILURE, EXIT_SETUP, EXIT_USAGE};
+use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
+use sitro::{diff_pixmaps, DiffMode, DiffOptions, RenderOptions, Renderer};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use tiny_skia::Pixmap;
+
+/// The result of comparing a single page.
+struct PageResult {
+    index: usize,
+    outcome: Result<Comparison, String>,
+}
+
+struct Comparison {
+    ratio: f32,
+    differing: usize,
+    sizes: ((u32, u32), (u32, u32)),
+    diff_path: PathBuf,
+}
+
+/// Compare the renders of a backend with another backend or golden images.
+pub fn diff(args: &DiffArgs) -> ExitCode {
+    let inputs = match collect_inputs(std::slice::from_ref(&args.input)) {
+        Ok(inputs) => inputs,
+        Err(e) => {
+            eprintln!("error: {e}");
+            return ExitCode::from(EXIT_USAGE);
+        }
+    };
+
+    let instance = match cache::renderer(&args.cache) {
+        Ok(instance) => instance,
+        Err(e) => {
+            eprintln!("error: failed to set up the renderer: {e}");
+            return ExitCode::from(EXIT_SETUP);
+        }
+    };
+
+    let results = inputs
+        .par_iter()
+        .map(|input| compare_file(instance, input, args))
+        .collect::<Vec<_>>();
+
+    let reference = match &args.reference {
+        Reference::Backend(backend) => backend.name(),
+        Reference::Golden(dir) => dir.display().to_string(),
+    };
+
+    let mut compared = 0;
+    let mut failed = 0;
+
+    for (input, result) in inputs.iter().zip(results) {
+        let name = input.name.display();
+
+        let pages = match result {
+            Ok(pages) => pages,
+            Err(e) => {
+                println!("{name}: {e} FAIL");
+                failed += 1;
+                continue;
+            }
+        };
+
+        for page in pages {
+            compared += 1;
+            let number = page.index + 1;
+
+            match page.outcome {
+                Ok(comparison) => {
+                    let exceeds = comparison.ratio > args.threshold;
+                    let ((w1, h1), (w2, h2)) = comparison.sizes;
+                    let sizes = if (w1, h1) != (w2, h2) {
+                        format!(", sizes {w1}x{h1} vs {w2}x{h2}")
+                    } else {
+                        String::new()
+                    };
+
+                    println!(
+                        "{name} page {number}: {:.2}% differ ({} pixels{sizes}) {}",
+                        comparison.ratio * 100.0,
+                        comparison.differing,
+                        if exceeds { "FAIL" } else { "ok" }
+                    );
+
+                    if exceeds {
+                        println!("  diff: {}", comparison.diff_path.display());
+                        failed += 1;
+                    }
+                }
+                Err(e) => {
+                    println!("{name} page {number}: {e} FAIL");
+                    failed += 1;
+                }
+            }
+        }
+    }
+
+    println!(
+        "\ncompared {} with {reference}: {failed} of {compared} pages exceed the threshold of {:.2}% or failed",
+        args.backend.name(),
+        args.threshold * 100.0
+    );
+
+    if failed == 0 {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::from(EXIT_FAILURE)
+    }
+}
+
+fn compare_file(
+    instance: &Renderer,
+    input: &Input,
+    args: &DiffArgs,
+) -> Result<Vec<PageResult>, String> {
+    let file = fs::read(&input.path)
+        .map_err(|e| format!("failed to read {}: {e}", input.path.display()))?;
+    let options = RenderOptions { scale: args.scale };
+    let stem = input.name.with_extension("");
+
+    let render = |backend| {
+        render(instance, backend, &file, &options, &args.pages)
+            .result
+            .map_err(|e| format!("{} failed: {e}", backend.name()))
+    };
+
+    let (pages, reference) = match &args.reference {
+        Reference::Backend(reference) => {
+            let (pages, reference) = rayon::join(|| render(&args.backend), || render(reference));
+            let reference = reference?.into_iter().map(Ok).collect::<Vec<_>>();
+            (pages?, reference)
+        }
+        Reference::Golden(dir) => {
+            let pages = render(&args.backend)?;
+            let reference = (0..pages.len())
+                .map(|i| load_golden(&golden_path(dir, &stem, args.pages.first + i)))
+                .collect();
+            (pages, reference)
+        }
+    };
+
+    let diff_options = DiffOptions {
+        tolerance: args.tolerance,
+        mode: if args.ignore_anti_aliasing {
+            DiffMode::IgnoreAntiAliasing
+        } else {
+            DiffMode::Exact
+        },
+    };
+
+    let count = pages.len().max(reference.len());
+    let mut results = vec![];
+
+    for i in 0..count {
+        let index = args.pages.first + i;
+        let outcome = match (pages.get(i), reference.get(i)) {
+            (Some(page), Some(Ok(reference))) => {
+                let diff = diff_pixmaps(page, reference, &diff_options);
+                let diff_path = args
+                    .output
+                    .join(format!("{}-{index}.diff.png", stem.display()));
+
+                if let Some(dir) = diff_path.parent() {
+                    let _ = fs::create_dir_all(dir);
+                }
+
+                diff.to_pixmap(page)
+                    .save_png(&diff_path)
+                    .map_err(|e| format!("failed to write {}: {e}", diff_path.display()))
+                    .map(|_| Comparison {
+                        ratio: diff.ratio(),
+                        differing: diff.differing_pixels(),
+                        sizes: (
+                            (page.width(), page.height()),
+                            (reference.width(), reference.height()),
+                        ),
+                        diff_path,
+                    })
+            }
+            (Some(_), Some(Err(e))) => Err(e.clone()),
+            (Some(_), None) => Err("missing in the reference".to_string()),
+            (None, _) => Err(format!("missing in {}", args.backend.name())),
+        };
+
+        results.push(PageResult { index, outcome });
+    }
+
+    Ok(results)
+}
+
+/// The path of the golden image of a page, like `dir/file-0.png` for the first page of
+/// `dir/file.pdf`.
+fn golden_path(dir: &Path, stem: &Path, index: usize) -> PathBuf {
+    dir.join(format!("{}-{index}.png", stem.display()))
+}
+
+fn load_golden(path: &Path) -> Result<Pixmap, String> {
+    if !path.exists() {
+        return Err(format!("missing golden image {}", path.display()));
+    }
+
+    Pixmap::load_png(path).map_err(|e| format!("failed to read {}: {e}", path.display()))
+}
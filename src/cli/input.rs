@@ -0,0 +1,95 @@
+use super::args::PageRange;
+use sitro::{Backend, BackendRun, RenderOptions, Renderer};
+use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
+
+/// A PDF file to render.
+pub struct Input {
+    /// The path of the file.
+    pub path: PathBuf,
+    /// The name of the file in the output, relative to the input directory it was found in.
+    pub name: PathBuf,
+}
+
+/// Whether a path is a glob pattern like `pdf/**/*.pdf`.
+fn is_pattern(path: &Path) -> bool {
+    path.to_string_lossy().contains(['*', '?', '['])
+}
+
+/// The part of a path before the first component with a glob pattern.
+pub fn base(path: &Path) -> PathBuf {
+    path.components()
+        .take_while(|component| !is_pattern(Path::new(component.as_os_str())))
+        .collect()
+}
+
+fn is_pdf(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("pdf"))
+}
+
+/// Find all PDF files in the given files, directories and glob patterns.
+///
+/// Files found in a directory or by a pattern are named relative to the directory or
+/// the part of the pattern before the first wildcard.
+pub fn collect_inputs(paths: &[PathBuf]) -> Result<Vec<Input>, String> {
+    let mut inputs = vec![];
+
+    for path in paths {
+        if is_pattern(path) {
+            let pattern = path.to_string_lossy();
+            let base = base(path);
+            let paths =
+                glob::glob(&pattern).map_err(|e| format!("invalid pattern `{pattern}`: {e}"))?;
+
+            for path in paths {
+                let path =
+                    path.map_err(|e| format!("failed to read {}: {e}", e.path().display()))?;
+
+                if path.is_file() && is_pdf(&path) {
+                    let name = path.strip_prefix(&base).unwrap_or(&path).to_path_buf();
+                    inputs.push(Input { path, name });
+                }
+            }
+        } else if path.is_file() {
+            let name = path.file_name().map(PathBuf::from).unwrap_or(path.clone());
+            inputs.push(Input {
+                path: path.clone(),
+                name,
+            });
+        } else if path.is_dir() {
+            for entry in WalkDir::new(path).sort_by_file_name() {
+                let entry = entry.map_err(|e| format!("failed to read {}: {e}", path.display()))?;
+
+                if entry.file_type().is_file() && is_pdf(entry.path()) {
+                    let name = entry.path().strip_prefix(path).unwrap_or(entry.path());
+                    inputs.push(Input {
+                        path: entry.path().to_path_buf(),
+                        name: name.to_path_buf(),
+                    });
+                }
+            }
+        } else {
+            return Err(format!("{} does not exist", path.display()));
+        }
+    }
+
+    Ok(inputs)
+}
+
+/// Render a PDF with a backend and keep only the selected pages.
+pub fn render(
+    renderer: &Renderer,
+    backend: &Backend,
+    buf: &[u8],
+    options: &RenderOptions,
+    pages: &PageRange,
+) -> BackendRun {
+    let mut run = BackendRun::render(renderer, backend, buf, options);
+
+    if let Ok(rendered) = &mut run.result {
+        *rendered = pages.select(std::mem::take(rendered));
+    }
+
+    run
+}
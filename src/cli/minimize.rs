@@ -0,0 +1,73 @@
+use super::args::MinimizeArgs;
+use super::cache;
+use super::{EXIT_FAILURE, EXIT_SETUP, EXIT_USAGE};
+use sitro::{DiffMode, DiffOptions, MinimizeOptions, RenderOptions};
+use std::fs;
+use std::process::ExitCode;
+
+/// Shrink a PDF on which two backends diverge and write the result.
+pub fn minimize(args: &MinimizeArgs) -> ExitCode {
+    let file = match fs::read(&args.input) {
+        Ok(file) => file,
+        Err(e) => {
+            eprintln!("error: failed to read {}: {e}", args.input.display());
+            return ExitCode::from(EXIT_USAGE);
+        }
+    };
+
+    let instance = match cache::renderer(&args.cache) {
+        Ok(instance) => instance,
+        Err(e) => {
+            eprintln!("error: failed to set up the renderer: {e}");
+            return ExitCode::from(EXIT_SETUP);
+        }
+    };
+
+    let options = MinimizeOptions {
+        render: RenderOptions { scale: args.scale },
+        diff: DiffOptions {
+            tolerance: args.tolerance,
+            mode: if args.ignore_anti_aliasing {
+                DiffMode::IgnoreAntiAliasing
+            } else {
+                DiffMode::Exact
+            },
+        },
+        threshold: args.threshold,
+        ..MinimizeOptions::default()
+    };
+
+    let minimized = instance.minimize(&file, args.backends.clone(), &options, |reduction| {
+        println!(
+            "{}: {} bytes, {:.2}% differ",
+            reduction.description,
+            reduction.size,
+            reduction.divergence * 100.0
+        );
+    });
+
+    let minimized = match minimized {
+        Ok(minimized) => minimized,
+        Err(e) => {
+            eprintln!("error: {e}");
+            return ExitCode::from(EXIT_FAILURE);
+        }
+    };
+
+    if let Err(e) = fs::write(&args.output, &minimized.pdf) {
+        eprintln!("error: failed to write {}: {e}", args.output.display());
+        return ExitCode::from(EXIT_FAILURE);
+    }
+
+    println!(
+        "\nwrote minimized PDF to {} ({} bytes, was {} bytes) after {} renders: {:.2}% differ, was {:.2}%",
+        args.output.display(),
+        minimized.pdf.len(),
+        file.len(),
+        minimized.attempts,
+        minimized.divergence * 100.0,
+        minimized.original_divergence * 100.0
+    );
+
+    ExitCode::SUCCESS
+}
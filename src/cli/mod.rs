@@ -0,0 +1,333 @@
+mod args;
+mod cache;
+mod compare;
+mod config;
+mod diff;
+mod input;
+#[cfg(feature = "hayro")]
+mod minimize;
+mod run;
+mod serve;
+mod watch;
+
+#[cfg(feature = "hayro")]
+use args::MinimizeArgs;
+use args::{Args, CacheArgs, CompareArgs, DiffArgs, RunArgs, ServeArgs};
+use std::path::Path;
+use std::process::ExitCode;
+
+/// Some renders or outputs failed.
+pub const EXIT_FAILURE: u8 = 1;
+/// The arguments or inputs were invalid.
+pub const EXIT_USAGE: u8 = 2;
+/// The renderer or output directory couldn't be set up.
+pub const EXIT_SETUP: u8 = 3;
+
+const USAGE: &str = "\
+Render PDFs with multiple backends and compare the results.
+
+Usage: sitro [OPTIONS] <INPUT>...
+       sitro diff [OPTIONS] <INPUT> <BACKEND> <BACKEND|GOLDEN_DIR>
+       sitro compare [OPTIONS] <BEFORE> <AFTER>
+       sitro minimize [OPTIONS] <INPUT> <BACKEND> <BACKEND>
+       sitro serve [OPTIONS]
+       sitro cache [--cache-dir <DIR>] <info|clear>
+
+Commands:
+  diff                       Compare a backend with another backend or golden images
+  compare                    Show how the output of each backend changed between runs
+  minimize                   Shrink a PDF on which two backends diverge for a bug report
+  serve                      Compare renders interactively in the browser
+  cache                      Show the size of the render cache or clear it
+
+Arguments:
+  <INPUT>...                 PDF files, directories containing PDF files or glob
+                             patterns like pdf/**/*.pdf
+
+Options:
+  -o, --output <DIR>         Directory to write composites and reports to [default: test]
+  -b, --backends <NAMES>     Comma-separated list of backends, like pdfium,hayro or
+                             pdfium@old for an image in $SITRO_DOCKER_IMAGES
+                             [default: all available]
+  -s, --scale <SCALE>        Scale to render pages at [default: 1]
+  -p, --pages <RANGE>        Pages to keep, like 3, 2-5, 2- or -5 [default: all]
+      --border-width <WIDTH> Border width relative to the smaller side of the page,
+                             0 for no border [default: 0.02]
+      --layout <LAYOUT>      Arrangement of the composites: auto, horizontal, vertical
+                             or grid:<COLUMNS> [default: auto]
+  -t, --threshold <FRACTION> Fraction of pixels a page may differ from the consensus
+                             before it fails in the JUnit report [default: 0.01]
+  -e, --expectations <FILE>  Registry of known divergences, which are reported as
+                             skipped instead of failing
+  -c, --config <FILE>        Config file to read settings from [default: sitro.toml
+                             if it exists]
+      --profile <NAME>       Profile of the config file to use
+      --annotate             Outline regions that differ from the consensus
+      --clean                Delete the output directory before rendering
+      --watch                Keep running and re-render files when they change
+      --poll-interval <SECS> How often to check for changes in watch mode [default: 1]
+      --cache-dir <DIR>      Directory to cache renders in [default: $SITRO_CACHE_DIR
+                             or ~/.cache/sitro]
+      --no-cache             Render everything, even if it is cached
+  -h, --help                 Print help
+  -V, --version              Print version
+
+Config file:
+  A TOML file with the same settings as the options above, plus per-backend
+  thresholds and known failures. Profiles override the top-level settings and
+  flags override both. Relative paths are relative to the config file.
+
+    inputs = [\"pdf/**/*.pdf\"]
+    backends = [\"hayro\", \"pdfium\", \"mupdf\"]
+    pages = \"1-3\"
+    expectations = \"expectations.toml\"
+
+    [backend.serenity]
+    threshold = 0.05
+    known_failures = [\"shading/*.pdf\"]
+
+    [profile.all-2x]
+    backends = [\"mupdf\", \"ghostscript\", \"pdfium\", \"poppler\", \"pdfjs\", \"pdfbox\", \"hayro\"]
+    scale = 2
+
+Backends: mupdf, ghostscript, pdfium, poppler, quartz, pdfjs, pdfbox, hayro, serenity
+
+Exit codes:
+  0  everything was rendered and written
+  1  some backends failed or outputs couldn't be written
+  2  invalid arguments or inputs
+  3  the renderer or output directory couldn't be set up
+";
+
+const DIFF_USAGE: &str = "\
+Compare the pages rendered by a backend with another backend or with golden images.
+
+Prints the share of differing pixels of each page and writes diff images that
+highlight the differences. Golden images are looked up as <DIR>/<NAME>-<PAGE>.png,
+where <NAME> is the file name without extension and <PAGE> starts at 0.
+
+Usage: sitro diff [OPTIONS] <INPUT> <BACKEND> <BACKEND|GOLDEN_DIR>
+
+Arguments:
+  <INPUT>                    PDF file or directory containing PDF files
+  <BACKEND>                  Backend to render with
+  <BACKEND|GOLDEN_DIR>       Backend or directory of golden images to compare with
+
+Options:
+  -o, --output <DIR>         Directory to write diff images to [default: diff]
+  -s, --scale <SCALE>        Scale to render pages at [default: 1]
+  -p, --pages <RANGE>        Pages to compare, like 3, 2-5, 2- or -5 [default: all]
+  -t, --threshold <FRACTION> Fraction of differing pixels above which a page fails
+                             [default: 0.01]
+      --tolerance <DELTA>    Maximum difference of a color channel for pixels to be
+                             considered equal [default: 8]
+      --ignore-anti-aliasing Don't count differences caused by anti-aliasing
+      --cache-dir <DIR>      Directory to cache renders in [default: $SITRO_CACHE_DIR
+                             or ~/.cache/sitro]
+      --no-cache             Render everything, even if it is cached
+  -h, --help                 Print help
+
+Exit codes:
+  0  no page exceeds the threshold
+  1  some pages exceed the threshold or failed to render
+  2  invalid arguments or inputs
+  3  the renderer couldn't be set up
+";
+
+const COMPARE_USAGE: &str = "\
+Compare the output directories of two runs and show the pages where the output of a
+backend changed, for example after updating the Docker image or hayro.
+
+Prints the changes grouped by backend and writes an HTML report with each changed page
+before and after the change and an image highlighting the changed pixels. Both runs
+must have been rendered at the same scale.
+
+Usage: sitro compare [OPTIONS] <BEFORE> <AFTER>
+
+Arguments:
+  <BEFORE>                   Output directory of the run before
+  <AFTER>                    Output directory of the run after
+
+Options:
+  -o, --output <DIR>         Directory to write the report to [default: changes]
+  -b, --backends <NAMES>     Comma-separated list of backends to compare [default: all]
+  -t, --threshold <FRACTION> Fraction of changed pixels above which a page counts as
+                             changed [default: 0]
+      --tolerance <DELTA>    Maximum difference of a color channel for pixels to be
+                             considered equal [default: 8]
+  -h, --help                 Print help
+
+Exit codes:
+  0  nothing changed
+  1  the output of some backend changed
+  2  invalid arguments or runs
+";
+
+#[cfg(feature = "hayro")]
+const MINIMIZE_USAGE: &str = "\
+Shrink a PDF on which two backends diverge to a minimal PDF on which they still
+diverge, for example for a bug report.
+
+Repeatedly removes pages, annotations, content stream operators and resources, renders
+the result with both backends and keeps each change as long as the fraction of
+differing pixels on some page still exceeds the threshold. Prints every change that is
+kept and writes the minimized PDF, with uncompressed content streams.
+
+Usage: sitro minimize [OPTIONS] <INPUT> <BACKEND> <BACKEND>
+
+Arguments:
+  <INPUT>                    PDF file to minimize
+  <BACKEND>                  Backends that diverge on the PDF
+
+Options:
+  -o, --output <FILE>        Path to write the minimized PDF to
+                             [default: <NAME>-minimized.pdf]
+  -s, --scale <SCALE>        Scale to render pages at [default: 1]
+  -t, --threshold <FRACTION> Fraction of differing pixels above which the backends
+                             diverge on a page [default: 0.01]
+      --tolerance <DELTA>    Maximum difference of a color channel for pixels to be
+                             considered equal [default: 8]
+      --ignore-anti-aliasing Don't count differences caused by anti-aliasing
+      --cache-dir <DIR>      Directory to cache renders in [default: $SITRO_CACHE_DIR
+                             or ~/.cache/sitro]
+      --no-cache             Render everything, even if it is cached
+  -h, --help                 Print help
+
+Exit codes:
+  0  the minimized PDF was written
+  1  the backends don't diverge, minimizing failed or the PDF couldn't be written
+  2  invalid arguments or inputs
+  3  the renderer couldn't be set up
+";
+
+const CACHE_USAGE: &str = "\
+Show the size of the render cache or clear it.
+
+Renders are cached by the hash of the PDF, the backend, the Docker image or hayro
+version and the scale, so unchanged files aren't rendered again. The cache is limited
+to $SITRO_CACHE_MAX_SIZE megabytes (4096 by default), evicting the least recently
+used renders first.
+
+Usage: sitro cache [OPTIONS] <info|clear>
+
+Commands:
+  info                       Print the location, number of entries and size of the cache
+  clear                      Remove all entries from the cache
+
+Options:
+      --cache-dir <DIR>      Cache directory [default: $SITRO_CACHE_DIR or ~/.cache/sitro]
+  -h, --help                 Print help
+";
+
+const SERVE_USAGE: &str = "\
+Serve a page on localhost to compare renders interactively in the browser.
+
+Upload a PDF, pick backends, a scale and a page, and view the renders side by side with
+zoom, swipe, onion skin and pixel diff. Other tools can render through the JSON API:
+
+  curl --data-binary @file.pdf 'http://localhost:8000/render?backend=pdfium&page=1'
+
+Usage: sitro serve [OPTIONS]
+
+Options:
+      --port <PORT>          Port to listen on [default: 8000]
+      --cache-dir <DIR>      Directory to cache renders in [default: $SITRO_CACHE_DIR
+                             or ~/.cache/sitro]
+      --no-cache             Render everything, even if it is cached
+  -h, --help                 Print help
+";
+
+pub fn main() -> ExitCode {
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    let rest = || args[1..].to_vec();
+
+    match args.first().map(String::as_str) {
+        Some("diff") => command(
+            rest(),
+            "sitro diff",
+            DIFF_USAGE,
+            true,
+            DiffArgs::parse,
+            diff::diff,
+        ),
+        Some("compare") => command(
+            rest(),
+            "sitro compare",
+            COMPARE_USAGE,
+            true,
+            CompareArgs::parse,
+            compare::compare,
+        ),
+        #[cfg(feature = "hayro")]
+        Some("minimize") => command(
+            rest(),
+            "sitro minimize",
+            MINIMIZE_USAGE,
+            true,
+            MinimizeArgs::parse,
+            minimize::minimize,
+        ),
+        #[cfg(not(feature = "hayro"))]
+        Some("minimize") => {
+            eprintln!("error: `sitro minimize` requires the `hayro` feature");
+            ExitCode::from(EXIT_USAGE)
+        }
+        Some("serve") => command(
+            rest(),
+            "sitro serve",
+            SERVE_USAGE,
+            false,
+            ServeArgs::parse,
+            serve::serve,
+        ),
+        Some("cache") => command(
+            rest(),
+            "sitro cache",
+            CACHE_USAGE,
+            true,
+            CacheArgs::parse,
+            cache::cache,
+        ),
+        _ if args.iter().any(|arg| arg == "-V" || arg == "--version") => {
+            println!("sitro {}", env!("CARGO_PKG_VERSION"));
+            ExitCode::SUCCESS
+        }
+        _ => {
+            // Without arguments, run the default config if there is one.
+            let needs_arguments = !Path::new(config::DEFAULT_CONFIG).is_file();
+            command(
+                args,
+                "sitro",
+                USAGE,
+                needs_arguments,
+                RunArgs::parse,
+                run::run,
+            )
+        }
+    }
+}
+
+/// Parse the arguments of a command and run it, or print its usage if help is requested
+/// or arguments are required but none were given.
+fn command<T>(
+    args: Vec<String>,
+    name: &str,
+    usage: &str,
+    needs_arguments: bool,
+    parse: fn(&mut Args) -> Result<T, String>,
+    run: fn(&T) -> ExitCode,
+) -> ExitCode {
+    if args.iter().any(|arg| arg == "-h" || arg == "--help") || (args.is_empty() && needs_arguments)
+    {
+        print!("{usage}");
+        return ExitCode::SUCCESS;
+    }
+
+    match parse(&mut Args::new(args)) {
+        Ok(args) => run(&args),
+        Err(e) => {
+            eprintln!("error: {e}\n\nRun `{name} --help` for usage.");
+            ExitCode::from(EXIT_USAGE)
+        }
+    }
+}
@@ -0,0 +1,325 @@
+use super::args::RunArgs;
+use super::input::{self, collect_inputs, render, Input};
+use super::{cache, watch};
+use super::{EXIT_FAILURE, EXIT_SETUP, EXIT_USAGE};
+use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
+use sitro::{
+    composite, flipbook, write_json_report, write_junit_report, CompositeOptions, ConsensusOptions,
+    FileRun, FlipbookOptions, HtmlReport, HtmlReportOptions, JunitOptions, RenderOptions, Renderer,
+    Tile, Verdict,
+};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::sync::Mutex;
+
+/// Delete the output directory, unless it contains any of the inputs.
+pub fn clean(output: &Path, inputs: &[PathBuf]) -> Result<(), String> {
+    let Ok(output) = output.canonicalize() else {
+        // Nothing to clean.
+        return Ok(());
+    };
+
+    let cwd = std::env::current_dir()
+        .ok()
+        .and_then(|cwd| cwd.canonicalize().ok());
+
+    if cwd.is_some_and(|cwd| cwd.starts_with(&output)) {
+        return Err(format!(
+            "refusing to clean {}, because it contains the current directory",
+            output.display()
+        ));
+    }
+
+    for input in inputs {
+        if input::base(input)
+            .canonicalize()
+            .is_ok_and(|input| input.starts_with(&output))
+        {
+            return Err(format!(
+                "refusing to clean {}, because it contains the input {}",
+                output.display(),
+                input.display()
+            ));
+        }
+    }
+
+    fs::remove_dir_all(&output).map_err(|e| format!("failed to clean {}: {e}", output.display()))
+}
+
+/// Render all inputs with all backends and write the composites and reports.
+pub fn run(args: &RunArgs) -> ExitCode {
+    let inputs = match collect_inputs(&args.inputs) {
+        Ok(inputs) => inputs,
+        Err(e) => {
+            eprintln!("error: {e}");
+            return ExitCode::from(EXIT_USAGE);
+        }
+    };
+
+    if args.clean {
+        if let Err(e) = clean(&args.output, &args.inputs) {
+            eprintln!("error: {e}");
+            return ExitCode::from(EXIT_USAGE);
+        }
+    }
+
+    let instance = match cache::renderer(&args.cache) {
+        Ok(instance) => instance,
+        Err(e) => {
+            eprintln!("error: failed to set up the renderer: {e}");
+            return ExitCode::from(EXIT_SETUP);
+        }
+    };
+
+    let runner = match Runner::new(args, instance) {
+        Ok(runner) => runner,
+        Err(e) => {
+            eprintln!("error: {e}");
+            return ExitCode::from(EXIT_SETUP);
+        }
+    };
+
+    // Only keep the renders around if they are compared with the next ones.
+    let runs = inputs
+        .par_iter()
+        .filter_map(|input| {
+            let run = runner.process(input)?;
+            args.watch.then(|| (input.path.clone(), run))
+        })
+        .collect();
+
+    runner.write_reports();
+    let failures = runner.take_failures();
+
+    if !failures.is_empty() {
+        eprintln!("\n{} failures:", failures.len());
+
+        for failure in &failures {
+            eprintln!("  {failure}");
+        }
+    }
+
+    if args.watch {
+        watch::watch(&runner, runs);
+    }
+
+    if failures.is_empty() {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::from(EXIT_FAILURE)
+    }
+}
+
+/// Renders files with the selected backends and writes their composites and report pages.
+pub struct Runner<'a> {
+    pub args: &'a RunArgs,
+    instance: &'a Renderer,
+    report: HtmlReport,
+    options: RenderOptions,
+    composite_options: CompositeOptions,
+    flipbook_options: FlipbookOptions,
+    junit_options: JunitOptions,
+    failures: Mutex<Vec<String>>,
+}
+
+impl<'a> Runner<'a> {
+    pub fn new(args: &'a RunArgs, instance: &'a Renderer) -> Result<Self, String> {
+        let report_options = HtmlReportOptions {
+            consensus: ConsensusOptions {
+                threshold: args.threshold,
+                ..ConsensusOptions::default()
+            },
+        };
+        let report = HtmlReport::new(args.output.join("report"), report_options)?;
+
+        let composite_options = CompositeOptions {
+            layout: args.layout,
+            border_width: (args.border_width > 0.0).then_some(args.border_width),
+            annotate: args.annotate,
+            ..CompositeOptions::default()
+        };
+
+        let flipbook_options = FlipbookOptions {
+            border_width: composite_options.border_width,
+            annotate: args.annotate,
+            ..FlipbookOptions::default()
+        };
+
+        Ok(Self {
+            args,
+            instance,
+            report,
+            options: RenderOptions { scale: args.scale },
+            composite_options,
+            flipbook_options,
+            junit_options: args.junit_options(),
+            failures: Mutex::new(vec![]),
+        })
+    }
+
+    /// Print a failure and remember it for the summary.
+    fn record_failure(&self, failure: String) {
+        eprintln!("{failure}");
+        self.failures.lock().unwrap().push(failure);
+    }
+
+    /// The failures since the last call.
+    pub fn take_failures(&self) -> Vec<String> {
+        std::mem::take(&mut self.failures.lock().unwrap())
+    }
+
+    /// Render a file with all backends, add it to the report and write its composites.
+    ///
+    /// Returns `None` if the file couldn't be read.
+    pub fn process(&self, input: &Input) -> Option<FileRun> {
+        let args = self.args;
+        let file = match fs::read(&input.path) {
+            Ok(file) => file,
+            Err(e) => {
+                self.record_failure(format!("failed to read {}: {e}", input.path.display()));
+                return None;
+            }
+        };
+
+        let run = FileRun {
+            name: input.name.to_string_lossy().into_owned(),
+            first_page: args.pages.first,
+            backends: args
+                .backends
+                .par_iter()
+                .map(|backend| {
+                    println!("rendering {} with {}", input.path.display(), backend.name());
+                    let run = render(self.instance, backend, &file, &self.options, &args.pages);
+                    let name = input.name.to_string_lossy();
+                    let failed = run.result.is_err();
+
+                    match self
+                        .junit_options
+                        .expectations
+                        .judge_render(&name, backend, failed)
+                    {
+                        Verdict::Pass | Verdict::Skipped(_) => {}
+                        Verdict::Diverged => self.record_failure(format!(
+                            "failed to render {} with {}: {}",
+                            input.path.display(),
+                            backend.name(),
+                            run.result.as_ref().err().map_or("", String::as_str)
+                        )),
+                        Verdict::Expected(d) => println!("{d}, and failed to render {name}"),
+                        Verdict::Stale(d) => self
+                            .record_failure(format!("stale expectation: {d}, but rendered {name}")),
+                    }
+
+                    run
+                })
+                .collect(),
+        };
+
+        if let Err(e) = self.report.add_file(&run) {
+            self.record_failure(e);
+        }
+
+        let base = args
+            .output
+            .join(input.name.with_extension(""))
+            .to_string_lossy()
+            .into_owned();
+        let composite_paths = |index: usize| {
+            (
+                PathBuf::from(format!("{base}-{index}.png")),
+                PathBuf::from(format!("{base}-{index}-flip.png")),
+            )
+        };
+
+        // Still create a composite if all backends failed, so that the failures show up.
+        let page_count = run.page_count().max(1);
+
+        for i in 0..page_count {
+            let pages: Vec<_> = run
+                .backends
+                .iter()
+                .enumerate()
+                .map(|(j, backend_run)| (backend_run.backend.clone(), run.page(j, i).cloned()))
+                .collect();
+
+            // Placeholders get the size of the pages that were rendered successfully.
+            let (width, height) = pages
+                .iter()
+                .find_map(|(_, page)| page.as_ref().ok())
+                .map(|page| (page.width(), page.height()))
+                .unwrap_or((
+                    (595.0 * self.options.scale) as u32,
+                    (842.0 * self.options.scale) as u32,
+                ));
+
+            let tiles: Vec<_> = pages
+                .into_iter()
+                .map(|(backend, page)| match page {
+                    Ok(page) => Tile::new(&backend, page),
+                    Err(e) => Tile::failed(&backend, &e, width, height),
+                })
+                .collect();
+
+            let index = run.first_page + i;
+            let (path, flipbook_path) = composite_paths(index);
+
+            if let Some(dir) = path.parent() {
+                let _ = fs::create_dir_all(dir);
+            }
+
+            if let Err(e) = composite(&tiles, &self.composite_options).save_png(&path) {
+                self.record_failure(format!("failed to write {}: {e}", path.display()));
+            }
+
+            if let Err(e) = flipbook(&tiles, &self.flipbook_options).save_apng(&flipbook_path) {
+                self.record_failure(format!("{}: {e}", flipbook_path.display()));
+            }
+        }
+
+        // Remove the composites of pages in the range the file no longer has, for example
+        // because it shrank while watching it.
+        let end = args.pages.last.map_or(usize::MAX, |last| last + 1);
+
+        for index in run.first_page + page_count..end {
+            let (path, flipbook_path) = composite_paths(index);
+
+            if !path.exists() && !flipbook_path.exists() {
+                break;
+            }
+
+            for path in [path, flipbook_path] {
+                if let Err(e) = fs::remove_file(&path) {
+                    if e.kind() != io::ErrorKind::NotFound {
+                        self.record_failure(format!("failed to remove {}: {e}", path.display()));
+                    }
+                }
+            }
+        }
+
+        Some(run)
+    }
+
+    /// Write the index of the HTML report, the JSON report and the JUnit report with all
+    /// files processed so far.
+    pub fn write_reports(&self) {
+        match self.report.write_index() {
+            Ok(path) => println!("wrote report to {}", path.display()),
+            Err(e) => self.record_failure(e),
+        }
+
+        let summaries = self.report.files();
+        let output = &self.args.output;
+
+        if let Err(e) = write_json_report(output.join("report.json"), &summaries, &self.options) {
+            self.record_failure(e);
+        }
+
+        let junit = output.join("junit.xml");
+
+        if let Err(e) = write_junit_report(junit, &summaries, &self.junit_options) {
+            self.record_failure(e);
+        }
+    }
+}
@@ -0,0 +1,34 @@
+use super::args::ServeArgs;
+use super::{cache, EXIT_SETUP};
+use sitro::{serve as serve_forever, ServerOptions};
+use std::net::{Ipv4Addr, TcpListener};
+use std::process::ExitCode;
+
+/// Serve the interactive comparison page on localhost.
+pub fn serve(args: &ServeArgs) -> ExitCode {
+    // Set up the renderer before accepting requests, so that problems show up right away.
+    if let Err(e) = cache::renderer(&args.cache) {
+        eprintln!("error: failed to set up the renderer: {e}");
+        return ExitCode::from(EXIT_SETUP);
+    }
+
+    let listener = match TcpListener::bind((Ipv4Addr::LOCALHOST, args.port)) {
+        Ok(listener) => listener,
+        Err(e) => {
+            eprintln!("error: failed to listen on port {}: {e}", args.port);
+            return ExitCode::from(EXIT_SETUP);
+        }
+    };
+
+    if let Ok(address) = listener.local_addr() {
+        println!("serving on http://{address}, press Ctrl-C to stop");
+    }
+
+    match serve_forever(listener, &ServerOptions::default()) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("error: {e}");
+            ExitCode::from(EXIT_SETUP)
+        }
+    }
+}
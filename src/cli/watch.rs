@@ -0,0 +1,110 @@
+use super::input::collect_inputs;
+use super::run::Runner;
+use sitro::FileRun;
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+/// How long a changed file must stay unchanged before it is rendered, so that files
+/// aren't rendered while they are still being written.
+const SETTLE_TIME: Duration = Duration::from_millis(200);
+
+/// When a file was last modified and how large it was.
+type Stamp = (Option<SystemTime>, u64);
+
+fn stamp(path: &PathBuf) -> Option<Stamp> {
+    let metadata = fs::metadata(path).ok()?;
+    Some((metadata.modified().ok(), metadata.len()))
+}
+
+/// Poll the inputs for new and changed PDFs and re-render them until the process is
+/// killed, printing how the renders of each backend changed.
+pub fn watch(runner: &Runner, runs: Vec<(PathBuf, FileRun)>) -> ! {
+    let args = runner.args;
+    let mut stamps: HashMap<_, _> = runs
+        .iter()
+        .filter_map(|(path, _)| Some((path.clone(), stamp(path)?)))
+        .collect();
+    let mut previous: HashMap<_, _> = runs.into_iter().collect();
+
+    println!("\nwatching for changes, press Ctrl-C to stop");
+
+    loop {
+        thread::sleep(args.poll_interval);
+
+        let inputs = match collect_inputs(&args.inputs) {
+            Ok(inputs) => inputs,
+            Err(e) => {
+                eprintln!("error: {e}");
+                continue;
+            }
+        };
+
+        stamps.retain(|path, _| {
+            let exists = inputs.iter().any(|input| &input.path == path);
+
+            if !exists {
+                println!("\n{} was removed", path.display());
+                previous.remove(path);
+            }
+
+            exists
+        });
+
+        let mut changed: Vec<_> = inputs
+            .into_iter()
+            .filter_map(|input| {
+                let stamp = stamp(&input.path)?;
+                (stamps.get(&input.path) != Some(&stamp)).then_some((input, stamp))
+            })
+            .collect();
+
+        if changed.is_empty() {
+            continue;
+        }
+
+        // Files that are still being written are picked up by the next poll.
+        thread::sleep(SETTLE_TIME);
+        changed.retain(|(input, s)| stamp(&input.path).as_ref() == Some(s));
+
+        if changed.is_empty() {
+            continue;
+        }
+
+        for (input, s) in changed {
+            stamps.insert(input.path.clone(), s);
+            println!("\n{} changed", input.path.display());
+
+            if let Some(run) = runner.process(&input) {
+                print_changes(previous.get(&input.path), &run);
+                previous.insert(input.path, run);
+            }
+        }
+
+        runner.write_reports();
+        // Failures were already printed while rendering.
+        runner.take_failures();
+    }
+}
+
+/// Print how the render of each backend changed compared with the previous render.
+fn print_changes(previous: Option<&FileRun>, run: &FileRun) {
+    println!("changes in {}:", run.name);
+
+    for backend_run in &run.backends {
+        let before = previous.and_then(|previous| {
+            previous
+                .backends
+                .iter()
+                .find(|b| b.backend == backend_run.backend)
+        });
+
+        println!(
+            "  {}: {}",
+            backend_run.backend.name(),
+            backend_run.describe_change(before, run.first_page)
+        );
+    }
+}
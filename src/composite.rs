@@ -0,0 +1,302 @@
+use crate::consensus::{consensus_pixmap, ConsensusOptions};
+use crate::diff::{diff_pixmaps, DiffMode, DiffOptions};
+use crate::regions::draw_regions;
+use crate::renderer::{draw_border, Backend, RenderOptions};
+use crate::text::{draw_text, text_height, text_width, wrap};
+use tiny_skia::{Color, Paint, PathBuilder, Pixmap, PixmapPaint, Rect, Stroke, Transform};
+
+/// How the tiles of a composite should be arranged.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Layout {
+    /// All tiles in a single row.
+    Horizontal,
+    /// All tiles in a single column.
+    Vertical,
+    /// A grid with the given number of columns, filled row by row.
+    Grid(usize),
+    /// A single row for portrait pages and a single column for landscape pages,
+    /// based on the first tile.
+    Auto,
+}
+
+/// The options that should be applied when building a composite.
+#[derive(Copy, Clone)]
+pub struct CompositeOptions {
+    /// How the tiles should be arranged.
+    pub layout: Layout,
+    /// The space between two tiles in pixels.
+    pub spacing: u32,
+    /// The width of the colored border around each page relative to the smaller
+    /// side of the page, or `None` for no border.
+    pub border_width: Option<f32>,
+    /// Whether the label of each tile should be drawn above it.
+    pub labels: bool,
+    /// Whether the regions in which a page differs from the consensus of all pages
+    /// that were rendered successfully should be outlined in red.
+    pub annotate: bool,
+}
+
+impl Default for CompositeOptions {
+    fn default() -> Self {
+        Self {
+            layout: Layout::Horizontal,
+            spacing: 0,
+            border_width: Some(1.0 / 50.0),
+            labels: true,
+            annotate: false,
+        }
+    }
+}
+
+/// A single page in a composite.
+#[derive(Clone)]
+pub struct Tile {
+    /// The label of the tile.
+    pub label: String,
+    /// The color of the border and label of the tile.
+    pub color: (u8, u8, u8),
+    /// The rendered page.
+    pub pixmap: Pixmap,
+    /// Whether the page is a placeholder for a page that failed to render, which is
+    /// left out when annotating differences.
+    pub failed: bool,
+}
+
+impl Tile {
+    /// Create a tile for a page rendered by the given backend, labeled with the
+    /// name and color of the backend.
+    pub fn new(backend: &Backend, pixmap: Pixmap) -> Self {
+        Self {
+            label: backend.name(),
+            color: backend.color(),
+            pixmap,
+            failed: false,
+        }
+    }
+
+    /// Create a placeholder tile for a page that a backend failed to render.
+    ///
+    /// The placeholder has the given size, so that it lines up with the pages of the
+    /// other backends, and shows the error message on a hatched background.
+    pub fn failed(backend: &Backend, error: &str, width: u32, height: u32) -> Self {
+        Self {
+            label: backend.name(),
+            color: backend.color(),
+            pixmap: placeholder(backend.color(), error, width, height),
+            failed: true,
+        }
+    }
+}
+
+/// Arrange tiles in a single image, in the order they were given in.
+pub fn composite(tiles: &[Tile], options: &CompositeOptions) -> Pixmap {
+    let cells = draw_tiles(tiles, options);
+
+    let columns = match options.layout {
+        Layout::Horizontal => cells.len(),
+        Layout::Vertical => 1,
+        Layout::Grid(columns) => columns,
+        Layout::Auto => match tiles.first() {
+            Some(tile) if tile.pixmap.width() > tile.pixmap.height() => 1,
+            _ => cells.len(),
+        },
+    }
+    .max(1);
+
+    let rows = cells.len().div_ceil(columns);
+    let mut widths = vec![0; columns];
+    let mut heights = vec![0; rows];
+
+    for (i, cell) in cells.iter().enumerate() {
+        widths[i % columns] = widths[i % columns].max(cell.width());
+        heights[i / columns] = heights[i / columns].max(cell.height());
+    }
+
+    let gaps = |count: usize| count.saturating_sub(1) as u32 * options.spacing;
+    let width = widths.iter().sum::<u32>() + gaps(columns);
+    let height = heights.iter().sum::<u32>() + gaps(rows);
+
+    let mut pixmap = Pixmap::new(width.max(1), height.max(1)).unwrap();
+    pixmap.fill(Color::WHITE);
+
+    for (i, cell) in cells.iter().enumerate() {
+        let (column, row) = (i % columns, i / columns);
+        let x = widths[..column].iter().sum::<u32>() + column as u32 * options.spacing;
+        let y = heights[..row].iter().sum::<u32>() + row as u32 * options.spacing;
+
+        pixmap.draw_pixmap(
+            x as i32,
+            y as i32,
+            cell.as_ref(),
+            &PixmapPaint::default(),
+            Transform::identity(),
+            None,
+        );
+    }
+
+    pixmap
+}
+
+/// Draw tiles with their borders and labels, annotating them if requested.
+pub(crate) fn draw_tiles(tiles: &[Tile], options: &CompositeOptions) -> Vec<Pixmap> {
+    if !options.annotate {
+        return tiles.iter().map(|tile| draw_tile(tile, options)).collect();
+    }
+
+    let rendered = tiles
+        .iter()
+        .filter(|tile| !tile.failed)
+        .map(|tile| &tile.pixmap)
+        .collect::<Vec<_>>();
+    let consensus =
+        (!rendered.is_empty()).then(|| consensus_pixmap(&rendered, &ConsensusOptions::default()));
+    let diff_options = DiffOptions {
+        mode: DiffMode::IgnoreAntiAliasing,
+        ..DiffOptions::default()
+    };
+
+    tiles
+        .iter()
+        .map(|tile| match &consensus {
+            Some(consensus) if !tile.failed => {
+                let mut tile = tile.clone();
+                let diff = diff_pixmaps(&tile.pixmap, consensus, &diff_options);
+                let regions = diff.regions(4, &RenderOptions::default());
+                draw_regions(&mut tile.pixmap, &regions, Transform::identity());
+                draw_tile(&tile, options)
+            }
+            _ => draw_tile(tile, options),
+        })
+        .collect()
+}
+
+/// Draw a tile with its border and label.
+pub(crate) fn draw_tile(tile: &Tile, options: &CompositeOptions) -> Pixmap {
+    let page = match options.border_width {
+        Some(border_width) => draw_border(&tile.pixmap, tile.color, border_width),
+        None => tile.pixmap.clone(),
+    };
+
+    if !options.labels {
+        return page;
+    }
+
+    let scale = label_scale(&tile.pixmap);
+    let padding = 2 * scale;
+    let label_height = text_height(scale) + 2 * padding;
+    let width = page
+        .width()
+        .max(text_width(&tile.label, scale) + 2 * padding);
+
+    let mut pixmap = Pixmap::new(width, page.height() + label_height).unwrap();
+    pixmap.fill(Color::WHITE);
+
+    let (r, g, b) = tile.color;
+    let mut paint = Paint::default();
+    paint.set_color_rgba8(r, g, b, 255);
+    pixmap.fill_rect(
+        Rect::from_xywh(0.0, 0.0, width as f32, label_height as f32).unwrap(),
+        &paint,
+        Transform::identity(),
+        None,
+    );
+
+    let luma = (r as u32 * 299 + g as u32 * 587 + b as u32 * 114) / 1000;
+    let text_color = if luma > 140 {
+        (0, 0, 0)
+    } else {
+        (255, 255, 255)
+    };
+    draw_text(
+        &mut pixmap,
+        &tile.label,
+        padding as f32,
+        padding as f32,
+        scale,
+        text_color,
+    );
+
+    pixmap.draw_pixmap(
+        0,
+        label_height as i32,
+        page.as_ref(),
+        &PixmapPaint::default(),
+        Transform::identity(),
+        None,
+    );
+
+    pixmap
+}
+
+/// Draw a hatched page with an error message in its center.
+fn placeholder(color: (u8, u8, u8), error: &str, width: u32, height: u32) -> Pixmap {
+    let mut pixmap = Pixmap::new(width.max(1), height.max(1)).unwrap();
+    pixmap.fill(Color::from_rgba8(240, 240, 240, 255));
+
+    let scale = label_scale(&pixmap);
+    let spacing = 12.0 * scale as f32;
+    let (w, h) = (width as f32, height as f32);
+
+    let mut pb = PathBuilder::new();
+    let mut offset = -h;
+
+    while offset < w {
+        pb.move_to(offset, h);
+        pb.line_to(offset + h, 0.0);
+        offset += spacing;
+    }
+
+    if let Some(path) = pb.finish() {
+        let mut paint = Paint::default();
+        paint.set_color_rgba8(color.0, color.1, color.2, 96);
+        let stroke = Stroke {
+            width: scale as f32 * 2.0,
+            ..Stroke::default()
+        };
+
+        pixmap.stroke_path(&path, &paint, &stroke, Transform::identity(), None);
+    }
+
+    let padding = 4 * scale;
+    let line_height = text_height(scale) + scale * 2;
+    let mut lines = vec!["rendering failed:".to_string()];
+    lines.extend(wrap(error, width.saturating_sub(4 * padding), scale));
+
+    // Don't let the message overflow the page.
+    let max_lines = (height.saturating_sub(2 * padding) / line_height).max(1) as usize;
+    lines.truncate(max_lines);
+
+    let box_width = lines
+        .iter()
+        .map(|line| text_width(line, scale))
+        .max()
+        .unwrap_or(0)
+        + 2 * padding;
+    let box_height = lines.len() as u32 * line_height + 2 * padding;
+    let x = (width as f32 - box_width as f32) / 2.0;
+    let y = (height as f32 - box_height as f32) / 2.0;
+
+    if let Some(rect) = Rect::from_xywh(x, y, box_width as f32, box_height as f32) {
+        let mut paint = Paint::default();
+        paint.set_color_rgba8(255, 255, 255, 255);
+        pixmap.fill_rect(rect, &paint, Transform::identity(), None);
+    }
+
+    for (i, line) in lines.iter().enumerate() {
+        draw_text(
+            &mut pixmap,
+            line,
+            x + padding as f32,
+            y + (padding + i as u32 * line_height) as f32,
+            scale,
+            (0, 0, 0),
+        );
+    }
+
+    pixmap
+}
+
+/// The scale of the label text, so that it stays readable relative to the page.
+pub(crate) fn label_scale(pixmap: &Pixmap) -> u32 {
+    (pixmap.width().min(pixmap.height()) / 150).clamp(1, 4)
+}
@@ -0,0 +1,224 @@
+use crate::diff::{diff_pixmaps, pixel, pixels_match, DiffOptions};
+use crate::normalize::{normalize, SizeDiscrepancy, SizePolicy};
+use crate::renderer::{Backend, RenderOptions, Renderer};
+use tiny_skia::{Color, Pixmap};
+
+/// How the consensus image of multiple rendered pages should be computed.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum ConsensusMode {
+    /// Use the per-channel median of all backends.
+    Median,
+    /// Use the color that most backends agree on, within the tolerance of the diff options.
+    Majority,
+}
+
+/// The options that should be applied when building a consensus.
+#[derive(Copy, Clone)]
+pub struct ConsensusOptions {
+    /// How the consensus image should be computed.
+    pub mode: ConsensusMode,
+    /// How pages of different sizes should be brought to a common size.
+    pub size_policy: SizePolicy,
+    /// The options used when comparing a backend with the consensus.
+    pub diff: DiffOptions,
+    /// The fraction of differing pixels above which a backend is considered an outlier.
+    pub threshold: f32,
+}
+
+impl Default for ConsensusOptions {
+    fn default() -> Self {
+        Self {
+            mode: ConsensusMode::Median,
+            size_policy: SizePolicy::Pad,
+            diff: DiffOptions::default(),
+            threshold: 0.01,
+        }
+    }
+}
+
+/// How much a single backend deviates from the consensus of a page.
+#[derive(Clone, Debug)]
+pub struct BackendScore {
+    /// The backend.
+    pub backend: Backend,
+    /// The fraction of pixels that differ from the consensus, between 0.0 and 1.0.
+    ///
+    /// Backends that didn't produce the page at all have a distance of 1.0.
+    pub distance: f32,
+    /// Whether the distance is above the outlier threshold.
+    pub outlier: bool,
+}
+
+/// The consensus of a single page across multiple backends.
+pub struct PageConsensus {
+    /// The consensus image.
+    pub image: Pixmap,
+    /// The score of each backend, in the order the backends were given in.
+    pub scores: Vec<BackendScore>,
+    /// How much the page sizes of the backends differed before normalizing them.
+    pub size_discrepancy: SizeDiscrepancy,
+}
+
+impl PageConsensus {
+    /// The backends that were flagged as outliers.
+    pub fn outliers(&self) -> impl Iterator<Item = &BackendScore> {
+        self.scores.iter().filter(|s| s.outlier)
+    }
+}
+
+impl Renderer {
+    /// Render a PDF with multiple backends and build the consensus of each page.
+    ///
+    /// Returns an error if any of the backends fails to render the PDF.
+    pub fn render_consensus(
+        &self,
+        backends: &[Backend],
+        buf: &[u8],
+        options: &RenderOptions,
+        consensus_options: &ConsensusOptions,
+    ) -> Result<Vec<PageConsensus>, String> {
+        let documents = self.render_all_as_pixmaps(backends, buf, options)?;
+
+        let page_count = documents.iter().map(|d| d.1.len()).max().unwrap_or(0);
+
+        Ok((0..page_count)
+            .map(|i| {
+                let pages = documents
+                    .iter()
+                    .map(|(backend, pixmaps)| (backend.clone(), pixmaps.get(i)))
+                    .collect::<Vec<_>>();
+
+                page_consensus(&pages, consensus_options)
+            })
+            .collect())
+    }
+}
+
+/// Build the consensus of a page rendered with multiple backends.
+pub fn consensus(pages: &[(Backend, Pixmap)], options: &ConsensusOptions) -> PageConsensus {
+    let pages = pages
+        .iter()
+        .map(|(backend, pixmap)| (backend.clone(), Some(pixmap)))
+        .collect::<Vec<_>>();
+
+    page_consensus(&pages, options)
+}
+
+pub(crate) fn page_consensus(
+    pages: &[(Backend, Option<&Pixmap>)],
+    options: &ConsensusOptions,
+) -> PageConsensus {
+    let present = pages
+        .iter()
+        .filter_map(|(_, pixmap)| *pixmap)
+        .collect::<Vec<_>>();
+
+    let normalized = normalize(&present, options.size_policy);
+    let image = consensus_image(
+        &normalized.pixmaps,
+        normalized.width,
+        normalized.height,
+        options,
+    );
+
+    let mut pixmaps = normalized.pixmaps.iter();
+    let scores = pages
+        .iter()
+        .map(|(backend, pixmap)| {
+            let distance = match pixmap {
+                Some(_) => diff_pixmaps(pixmaps.next().unwrap(), &image, &options.diff).ratio(),
+                None => 1.0,
+            };
+
+            BackendScore {
+                backend: backend.clone(),
+                distance,
+                outlier: distance > options.threshold,
+            }
+        })
+        .collect();
+
+    PageConsensus {
+        image,
+        scores,
+        size_discrepancy: normalized.discrepancy,
+    }
+}
+
+/// Build the consensus image of pages without scoring them.
+pub(crate) fn consensus_pixmap(pixmaps: &[&Pixmap], options: &ConsensusOptions) -> Pixmap {
+    let normalized = normalize(pixmaps, options.size_policy);
+    consensus_image(
+        &normalized.pixmaps,
+        normalized.width,
+        normalized.height,
+        options,
+    )
+}
+
+fn consensus_image(
+    pixmaps: &[Pixmap],
+    width: u32,
+    height: u32,
+    options: &ConsensusOptions,
+) -> Pixmap {
+    let mut image = Pixmap::new(width, height).unwrap();
+    image.fill(Color::WHITE);
+
+    if pixmaps.is_empty() {
+        return image;
+    }
+
+    let data = image.data_mut();
+    let mut samples = Vec::with_capacity(pixmaps.len());
+
+    for y in 0..height {
+        for x in 0..width {
+            samples.clear();
+            samples.extend(pixmaps.iter().map(|p| pixel(p, x, y).unwrap()));
+
+            let color = match options.mode {
+                ConsensusMode::Median => median(&mut samples),
+                ConsensusMode::Majority => majority(&samples, options.diff.tolerance),
+            };
+
+            let idx = (y * width + x) as usize * 4;
+            data[idx..idx + 4].copy_from_slice(&color);
+        }
+    }
+
+    image
+}
+
+fn median(samples: &mut [[u8; 4]]) -> [u8; 4] {
+    let mut color = [0; 4];
+
+    for (channel, value) in color.iter_mut().enumerate() {
+        samples.sort_unstable_by_key(|s| s[channel]);
+        *value = samples[samples.len() / 2][channel];
+    }
+
+    // The channels are picked independently, so make sure the result is
+    // still a valid premultiplied color.
+    for channel in 0..3 {
+        color[channel] = color[channel].min(color[3]);
+    }
+
+    color
+}
+
+pub(crate) fn majority(samples: &[[u8; 4]], tolerance: u8) -> [u8; 4] {
+    samples
+        .iter()
+        .enumerate()
+        .max_by_key(|(i, s)| {
+            let votes = samples
+                .iter()
+                .filter(|other| pixels_match(**s, **other, tolerance))
+                .count();
+            // Prefer earlier samples in case of a tie.
+            (votes, usize::MAX - i)
+        })
+        .map(|(_, s)| *s)
+        .unwrap()
+}
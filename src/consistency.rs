@@ -0,0 +1,316 @@
+use crate::composite::{composite, CompositeOptions, Tile};
+use crate::diff::{diff_pixmaps, diff_pixmaps_masked, DiffOptions};
+use crate::expectations::{Expectation, Expectations, Verdict};
+use crate::mask::{page_mask, IgnoreRegion};
+use crate::normalize::{normalize, SizePolicy};
+use crate::renderer::{
+    render_pages_to_pixmaps, Backend, RenderOptions, RenderedDocument, Renderer, RENDER_INSTANCE,
+};
+use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
+use std::path::PathBuf;
+use std::{env, fs};
+use tiny_skia::Pixmap;
+
+/// The options that should be applied when checking whether backends render a PDF consistently.
+#[derive(Clone)]
+pub struct ConsistencyOptions {
+    /// The options used for rendering the PDF.
+    pub render: RenderOptions,
+    /// The options used for comparing the pages of two backends.
+    pub diff: DiffOptions,
+    /// The maximum fraction of pixels that may differ between any two backends.
+    pub tolerance: f32,
+    /// By how many pixels the width and height of a page may differ between two backends.
+    pub size_tolerance: u32,
+    /// How pages whose sizes differ within the size tolerance should be brought to a
+    /// common size before comparing their pixels.
+    pub size_policy: SizePolicy,
+    /// Areas that should be excluded from the comparison.
+    pub ignore: Vec<IgnoreRegion>,
+    /// The directory composite images of inconsistent pages are written to.
+    pub output_dir: PathBuf,
+    /// Known divergences of backends, which don't make the check fail. Known
+    /// divergences that no longer occur do.
+    pub expectations: Expectations,
+    /// The name of the PDF that the known divergences are matched against.
+    pub name: String,
+}
+
+impl Default for ConsistencyOptions {
+    fn default() -> Self {
+        Self {
+            render: RenderOptions::default(),
+            diff: DiffOptions::default(),
+            tolerance: 0.01,
+            size_tolerance: 1,
+            size_policy: SizePolicy::Crop,
+            ignore: vec![],
+            output_dir: env::temp_dir().join("sitro"),
+            expectations: Expectations::default(),
+            name: String::new(),
+        }
+    }
+}
+
+impl Renderer {
+    /// Check whether a PDF renders the same with all of the given backends.
+    ///
+    /// Backends that are not available are skipped, see [`Renderer::check_available`].
+    /// Skipped backends are logged to stderr and listed in the error. The page
+    /// counts, page sizes and the pixels of each pair of backends are compared. Known
+    /// divergences in the options are tolerated, but reported if they no longer occur.
+    /// If the check fails, a composite image of each inconsistent page is written to the output
+    /// directory and an error describing all inconsistencies is returned.
+    pub fn check_consistency(
+        &self,
+        buf: &[u8],
+        backends: &[Backend],
+        options: &ConsistencyOptions,
+    ) -> Result<(), String> {
+        let mut available = vec![];
+        let mut skipped = vec![];
+
+        for backend in backends {
+            match self.check_available(backend) {
+                Ok(()) => available.push(backend.clone()),
+                Err(e) => {
+                    let message = format!("skipped {}: {e}", backend.name());
+                    eprintln!("{message}");
+                    skipped.push(message);
+                }
+            }
+        }
+
+        let renders = available
+            .par_iter()
+            .map(|backend| (backend.clone(), self.render(backend, buf, &options.render)))
+            .collect::<Vec<_>>();
+
+        check_rendered_consistency(&renders, options).map_err(|e| {
+            skipped.insert(0, e);
+            skipped.join("\n")
+        })
+    }
+}
+
+/// Check whether the renders of a PDF with different backends are the same.
+///
+/// This is the check of [`Renderer::check_consistency`] for documents that were already
+/// rendered, with the result of each backend. `options.render` should be the options
+/// the documents were rendered with.
+pub fn check_rendered_consistency(
+    renders: &[(Backend, Result<RenderedDocument, String>)],
+    options: &ConsistencyOptions,
+) -> Result<(), String> {
+    let mut failures = vec![];
+    let mut rendered = vec![];
+
+    for (backend, result) in renders {
+        let verdict = options
+            .expectations
+            .judge_render(&options.name, backend, result.is_err());
+
+        match (verdict, result) {
+            (Verdict::Expected(_) | Verdict::Skipped(_), _) => {}
+            (_, Err(e)) => failures.push(format!("{} failed to render: {e}", backend.name())),
+            (verdict, Ok(document)) => {
+                if let Verdict::Stale(d) = verdict {
+                    failures.push(format!("stale expectation: {d}, but it rendered the file"));
+                }
+
+                rendered.push((backend.clone(), document));
+            }
+        }
+    }
+
+    let (backends, documents): (Vec<_>, Vec<_>) = rendered.into_iter().unzip();
+
+    let pixmaps = documents
+        .iter()
+        .map(|pages| render_pages_to_pixmaps(pages, (0, 0, 0), None))
+        .collect::<Result<Vec<_>, String>>()?;
+
+    for (backend, pages) in backends.iter().zip(&pixmaps).skip(1) {
+        if pages.len() != pixmaps[0].len() {
+            failures.push(format!(
+                "{} rendered {} page(s), but {} rendered {}",
+                backends[0].name(),
+                pixmaps[0].len(),
+                backend.name(),
+                pages.len()
+            ));
+        }
+    }
+
+    let page_count = pixmaps.iter().map(|p| p.len()).min().unwrap_or(0);
+    let mut composites = vec![];
+
+    for i in 0..page_count {
+        let page_failures = check_page(&backends, &pixmaps, i, options);
+
+        if !page_failures.is_empty() {
+            let path = write_composite(&backends, &pixmaps, i, options)?;
+            failures.extend(page_failures.into_iter().map(|f| format!("page {i}: {f}")));
+            composites.push(path);
+        }
+    }
+
+    if failures.is_empty() {
+        return Ok(());
+    }
+
+    for path in composites {
+        failures.push(format!("composite written to {}", path.display()));
+    }
+
+    Err(failures.join("\n"))
+}
+
+fn check_page(
+    backends: &[Backend],
+    pixmaps: &[Vec<Pixmap>],
+    page: usize,
+    options: &ConsistencyOptions,
+) -> Vec<String> {
+    let mut failures = vec![];
+    let expectations = &options.expectations;
+    let known = backends
+        .iter()
+        .map(|backend| expectations.find(&options.name, page, backend))
+        .collect::<Vec<_>>();
+
+    let skipped = |i: usize| known[i].is_some_and(|d| d.expectation == Expectation::Skip);
+    let expected = |i: usize, ratio: f32| {
+        known[i]
+            .is_some_and(|d| matches!(d.expectation, Expectation::Difference(max) if ratio <= max))
+    };
+
+    // The largest difference of each backend from any other, to find stale expectations.
+    let mut max_ratios = vec![0.0f32; backends.len()];
+
+    for i in 0..backends.len() {
+        for j in i + 1..backends.len() {
+            if skipped(i) || skipped(j) {
+                continue;
+            }
+
+            let (a, b) = (&pixmaps[i][page], &pixmaps[j][page]);
+            let normalized = normalize(&[a, b], options.size_policy);
+
+            if normalized.discrepancy.max_difference() > options.size_tolerance {
+                failures.push(format!(
+                    "{} has size {}x{}, but {} has size {}x{}",
+                    backends[i].name(),
+                    a.width(),
+                    a.height(),
+                    backends[j].name(),
+                    b.width(),
+                    b.height()
+                ));
+                max_ratios[i] = 1.0;
+                max_ratios[j] = 1.0;
+                continue;
+            }
+
+            let (a, b) = (&normalized.pixmaps[0], &normalized.pixmaps[1]);
+            let mask = page_mask(
+                &options.ignore,
+                page,
+                normalized.width,
+                normalized.height,
+                &options.render,
+            );
+            let ratio = match mask {
+                Some(mask) => diff_pixmaps_masked(a, b, &options.diff, &mask),
+                None => diff_pixmaps(a, b, &options.diff),
+            }
+            .ratio();
+
+            max_ratios[i] = max_ratios[i].max(ratio);
+            max_ratios[j] = max_ratios[j].max(ratio);
+
+            if ratio > options.tolerance && !expected(i, ratio) && !expected(j, ratio) {
+                failures.push(format!(
+                    "{} and {} differ in {:.2}% of pixels (maximum is {:.2}%)",
+                    backends[i].name(),
+                    backends[j].name(),
+                    ratio * 100.0,
+                    options.tolerance * 100.0
+                ));
+            }
+        }
+    }
+
+    for (backend, ratio) in backends.iter().zip(max_ratios) {
+        let verdict =
+            expectations.judge_page(&options.name, page, backend, ratio, options.tolerance);
+
+        if let Verdict::Stale(d) = verdict {
+            failures.push(format!(
+                "stale expectation: {d}, but it differs in at most {:.2}% of pixels",
+                ratio * 100.0
+            ));
+        }
+    }
+
+    failures
+}
+
+fn write_composite(
+    backends: &[Backend],
+    pixmaps: &[Vec<Pixmap>],
+    page: usize,
+    options: &ConsistencyOptions,
+) -> Result<PathBuf, String> {
+    let tiles = backends
+        .iter()
+        .zip(pixmaps)
+        .map(|(backend, pages)| Tile::new(backend, pages[page].clone()))
+        .collect::<Vec<_>>();
+
+    fs::create_dir_all(&options.output_dir)
+        .map_err(|e| format!("failed to create output directory: {e}"))?;
+    let path = options
+        .output_dir
+        .join(format!("consistency-{}-{page}.png", uuid::Uuid::new_v4()));
+
+    composite(&tiles, &CompositeOptions::default())
+        .save_png(&path)
+        .map_err(|e| format!("failed to write composite: {e}"))?;
+
+    Ok(path)
+}
+
+/// Assert that a PDF renders the same with all of the given backends.
+///
+/// Uses the global render instance and panics with a description of all inconsistencies
+/// as well as the path of a composite image if the check fails. `tolerance` is the maximum
+/// fraction of pixels that may differ between any two backends. See
+/// [`Renderer::check_consistency`] for details.
+///
+/// ```no_run
+/// # use sitro::{assert_consistent, Backend};
+/// let pdf = std::fs::read("out.pdf").unwrap();
+/// assert_consistent(&pdf, &[Backend::Pdfium, Backend::Quartz, Backend::Hayro], 0.01);
+/// ```
+#[track_caller]
+pub fn assert_consistent(buf: &[u8], backends: &[Backend], tolerance: f32) {
+    let options = ConsistencyOptions {
+        tolerance,
+        ..ConsistencyOptions::default()
+    };
+
+    assert_consistent_with(buf, backends, &options);
+}
+
+/// Like [`assert_consistent`], but with custom options.
+#[track_caller]
+pub fn assert_consistent_with(buf: &[u8], backends: &[Backend], options: &ConsistencyOptions) {
+    let renderer = RENDER_INSTANCE
+        .as_ref()
+        .unwrap_or_else(|e| panic!("failed to initialize renderer: {e}"));
+
+    if let Err(e) = renderer.check_consistency(buf, backends, options) {
+        panic!("backends render the PDF inconsistently:\n{e}");
+    }
+}
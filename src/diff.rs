@@ -0,0 +1,267 @@
+use crate::mask::Mask;
+use tiny_skia::Pixmap;
+
+/// How differences between two rendered pages should be classified.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum DiffMode {
+    /// Every pixel that differs counts as a difference.
+    Exact,
+    /// Differences that are confined to one-pixel bands along edges are ignored.
+    ///
+    /// A differing pixel is considered to be caused by anti-aliasing if it lies on an edge
+    /// in both pages and its color in each page matches one of the pixels in its direct
+    /// neighborhood in the other page, i.e. the edge only moved by at most one pixel. Such
+    /// pixels are reported separately and don't count towards [`Diff::ratio`]. Differences
+    /// in smooth areas like gradients are always reported.
+    IgnoreAntiAliasing,
+}
+
+/// The options that should be applied when comparing two rendered pages.
+#[derive(Copy, Clone)]
+pub struct DiffOptions {
+    /// The maximum difference of a single color channel for which two pixels
+    /// are still considered to be equal.
+    pub tolerance: u8,
+    /// How differences should be classified.
+    pub mode: DiffMode,
+}
+
+impl Default for DiffOptions {
+    fn default() -> Self {
+        Self {
+            tolerance: 8,
+            mode: DiffMode::Exact,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Eq, PartialEq)]
+enum PixelState {
+    Equal,
+    Different,
+    AntiAliased,
+    Ignored,
+}
+
+/// The result of comparing two rendered pages pixel by pixel.
+///
+/// If the two pages have different sizes, the comparison covers the union of both,
+/// and pixels that only exist in one of the pages are counted as differing.
+pub struct Diff {
+    width: u32,
+    height: u32,
+    states: Vec<PixelState>,
+    differing: usize,
+    anti_aliased: usize,
+    ignored: usize,
+}
+
+impl Diff {
+    /// The width of the compared area.
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    /// The height of the compared area.
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// The number of pixels that differ.
+    pub fn differing_pixels(&self) -> usize {
+        self.differing
+    }
+
+    /// The number of differing pixels that were attributed to anti-aliasing.
+    ///
+    /// Always zero unless [`DiffMode::IgnoreAntiAliasing`] is used.
+    pub fn anti_aliased_pixels(&self) -> usize {
+        self.anti_aliased
+    }
+
+    /// The number of pixels that were excluded from the comparison by a mask.
+    pub fn ignored_pixels(&self) -> usize {
+        self.ignored
+    }
+
+    /// The fraction of pixels that differ, between 0.0 and 1.0.
+    ///
+    /// Pixels excluded by a mask are not taken into account.
+    pub fn ratio(&self) -> f32 {
+        let total = self.width as usize * self.height as usize - self.ignored;
+
+        if total == 0 {
+            0.0
+        } else {
+            self.differing as f32 / total as f32
+        }
+    }
+
+    /// Whether the two pages are equal within the tolerance.
+    pub fn is_identical(&self) -> bool {
+        self.differing == 0
+    }
+
+    /// Whether the pixel at the given position differs.
+    pub fn is_different(&self, x: u32, y: u32) -> bool {
+        self.state(x, y) == Some(PixelState::Different)
+    }
+
+    /// Whether the pixel at the given position differs only because of anti-aliasing.
+    pub fn is_anti_aliased(&self, x: u32, y: u32) -> bool {
+        self.state(x, y) == Some(PixelState::AntiAliased)
+    }
+
+    fn state(&self, x: u32, y: u32) -> Option<PixelState> {
+        if x < self.width && y < self.height {
+            Some(self.states[(y * self.width + x) as usize])
+        } else {
+            None
+        }
+    }
+
+    /// Create an image visualizing the diff.
+    ///
+    /// Equal pixels are drawn as a faded version of `base`, differing pixels in red,
+    /// pixels that only differ because of anti-aliasing in yellow and masked pixels in grey.
+    pub fn to_pixmap(&self, base: &Pixmap) -> Pixmap {
+        let mut pixmap = Pixmap::new(self.width.max(1), self.height.max(1)).unwrap();
+        let data = pixmap.data_mut();
+
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let idx = (y * self.width + x) as usize;
+
+                let color = match self.states[idx] {
+                    PixelState::Different => [255, 0, 0, 255],
+                    PixelState::AntiAliased => [255, 200, 0, 255],
+                    PixelState::Ignored => [160, 160, 160, 255],
+                    PixelState::Equal => fade(pixel(base, x, y).unwrap_or([255, 255, 255, 255])),
+                };
+
+                data[idx * 4..idx * 4 + 4].copy_from_slice(&color);
+            }
+        }
+
+        pixmap
+    }
+}
+
+/// Compare two rendered pages pixel by pixel.
+pub fn diff_pixmaps(a: &Pixmap, b: &Pixmap, options: &DiffOptions) -> Diff {
+    diff_with_mask(a, b, options, None)
+}
+
+/// Compare two rendered pages pixel by pixel, excluding the pixels covered by the mask.
+pub fn diff_pixmaps_masked(a: &Pixmap, b: &Pixmap, options: &DiffOptions, mask: &Mask) -> Diff {
+    diff_with_mask(a, b, options, Some(mask))
+}
+
+fn diff_with_mask(a: &Pixmap, b: &Pixmap, options: &DiffOptions, mask: Option<&Mask>) -> Diff {
+    let width = a.width().max(b.width());
+    let height = a.height().max(b.height());
+
+    let mut states = vec![PixelState::Equal; width as usize * height as usize];
+    let mut differing = 0;
+    let mut anti_aliased = 0;
+    let mut ignored = 0;
+
+    for y in 0..height {
+        for x in 0..width {
+            let state = match (pixel(a, x, y), pixel(b, x, y)) {
+                _ if mask.is_some_and(|m| m.is_masked(x, y)) => PixelState::Ignored,
+                (Some(p1), Some(p2)) if pixels_match(p1, p2, options.tolerance) => {
+                    PixelState::Equal
+                }
+                (Some(p1), Some(p2))
+                    if options.mode == DiffMode::IgnoreAntiAliasing
+                        && on_edge(a, x, y)
+                        && on_edge(b, x, y)
+                        && matches_neighbor(p1, b, x, y, options.tolerance)
+                        && matches_neighbor(p2, a, x, y, options.tolerance) =>
+                {
+                    PixelState::AntiAliased
+                }
+                _ => PixelState::Different,
+            };
+
+            match state {
+                PixelState::Equal => {}
+                PixelState::Different => differing += 1,
+                PixelState::AntiAliased => anti_aliased += 1,
+                PixelState::Ignored => ignored += 1,
+            }
+
+            states[(y * width + x) as usize] = state;
+        }
+    }
+
+    Diff {
+        width,
+        height,
+        states,
+        differing,
+        anti_aliased,
+        ignored,
+    }
+}
+
+/// The smallest difference of a color channel within the direct neighborhood of a pixel
+/// for which the pixel is considered to lie on an edge.
+const EDGE_CONTRAST: u8 = 64;
+
+/// The pixels in the direct neighborhood of a pixel, including the pixel itself.
+fn neighborhood(pixmap: &Pixmap, x: u32, y: u32) -> impl Iterator<Item = [u8; 4]> + '_ {
+    (y.saturating_sub(1)..=y + 1)
+        .flat_map(move |ny| (x.saturating_sub(1)..=x + 1).map(move |nx| (nx, ny)))
+        .filter_map(|(nx, ny)| pixel(pixmap, nx, ny))
+}
+
+/// Check whether a pixel lies on an edge, i.e. a color channel changes by a large step
+/// in its direct neighborhood.
+fn on_edge(pixmap: &Pixmap, x: u32, y: u32) -> bool {
+    let mut min = [u8::MAX; 4];
+    let mut max = [u8::MIN; 4];
+
+    for neighbor in neighborhood(pixmap, x, y) {
+        for channel in 0..4 {
+            min[channel] = min[channel].min(neighbor[channel]);
+            max[channel] = max[channel].max(neighbor[channel]);
+        }
+    }
+
+    (0..4).any(|channel| max[channel].saturating_sub(min[channel]) >= EDGE_CONTRAST)
+}
+
+/// Check whether a color matches one of the pixels in the direct neighborhood of a pixel
+/// in another page.
+fn matches_neighbor(color: [u8; 4], other: &Pixmap, x: u32, y: u32, tolerance: u8) -> bool {
+    neighborhood(other, x, y).any(|neighbor| pixels_match(color, neighbor, tolerance))
+}
+
+/// Convert a pixel into a light grey, so that it can serve as the background of a visualization.
+pub(crate) fn fade([r, g, b, a]: [u8; 4]) -> [u8; 4] {
+    let luma = (r as u32 * 299 + g as u32 * 587 + b as u32 * 114) / 1000;
+    // Premultiplied pixels, so take the alpha into account
+    // when blending towards white.
+    let luma = luma + (255 - a as u32);
+    let faded = (255 - (255 - luma.min(255)) / 4) as u8;
+    [faded, faded, faded, 255]
+}
+
+/// Get the premultiplied RGBA value of a pixel, or `None` if it is out of bounds.
+pub(crate) fn pixel(pixmap: &Pixmap, x: u32, y: u32) -> Option<[u8; 4]> {
+    if x >= pixmap.width() || y >= pixmap.height() {
+        return None;
+    }
+
+    let idx = (y * pixmap.width() + x) as usize * 4;
+    let data = pixmap.data();
+
+    Some([data[idx], data[idx + 1], data[idx + 2], data[idx + 3]])
+}
+
+/// Check whether all channels of two pixels are within the tolerance.
+pub(crate) fn pixels_match(a: [u8; 4], b: [u8; 4], tolerance: u8) -> bool {
+    a.iter().zip(b).all(|(c1, c2)| c1.abs_diff(c2) <= tolerance)
+}
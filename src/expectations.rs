@@ -0,0 +1,292 @@
+use crate::renderer::Backend;
+use glob::Pattern;
+use std::fmt::{self, Display, Formatter};
+use std::fs;
+use std::path::Path;
+use toml::{Table, Value};
+
+/// What a backend is known to do on a file or page.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Expectation {
+    /// The backend fails to render the file.
+    Failure,
+    /// The backend differs from the other backends by at most the given fraction of
+    /// pixels, between 0.0 and 1.0.
+    Difference(f32),
+    /// The backend isn't checked at all.
+    Skip,
+}
+
+/// A known divergence of a backend on the files whose names match a pattern.
+#[derive(Clone, Debug)]
+pub struct KnownDivergence {
+    /// The backend.
+    pub backend: Backend,
+    /// A glob pattern of the names of the files, like `shading/*.pdf`.
+    ///
+    /// Invalid patterns never match.
+    pub file: String,
+    /// The index of the page in the document, or `None` for all pages.
+    ///
+    /// Only differences and skips can be limited to a page.
+    pub page: Option<usize>,
+    /// What the backend does.
+    pub expectation: Expectation,
+    /// Why the backend diverges, like a link to an issue.
+    pub reason: Option<String>,
+}
+
+impl KnownDivergence {
+    fn matches(&self, file: &str, backend: &Backend) -> bool {
+        self.backend == *backend && Pattern::new(&self.file).is_ok_and(|p| p.matches(file))
+    }
+}
+
+impl Display for KnownDivergence {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        let backend = self.backend.name();
+
+        match self.expectation {
+            Expectation::Failure => write!(f, "{backend} is expected to fail on {}", self.file)?,
+            Expectation::Difference(max) => write!(
+                f,
+                "{backend} is expected to differ by up to {:.2}% on {}",
+                max * 100.0,
+                self.file
+            )?,
+            Expectation::Skip => write!(f, "{backend} is skipped on {}", self.file)?,
+        }
+
+        if let Some(page) = self.page {
+            write!(f, " page {}", page + 1)?;
+        }
+
+        if let Some(reason) = &self.reason {
+            write!(f, " ({reason})")?;
+        }
+
+        Ok(())
+    }
+}
+
+/// How a backend did compared with what was expected of it.
+#[derive(Copy, Clone, Debug)]
+pub enum Verdict<'a> {
+    /// The backend doesn't diverge and isn't expected to.
+    Pass,
+    /// The backend diverges and no known divergence covers it.
+    Diverged,
+    /// The backend diverges as expected.
+    Expected(&'a KnownDivergence),
+    /// The backend isn't checked.
+    Skipped(&'a KnownDivergence),
+    /// The backend was expected to diverge, but doesn't anymore, so the known
+    /// divergence can probably be removed.
+    Stale(&'a KnownDivergence),
+}
+
+/// A registry of known divergences of backends, used to only report new divergences in
+/// corpus runs and consistency checks.
+///
+/// The registry is usually loaded from a TOML file with one entry per divergence.
+/// `file` is a glob pattern of file names, `page` is optional and numbered from 1,
+/// `expect` is `failure`, `difference` (with a `threshold` between 0.0 and 1.0) or
+/// `skip`, and `reason` is optional:
+///
+/// ```toml
+/// [[divergence]]
+/// backend = "serenity"
+/// file = "shading/*.pdf"
+/// expect = "failure"
+/// reason = "shading types 4 to 7 are not supported"
+///
+/// [[divergence]]
+/// backend = "pdfjs"
+/// file = "blend/multiply.pdf"
+/// page = 2
+/// expect = "difference"
+/// threshold = 0.2
+/// ```
+///
+/// Entries for a page take precedence over entries for the whole file. Otherwise, the
+/// first matching entry applies.
+#[derive(Clone, Debug, Default)]
+pub struct Expectations {
+    /// The known divergences.
+    pub divergences: Vec<KnownDivergence>,
+}
+
+impl Expectations {
+    /// Load a registry from a TOML file.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, String> {
+        let path = path.as_ref();
+        let text = fs::read_to_string(path)
+            .map_err(|e| format!("failed to read {}: {e}", path.display()))?;
+        Self::parse(&text).map_err(|e| format!("{}: {e}", path.display()))
+    }
+
+    /// Parse a registry from TOML, see [`Expectations`] for the format.
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let table = text.parse::<Table>().map_err(|e| e.to_string())?;
+        let mut expectations = Self::default();
+
+        for (key, value) in &table {
+            if key != "divergence" {
+                return Err(format!("unknown key `{key}`"));
+            }
+
+            let entries = value
+                .as_array()
+                .ok_or_else(|| "`divergence` must be an array of tables".to_string())?;
+
+            for (i, entry) in entries.iter().enumerate() {
+                let divergence =
+                    parse_divergence(entry).map_err(|e| format!("divergence {}: {e}", i + 1))?;
+                expectations.divergences.push(divergence);
+            }
+        }
+
+        Ok(expectations)
+    }
+
+    /// Add a known divergence.
+    pub fn push(&mut self, divergence: KnownDivergence) {
+        self.divergences.push(divergence);
+    }
+
+    /// Whether there are no known divergences.
+    pub fn is_empty(&self) -> bool {
+        self.divergences.is_empty()
+    }
+
+    /// The known difference or skip of a backend on a page of a file.
+    pub fn find(&self, file: &str, page: usize, backend: &Backend) -> Option<&KnownDivergence> {
+        let mut matching = self
+            .divergences
+            .iter()
+            .filter(|d| d.matches(file, backend) && d.expectation != Expectation::Failure);
+
+        matching
+            .clone()
+            .find(|d| d.page == Some(page))
+            .or_else(|| matching.find(|d| d.page.is_none()))
+    }
+
+    /// Judge whether a backend rendered a file as expected.
+    pub fn judge_render(&self, file: &str, backend: &Backend, failed: bool) -> Verdict<'_> {
+        let divergence = self.divergences.iter().find(|d| {
+            d.matches(file, backend)
+                && d.page.is_none()
+                && !matches!(d.expectation, Expectation::Difference(_))
+        });
+
+        match (divergence, failed) {
+            (Some(d), _) if d.expectation == Expectation::Skip => Verdict::Skipped(d),
+            (Some(d), true) => Verdict::Expected(d),
+            (Some(d), false) => Verdict::Stale(d),
+            (None, true) => Verdict::Diverged,
+            (None, false) => Verdict::Pass,
+        }
+    }
+
+    /// Judge whether a page of a backend that differs from the others by `distance`
+    /// is as expected, given the fraction of pixels above which a page diverges.
+    pub fn judge_page(
+        &self,
+        file: &str,
+        page: usize,
+        backend: &Backend,
+        distance: f32,
+        threshold: f32,
+    ) -> Verdict<'_> {
+        let divergence = self.find(file, page, backend);
+
+        match divergence.map(|d| (d, d.expectation)) {
+            Some((d, Expectation::Skip)) => Verdict::Skipped(d),
+            Some((_, Expectation::Difference(max))) if distance > max => Verdict::Diverged,
+            Some((d, _)) if distance > threshold => Verdict::Expected(d),
+            Some((d, _)) => Verdict::Stale(d),
+            None if distance > threshold => Verdict::Diverged,
+            None => Verdict::Pass,
+        }
+    }
+}
+
+fn parse_divergence(value: &Value) -> Result<KnownDivergence, String> {
+    let table = value
+        .as_table()
+        .ok_or_else(|| "expected a table".to_string())?;
+
+    let mut backend = None;
+    let mut file = None;
+    let mut page = None;
+    let mut expect = None;
+    let mut threshold = None;
+    let mut reason = None;
+
+    for (key, value) in table {
+        let string = || {
+            value
+                .as_str()
+                .map(str::to_string)
+                .ok_or_else(|| format!("`{key}` must be a string"))
+        };
+
+        match key.as_str() {
+            "backend" => {
+                let name = string()?;
+                backend = Some(
+                    Backend::from_name(&name).ok_or_else(|| format!("unknown backend `{name}`"))?,
+                );
+            }
+            "file" => {
+                let pattern = string()?;
+                Pattern::new(&pattern).map_err(|e| format!("invalid pattern `{pattern}`: {e}"))?;
+                file = Some(pattern);
+            }
+            "page" => match value.as_integer() {
+                Some(n) if n >= 1 => page = Some(n as usize - 1),
+                _ => return Err("`page` must be a page number, starting at 1".to_string()),
+            },
+            "expect" => expect = Some(string()?),
+            "threshold" => {
+                let value = value
+                    .as_float()
+                    .or_else(|| value.as_integer().map(|n| n as f64))
+                    .filter(|n| (0.0..=1.0).contains(n))
+                    .ok_or_else(|| "`threshold` must be a number from 0 to 1".to_string())?;
+                threshold = Some(value as f32);
+            }
+            "reason" => reason = Some(string()?),
+            _ => return Err(format!("unknown key `{key}`")),
+        }
+    }
+
+    let expectation = match (expect.as_deref(), threshold) {
+        (Some("failure"), None) => Expectation::Failure,
+        (Some("difference"), Some(threshold)) => Expectation::Difference(threshold),
+        (Some("difference"), None) => return Err("`difference` requires a `threshold`".into()),
+        (Some("skip"), None) => Expectation::Skip,
+        (Some("failure" | "skip"), Some(_)) => {
+            return Err("`threshold` only applies to `difference`".into())
+        }
+        (Some(other), _) => {
+            return Err(format!(
+                "unknown expectation `{other}` (expected failure, difference or skip)"
+            ))
+        }
+        (None, _) => return Err("missing `expect`".into()),
+    };
+
+    if expectation == Expectation::Failure && page.is_some() {
+        return Err("a failure applies to the whole file and can't have a `page`".into());
+    }
+
+    Ok(KnownDivergence {
+        backend: backend.ok_or_else(|| "missing `backend`".to_string())?,
+        file: file.ok_or_else(|| "missing `file`".to_string())?,
+        page,
+        expectation,
+        reason,
+    })
+}
@@ -0,0 +1,128 @@
+use crate::composite::{draw_tiles, CompositeOptions, Layout, Tile};
+use std::path::Path;
+use std::time::Duration;
+use tiny_skia::{Color, Pixmap, PixmapPaint, Transform};
+
+/// The options that should be applied when building a flip-book.
+#[derive(Copy, Clone)]
+pub struct FlipbookOptions {
+    /// How long each frame is shown.
+    pub delay: Duration,
+    /// The width of the colored border around each page relative to the smaller
+    /// side of the page, or `None` for no border.
+    pub border_width: Option<f32>,
+    /// Whether the label of each tile should be drawn above it.
+    pub labels: bool,
+    /// Whether the regions in which a page differs from the consensus of all pages
+    /// that were rendered successfully should be outlined in red.
+    pub annotate: bool,
+}
+
+impl Default for FlipbookOptions {
+    fn default() -> Self {
+        Self {
+            delay: Duration::from_millis(700),
+            border_width: Some(1.0 / 50.0),
+            labels: true,
+            annotate: false,
+        }
+    }
+}
+
+/// An animation that cycles through the renders of a page, one frame per backend.
+///
+/// Flipping between renders at the same position makes small shifts and weight
+/// differences much easier to spot than comparing them side by side.
+pub struct Flipbook {
+    /// The frames, all of the same size.
+    pub frames: Vec<Pixmap>,
+    /// How long each frame is shown.
+    pub delay: Duration,
+}
+
+impl Flipbook {
+    /// Encode the flip-book as an animated PNG that loops forever.
+    pub fn encode_apng(&self) -> Result<Vec<u8>, String> {
+        let Some(first) = self.frames.first() else {
+            return Err("flip-book has no frames".to_string());
+        };
+
+        let mut data = vec![];
+        let mut encoder = png::Encoder::new(&mut data, first.width(), first.height());
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+
+        let delay = self.delay.as_millis().clamp(1, u16::MAX as u128) as u16;
+        let map_err = |e: png::EncodingError| format!("failed to encode flip-book: {e}");
+        encoder
+            .set_animated(self.frames.len() as u32, 0)
+            .map_err(map_err)?;
+        encoder.set_frame_delay(delay, 1000).map_err(map_err)?;
+
+        let mut writer = encoder.write_header().map_err(map_err)?;
+
+        for frame in &self.frames {
+            let pixels = frame
+                .pixels()
+                .iter()
+                .flat_map(|p| {
+                    let p = p.demultiply();
+                    [p.red(), p.green(), p.blue(), p.alpha()]
+                })
+                .collect::<Vec<_>>();
+
+            writer.write_image_data(&pixels).map_err(map_err)?;
+        }
+
+        writer.finish().map_err(map_err)?;
+
+        Ok(data)
+    }
+
+    /// Save the flip-book as an animated PNG.
+    pub fn save_apng(&self, path: impl AsRef<Path>) -> Result<(), String> {
+        std::fs::write(path, self.encode_apng()?)
+            .map_err(|e| format!("failed to write flip-book: {e}"))
+    }
+}
+
+/// Build a flip-book from the renders of a page, in the order they were given in.
+///
+/// Each frame shows one tile with its label and border. Frames are padded to the
+/// size of the largest tile, with the pages aligned at the top-left corner.
+pub fn flipbook(tiles: &[Tile], options: &FlipbookOptions) -> Flipbook {
+    let composite_options = CompositeOptions {
+        layout: Layout::Horizontal,
+        spacing: 0,
+        border_width: options.border_width,
+        labels: options.labels,
+        annotate: options.annotate,
+    };
+
+    let cells = draw_tiles(tiles, &composite_options);
+
+    let width = cells.iter().map(|c| c.width()).max().unwrap_or(1);
+    let height = cells.iter().map(|c| c.height()).max().unwrap_or(1);
+
+    let frames = cells
+        .iter()
+        .map(|cell| {
+            let mut frame = Pixmap::new(width, height).unwrap();
+            frame.fill(Color::WHITE);
+            frame.draw_pixmap(
+                0,
+                0,
+                cell.as_ref(),
+                &PixmapPaint::default(),
+                Transform::identity(),
+                None,
+            );
+            frame
+        })
+        .collect();
+
+    Flipbook {
+        frames,
+        delay: options.delay,
+    }
+}
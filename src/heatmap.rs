@@ -0,0 +1,245 @@
+use crate::consensus::majority;
+use crate::diff::{fade, pixel, pixels_match, DiffOptions};
+use crate::normalize::{normalize, SizePolicy};
+use crate::renderer::{Backend, RenderOptions, Renderer};
+use crate::text::{draw_text, text_height, text_width};
+use tiny_skia::{Color, Paint, Pixmap, PixmapPaint, Rect, Transform};
+
+/// The options that should be applied when building an agreement heatmap.
+#[derive(Copy, Clone)]
+pub struct HeatmapOptions {
+    /// How pages of different sizes should be brought to a common size.
+    pub size_policy: SizePolicy,
+    /// The options used to decide whether two backends agree on a pixel.
+    pub diff: DiffOptions,
+}
+
+impl Default for HeatmapOptions {
+    fn default() -> Self {
+        Self {
+            size_policy: SizePolicy::Pad,
+            diff: DiffOptions::default(),
+        }
+    }
+}
+
+/// A heatmap showing how many backends agree with the majority on each pixel of a page.
+///
+/// Pixels on which all backends agree are drawn as a faded version of the page. If all
+/// but one backend agree, the pixel is drawn in the color of the dissenting backend.
+/// Otherwise, the pixel is drawn in a color between yellow and red, depending on how
+/// many backends disagree with the majority.
+pub struct Heatmap {
+    /// The heatmap, without a legend.
+    pub image: Pixmap,
+    /// The fraction of pixels on which not all backends agree.
+    pub contested: f32,
+    backends: Vec<Backend>,
+}
+
+impl Heatmap {
+    /// The backends the heatmap was built from.
+    pub fn backends(&self) -> &[Backend] {
+        &self.backends
+    }
+
+    /// Create an image of the heatmap with a legend of all colors below it.
+    pub fn with_legend(&self) -> Pixmap {
+        let count = self.backends.len();
+        let mut entries = vec![(fade([0, 0, 0, 255]), "all agree".to_string())];
+
+        if count >= 3 {
+            for backend in &self.backends {
+                let (r, g, b) = backend.color();
+                entries.push(([r, g, b, 255], format!("only {} differs", backend.name())));
+            }
+        }
+
+        // With three or more backends, a single dissenting backend is shown in its own color.
+        let max_heat = if count >= 3 {
+            count - 2
+        } else {
+            count.saturating_sub(1)
+        };
+
+        for agreeing in (1..=max_heat).rev() {
+            entries.push((heat(agreeing, count), format!("{agreeing}/{count} agree")));
+        }
+
+        draw_legend(&self.image, &entries)
+    }
+}
+
+impl Renderer {
+    /// Render a PDF with multiple backends and build an agreement heatmap of each page.
+    ///
+    /// Backends that didn't produce a page are left out of the heatmap of that page.
+    /// Returns an error if any of the backends fails to render the PDF.
+    pub fn render_heatmaps(
+        &self,
+        backends: &[Backend],
+        buf: &[u8],
+        options: &RenderOptions,
+        heatmap_options: &HeatmapOptions,
+    ) -> Result<Vec<Heatmap>, String> {
+        let documents = self.render_all_as_pixmaps(backends, buf, options)?;
+        let page_count = documents.iter().map(|d| d.1.len()).max().unwrap_or(0);
+
+        Ok((0..page_count)
+            .map(|i| {
+                let pages = documents
+                    .iter()
+                    .filter_map(|(backend, pixmaps)| {
+                        Some((backend.clone(), pixmaps.get(i)?.clone()))
+                    })
+                    .collect::<Vec<_>>();
+
+                agreement_heatmap(&pages, heatmap_options)
+            })
+            .collect())
+    }
+}
+
+/// Build an agreement heatmap of a page rendered with multiple backends.
+pub fn agreement_heatmap(pages: &[(Backend, Pixmap)], options: &HeatmapOptions) -> Heatmap {
+    let pixmaps = pages.iter().map(|(_, p)| p).collect::<Vec<_>>();
+    let normalized = normalize(&pixmaps, options.size_policy);
+    let (width, height) = (normalized.width, normalized.height);
+    let count = pages.len();
+
+    let mut image = Pixmap::new(width, height).unwrap();
+    image.fill(Color::WHITE);
+
+    if count == 0 {
+        return Heatmap {
+            image,
+            contested: 0.0,
+            backends: vec![],
+        };
+    }
+
+    let data = image.data_mut();
+    let mut samples = Vec::with_capacity(count);
+    let mut contested = 0;
+
+    for y in 0..height {
+        for x in 0..width {
+            samples.clear();
+            samples.extend(normalized.pixmaps.iter().map(|p| pixel(p, x, y).unwrap()));
+
+            let majority = majority(&samples, options.diff.tolerance);
+            let agrees = samples
+                .iter()
+                .map(|s| pixels_match(*s, majority, options.diff.tolerance))
+                .collect::<Vec<_>>();
+            let agreeing = agrees.iter().filter(|a| **a).count();
+
+            let color = if agreeing == count {
+                fade(majority)
+            } else if count >= 3 && agreeing == count - 1 {
+                let dissenter = agrees.iter().position(|a| !a).unwrap();
+                let (r, g, b) = pages[dissenter].0.color();
+                [r, g, b, 255]
+            } else {
+                heat(agreeing, count)
+            };
+
+            if agreeing != count {
+                contested += 1;
+            }
+
+            let idx = (y * width + x) as usize * 4;
+            data[idx..idx + 4].copy_from_slice(&color);
+        }
+    }
+
+    let total = width as usize * height as usize;
+
+    Heatmap {
+        image,
+        contested: if total == 0 {
+            0.0
+        } else {
+            contested as f32 / total as f32
+        },
+        backends: pages.iter().map(|(b, _)| b.clone()).collect(),
+    }
+}
+
+/// The color of a pixel on which only some of the backends agree, going from
+/// yellow if all but one backend agree to red if only a single backend agrees
+/// with the majority.
+fn heat(agreeing: usize, count: usize) -> [u8; 4] {
+    let t = if count <= 2 {
+        1.0
+    } else {
+        1.0 - (agreeing - 1) as f32 / (count - 2) as f32
+    };
+    let lerp = |from: f32, to: f32| (from + (to - from) * t) as u8;
+
+    [lerp(255.0, 200.0), lerp(210.0, 0.0), 0, 255]
+}
+
+const LEGEND_TEXT_SCALE: u32 = 2;
+const LEGEND_PADDING: u32 = 8;
+const LEGEND_GAP: u32 = 16;
+
+fn draw_legend(image: &Pixmap, entries: &[([u8; 4], String)]) -> Pixmap {
+    let swatch = text_height(LEGEND_TEXT_SCALE);
+    let row_height = swatch + LEGEND_PADDING;
+    let entry_width = |label: &str| swatch + 6 + text_width(label, LEGEND_TEXT_SCALE);
+
+    let width = image.width().max(
+        entries
+            .iter()
+            .map(|(_, label)| entry_width(label) + 2 * LEGEND_PADDING)
+            .max()
+            .unwrap_or(0),
+    );
+
+    // Flow the entries from left to right, wrapping at the width of the image.
+    let mut positions = vec![];
+    let (mut x, mut y) = (LEGEND_PADDING, LEGEND_PADDING);
+
+    for (_, label) in entries {
+        if x > LEGEND_PADDING && x + entry_width(label) + LEGEND_PADDING > width {
+            x = LEGEND_PADDING;
+            y += row_height;
+        }
+
+        positions.push((x, y));
+        x += entry_width(label) + LEGEND_GAP;
+    }
+
+    let legend_height = y + row_height;
+    let mut pixmap = Pixmap::new(width, image.height() + legend_height).unwrap();
+    pixmap.fill(Color::WHITE);
+    pixmap.draw_pixmap(
+        0,
+        0,
+        image.as_ref(),
+        &PixmapPaint::default(),
+        Transform::identity(),
+        None,
+    );
+
+    for ((color, label), (x, y)) in entries.iter().zip(positions) {
+        let y = y + image.height();
+        let mut paint = Paint::default();
+        paint.set_color_rgba8(color[0], color[1], color[2], color[3]);
+
+        let rect = Rect::from_xywh(x as f32, y as f32, swatch as f32, swatch as f32).unwrap();
+        pixmap.fill_rect(rect, &paint, Transform::identity(), None);
+
+        draw_text(
+            &mut pixmap,
+            label,
+            (x + swatch + 6) as f32,
+            y as f32,
+            LEGEND_TEXT_SCALE,
+            (0, 0, 0),
+        );
+    }
+
+    pixmap
+}
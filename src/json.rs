@@ -0,0 +1,401 @@
+use std::fmt::{self, Display, Formatter, Write};
+
+/// A JSON value, used to write and read the machine-readable reports.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum Json {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>),
+}
+
+impl Json {
+    /// Create an object from key-value pairs, keeping their order.
+    pub(crate) fn object<'a>(entries: impl IntoIterator<Item = (&'a str, Json)>) -> Self {
+        Json::Object(
+            entries
+                .into_iter()
+                .map(|(key, value)| (key.to_string(), value))
+                .collect(),
+        )
+    }
+
+    /// Parse a JSON document.
+    pub(crate) fn parse(text: &str) -> Result<Self, String> {
+        let mut parser = Parser { text, pos: 0 };
+        let value = parser.value()?;
+        parser.whitespace();
+
+        if parser.pos < text.len() {
+            return Err(parser.error("unexpected trailing characters"));
+        }
+
+        Ok(value)
+    }
+
+    /// The value of a key, if this is an object that has it.
+    pub(crate) fn get(&self, key: &str) -> Option<&Json> {
+        match self {
+            Json::Object(entries) => entries.iter().find(|(k, _)| k == key).map(|(_, v)| v),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn as_str(&self) -> Option<&str> {
+        match self {
+            Json::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn as_f64(&self) -> Option<f64> {
+        match self {
+            Json::Number(n) => Some(*n),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn as_array(&self) -> Option<&[Json]> {
+        match self {
+            Json::Array(items) => Some(items),
+            _ => None,
+        }
+    }
+
+    fn write(&self, f: &mut Formatter, indent: usize) -> fmt::Result {
+        let pad = |f: &mut Formatter, indent: usize| write!(f, "{:1$}", "", indent * 2);
+
+        match self {
+            Json::Null => f.write_str("null"),
+            Json::Bool(b) => write!(f, "{b}"),
+            // JSON has no representation for infinity and NaN.
+            Json::Number(n) if !n.is_finite() => f.write_str("null"),
+            Json::Number(n) => write!(f, "{n}"),
+            Json::String(s) => write_string(f, s),
+            Json::Array(items) if items.is_empty() => f.write_str("[]"),
+            Json::Array(items) => {
+                f.write_str("[\n")?;
+                for (i, item) in items.iter().enumerate() {
+                    pad(f, indent + 1)?;
+                    item.write(f, indent + 1)?;
+                    f.write_str(if i + 1 < items.len() { ",\n" } else { "\n" })?;
+                }
+                pad(f, indent)?;
+                f.write_str("]")
+            }
+            Json::Object(entries) if entries.is_empty() => f.write_str("{}"),
+            Json::Object(entries) => {
+                f.write_str("{\n")?;
+                for (i, (key, value)) in entries.iter().enumerate() {
+                    pad(f, indent + 1)?;
+                    write_string(f, key)?;
+                    f.write_str(": ")?;
+                    value.write(f, indent + 1)?;
+                    f.write_str(if i + 1 < entries.len() { ",\n" } else { "\n" })?;
+                }
+                pad(f, indent)?;
+                f.write_str("}")
+            }
+        }
+    }
+}
+
+impl Display for Json {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        self.write(f, 0)
+    }
+}
+
+impl From<&str> for Json {
+    fn from(value: &str) -> Self {
+        Json::String(value.to_string())
+    }
+}
+
+impl From<String> for Json {
+    fn from(value: String) -> Self {
+        Json::String(value)
+    }
+}
+
+impl From<bool> for Json {
+    fn from(value: bool) -> Self {
+        Json::Bool(value)
+    }
+}
+
+impl From<f32> for Json {
+    fn from(value: f32) -> Self {
+        // Go through the shortest decimal representation, so that 0.1 doesn't
+        // turn into 0.10000000149011612.
+        Json::Number(value.to_string().parse().unwrap_or(f64::NAN))
+    }
+}
+
+impl From<f64> for Json {
+    fn from(value: f64) -> Self {
+        Json::Number(value)
+    }
+}
+
+impl From<u32> for Json {
+    fn from(value: u32) -> Self {
+        Json::Number(value as f64)
+    }
+}
+
+impl From<usize> for Json {
+    fn from(value: usize) -> Self {
+        Json::Number(value as f64)
+    }
+}
+
+impl<T: Into<Json>> From<Option<T>> for Json {
+    fn from(value: Option<T>) -> Self {
+        value.map_or(Json::Null, Into::into)
+    }
+}
+
+impl<T: Into<Json>> From<Vec<T>> for Json {
+    fn from(value: Vec<T>) -> Self {
+        Json::Array(value.into_iter().map(Into::into).collect())
+    }
+}
+
+fn write_string(f: &mut Formatter, s: &str) -> fmt::Result {
+    f.write_char('"')?;
+
+    for c in s.chars() {
+        match c {
+            '"' => f.write_str("\\\"")?,
+            '\\' => f.write_str("\\\\")?,
+            '\n' => f.write_str("\\n")?,
+            '\r' => f.write_str("\\r")?,
+            '\t' => f.write_str("\\t")?,
+            c if (c as u32) < 0x20 => write!(f, "\\u{:04x}", c as u32)?,
+            c => f.write_char(c)?,
+        }
+    }
+
+    f.write_char('"')
+}
+
+struct Parser<'a> {
+    text: &'a str,
+    pos: usize,
+}
+
+impl Parser<'_> {
+    fn error(&self, message: &str) -> String {
+        let line = self.text[..self.pos].matches('\n').count() + 1;
+        format!("{message} at line {line}")
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.text.as_bytes().get(self.pos).copied()
+    }
+
+    fn whitespace(&mut self) {
+        while matches!(self.peek(), Some(b' ' | b'\t' | b'\n' | b'\r')) {
+            self.pos += 1;
+        }
+    }
+
+    fn eat(&mut self, byte: u8) -> bool {
+        self.whitespace();
+        let found = self.peek() == Some(byte);
+        self.pos += found as usize;
+        found
+    }
+
+    fn expect(&mut self, byte: u8) -> Result<(), String> {
+        if self.eat(byte) {
+            Ok(())
+        } else {
+            Err(self.error(&format!("expected `{}`", byte as char)))
+        }
+    }
+
+    fn keyword(&mut self, keyword: &str, value: Json) -> Result<Json, String> {
+        if self.text[self.pos..].starts_with(keyword) {
+            self.pos += keyword.len();
+            Ok(value)
+        } else {
+            Err(self.error("expected a value"))
+        }
+    }
+
+    fn value(&mut self) -> Result<Json, String> {
+        self.whitespace();
+
+        match self.peek() {
+            Some(b'n') => self.keyword("null", Json::Null),
+            Some(b't') => self.keyword("true", Json::Bool(true)),
+            Some(b'f') => self.keyword("false", Json::Bool(false)),
+            Some(b'"') => self.string().map(Json::String),
+            Some(b'[') => {
+                self.pos += 1;
+                let mut items = vec![];
+
+                if !self.eat(b']') {
+                    loop {
+                        items.push(self.value()?);
+
+                        if self.eat(b']') {
+                            break;
+                        }
+
+                        self.expect(b',')?;
+                    }
+                }
+
+                Ok(Json::Array(items))
+            }
+            Some(b'{') => {
+                self.pos += 1;
+                let mut entries = vec![];
+
+                if !self.eat(b'}') {
+                    loop {
+                        self.whitespace();
+                        let key = self.string()?;
+                        self.expect(b':')?;
+                        entries.push((key, self.value()?));
+
+                        if self.eat(b'}') {
+                            break;
+                        }
+
+                        self.expect(b',')?;
+                    }
+                }
+
+                Ok(Json::Object(entries))
+            }
+            Some(b'-' | b'0'..=b'9') => {
+                let start = self.pos;
+
+                while matches!(
+                    self.peek(),
+                    Some(b'-' | b'+' | b'.' | b'e' | b'E' | b'0'..=b'9')
+                ) {
+                    self.pos += 1;
+                }
+
+                self.text[start..self.pos]
+                    .parse()
+                    .map(Json::Number)
+                    .map_err(|_| self.error("invalid number"))
+            }
+            _ => Err(self.error("expected a value")),
+        }
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        if self.peek() != Some(b'"') {
+            return Err(self.error("expected a string"));
+        }
+
+        self.pos += 1;
+        let mut string = String::new();
+
+        loop {
+            let rest = &self.text[self.pos..];
+            let Some(c) = rest.chars().next() else {
+                return Err(self.error("unterminated string"));
+            };
+            self.pos += c.len_utf8();
+
+            match c {
+                '"' => return Ok(string),
+                '\\' => {
+                    let escape = self
+                        .peek()
+                        .ok_or_else(|| self.error("unterminated string"))?;
+                    self.pos += 1;
+
+                    match escape {
+                        b'"' => string.push('"'),
+                        b'\\' => string.push('\\'),
+                        b'/' => string.push('/'),
+                        b'b' => string.push('\u{8}'),
+                        b'f' => string.push('\u{c}'),
+                        b'n' => string.push('\n'),
+                        b'r' => string.push('\r'),
+                        b't' => string.push('\t'),
+                        b'u' => {
+                            let mut code = self.hex()?;
+
+                            // Characters outside the basic plane are written as surrogate pairs.
+                            if (0xd800..0xdc00).contains(&code)
+                                && self.text[self.pos..].starts_with("\\u")
+                            {
+                                self.pos += 2;
+                                let low = self.hex()?;
+                                code = 0x10000
+                                    + ((code - 0xd800) << 10)
+                                    + (low.wrapping_sub(0xdc00) & 0x3ff);
+                            }
+
+                            string
+                                .push(char::from_u32(code).unwrap_or(char::REPLACEMENT_CHARACTER));
+                        }
+                        _ => return Err(self.error("invalid escape sequence")),
+                    }
+                }
+                c => string.push(c),
+            }
+        }
+    }
+
+    fn hex(&mut self) -> Result<u32, String> {
+        let digits = self
+            .text
+            .get(self.pos..self.pos + 4)
+            .ok_or_else(|| self.error("invalid escape sequence"))?;
+        let code =
+            u32::from_str_radix(digits, 16).map_err(|_| self.error("invalid escape sequence"))?;
+        self.pos += 4;
+        Ok(code)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn json_roundtrip() {
+        let value = Json::object([
+            ("null", Json::Null),
+            ("bool", true.into()),
+            ("number", 0.1f32.into()),
+            ("string", "\"quoted\"\n\tü\u{1}".into()),
+            ("empty", Json::Array(vec![])),
+            ("array", vec![1u32, 2, 3].into()),
+            ("object", Json::object([("nested", Json::object([]))])),
+        ]);
+
+        assert_eq!(Json::parse(&value.to_string()), Ok(value));
+
+        // JSON has no representation for infinity and NaN.
+        assert_eq!(
+            Json::parse(&Json::Number(f64::NAN).to_string()),
+            Ok(Json::Null)
+        );
+    }
+
+    #[test]
+    fn json_parse_errors() {
+        for (text, message) in [
+            ("", "at line 1"),
+            ("{\n\"a\": }", "at line 2"),
+            ("[1, 2] 3", "unexpected trailing characters"),
+        ] {
+            let error = Json::parse(text).unwrap_err();
+            assert!(error.contains(message), "{text:?}: {error}");
+        }
+    }
+}
@@ -23,12 +23,51 @@ Docker must be installed and running. Sitro automatically uses the Docker image
 vallaris/sitro-backends:<crate-version>
 ```
 
-Set `SITRO_DOCKER_IMAGE` to override the image. The Quartz and Hayro backends run natively with no additional setup.
+Set `SITRO_DOCKER_IMAGE` to override the image. Additional images can run side by side, listed as `<LABEL>=<IMAGE>` pairs in `SITRO_DOCKER_IMAGES`, like `old=vallaris/sitro-backends:0.1.0,new=vallaris/sitro-backends:0.2.0`. Docker-based backends tagged with a label, like `pdfium@old`, render in the image with that label, see [`Backend::in_image`]. The Quartz and Hayro backends run natively with no additional setup.
+
+# Caching
+
+Set `SITRO_CACHE_DIR` to cache renders on disk. A render is reused when the PDF, the backend, the Docker image (or the versions of the hayro crates) and the render options are the same. `SITRO_CACHE_MAX_SIZE` limits the cache to a number of megabytes (4096 by default), evicting the least recently used renders first. Use [`set_render_cache`] to pick the cache of [`RENDER_INSTANCE`] in code instead. Quartz renders are never cached.
 */
 
 #![deny(unsafe_code)]
 #![warn(missing_docs)]
 #![allow(dead_code)]
 
+mod align;
+mod composite;
+mod consensus;
+mod consistency;
+mod diff;
+mod expectations;
+mod flipbook;
+mod heatmap;
+mod json;
+mod mask;
+#[cfg(feature = "hayro")]
+mod minimize;
+mod normalize;
+mod regions;
 mod renderer;
+mod report;
+mod server;
+mod snapshot;
+mod text;
+
+pub use align::*;
+pub use composite::*;
+pub use consensus::*;
+pub use consistency::*;
+pub use diff::*;
+pub use expectations::*;
+pub use flipbook::*;
+pub use heatmap::*;
+pub use mask::*;
+#[cfg(feature = "hayro")]
+pub use minimize::*;
+pub use normalize::*;
+pub use regions::*;
 pub use renderer::*;
+pub use report::*;
+pub use server::*;
+pub use snapshot::*;
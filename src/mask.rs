@@ -0,0 +1,158 @@
+use crate::renderer::RenderOptions;
+use tiny_skia::{Pixmap, Rect};
+
+/// A set of pixels that should be excluded when comparing rendered pages.
+#[derive(Clone)]
+pub struct Mask {
+    width: u32,
+    height: u32,
+    data: Vec<bool>,
+}
+
+impl Mask {
+    /// Create a mask of the given size that doesn't exclude any pixels.
+    pub fn new(width: u32, height: u32) -> Self {
+        Self {
+            width,
+            height,
+            data: vec![false; width as usize * height as usize],
+        }
+    }
+
+    /// Create a mask from an image.
+    ///
+    /// Dark pixels are excluded, so both black on white and black on transparent
+    /// images can be used.
+    pub fn from_pixmap(pixmap: &Pixmap) -> Self {
+        let data = pixmap
+            .data()
+            .chunks_exact(4)
+            .map(|p| {
+                // Premultiplied pixels, so blend towards white for transparent areas.
+                let white = 255 - p[3] as u32;
+                let luma = ((p[0] as u32 + white) * 299
+                    + (p[1] as u32 + white) * 587
+                    + (p[2] as u32 + white) * 114)
+                    / 1000;
+                luma < 128
+            })
+            .collect();
+
+        Self {
+            width: pixmap.width(),
+            height: pixmap.height(),
+            data,
+        }
+    }
+
+    /// The width of the mask.
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    /// The height of the mask.
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// Whether the pixel at the given position is excluded.
+    pub fn is_masked(&self, x: u32, y: u32) -> bool {
+        x < self.width && y < self.height && self.data[(y * self.width + x) as usize]
+    }
+
+    /// Whether the mask doesn't exclude any pixels.
+    pub fn is_empty(&self) -> bool {
+        !self.data.iter().any(|m| *m)
+    }
+
+    /// Exclude a rectangle given in PDF user space.
+    ///
+    /// The rectangle is relative to the bottom-left corner of the page, with the y axis
+    /// pointing up, just like [`Region::user_space`](crate::Region::user_space). The scale
+    /// of the render options is used to convert it into pixels.
+    pub fn add_rect(&mut self, rect: &Rect, options: &RenderOptions) {
+        let height = self.height as f32;
+        let clamp = |v: f32, max: u32| v.max(0.0).min(max as f32) as u32;
+
+        let left = clamp((rect.left() * options.scale).floor(), self.width);
+        let right = clamp((rect.right() * options.scale).ceil(), self.width);
+        let top = clamp(
+            (height - rect.bottom() * options.scale).floor(),
+            self.height,
+        );
+        let bottom = clamp((height - rect.top() * options.scale).ceil(), self.height);
+
+        for y in top..bottom {
+            for x in left..right {
+                self.data[(y * self.width + x) as usize] = true;
+            }
+        }
+    }
+
+    /// Exclude all pixels that are excluded by another mask.
+    ///
+    /// The other mask is scaled to the size of this one.
+    pub fn add_mask(&mut self, other: &Mask) {
+        if other.width == 0 || other.height == 0 {
+            return;
+        }
+
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let ox = (x as u64 * other.width as u64 / self.width as u64) as u32;
+                let oy = (y as u64 * other.height as u64 / self.height as u64) as u32;
+
+                if other.is_masked(ox, oy) {
+                    self.data[(y * self.width + x) as usize] = true;
+                }
+            }
+        }
+    }
+}
+
+/// An area of a document that should be excluded when comparing rendered pages.
+#[derive(Clone)]
+pub enum IgnoreRegion {
+    /// A rectangle in PDF user space, see [`Mask::add_rect`].
+    Rect {
+        /// The index of the page, or `None` for all pages.
+        page: Option<usize>,
+        /// The rectangle.
+        rect: Rect,
+    },
+    /// A mask, which is scaled to the size of the rendered page.
+    Mask {
+        /// The index of the page, or `None` for all pages.
+        page: Option<usize>,
+        /// The mask.
+        mask: Mask,
+    },
+}
+
+/// Build the mask of a rendered page from the regions that should be ignored.
+///
+/// Returns `None` if none of the regions apply to the page.
+pub fn page_mask(
+    regions: &[IgnoreRegion],
+    page: usize,
+    width: u32,
+    height: u32,
+    options: &RenderOptions,
+) -> Option<Mask> {
+    let applies = |p: &Option<usize>| p.is_none_or(|p| p == page);
+    let mut mask = None;
+
+    for region in regions {
+        match region {
+            IgnoreRegion::Rect { page, rect } if applies(page) => mask
+                .get_or_insert_with(|| Mask::new(width, height))
+                .add_rect(rect, options),
+            IgnoreRegion::Mask { page, mask: m } if applies(page) => mask
+                .get_or_insert_with(|| Mask::new(width, height))
+                .add_mask(m),
+            _ => {}
+        }
+    }
+
+    mask
+}
@@ -0,0 +1,149 @@
+use super::document::{convert, write_value, Dict, Value};
+use hayro::hayro_syntax::content::UntypedIter;
+use hayro::hayro_syntax::object::Object as RawObject;
+use std::collections::HashMap;
+use std::ops::Range;
+
+/// An operator of a content stream with its operands.
+#[derive(Clone, Debug)]
+pub(crate) struct Operation {
+    operands: Vec<Value>,
+    operator: Vec<u8>,
+    /// The dictionary and data of an inline image, if the operator is `BI`.
+    image: Option<(Dict, Vec<u8>)>,
+}
+
+/// A content stream.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct Content {
+    pub operations: Vec<Operation>,
+}
+
+/// Operators that start a block and the operators that end it.
+const BLOCKS: [(&[u8], &[u8]); 4] = [
+    (b"q", b"Q"),
+    (b"BT", b"ET"),
+    (b"BMC", b"EMC"),
+    (b"BDC", b"EMC"),
+];
+
+impl Content {
+    /// Parse a decoded content stream.
+    pub fn parse(data: &[u8]) -> Self {
+        let mut iter = UntypedIter::new(data);
+        let mut operations = vec![];
+        // Content streams can't contain references.
+        let refs = &mut |_| Value::Null;
+
+        while let Some(instruction) = iter.next() {
+            let mut operands = vec![];
+            let mut image = None;
+
+            for operand in instruction.operands() {
+                match operand {
+                    RawObject::Stream(stream) => {
+                        let dict = match convert(&RawObject::Dict(stream.dict().clone()), refs) {
+                            Value::Dict(dict) => dict,
+                            _ => vec![],
+                        };
+                        image = Some((dict, stream.raw_data().to_vec()));
+                    }
+                    operand => operands.push(convert(operand, refs)),
+                }
+            }
+
+            operations.push(Operation {
+                operands,
+                operator: instruction.operator.to_vec(),
+                image,
+            });
+        }
+
+        Self { operations }
+    }
+
+    /// The operations that are kept.
+    pub fn filter(&self, kept: &[bool]) -> Self {
+        Self {
+            operations: self
+                .operations
+                .iter()
+                .zip(kept)
+                .filter(|(_, kept)| **kept)
+                .map(|(operation, _)| operation.clone())
+                .collect(),
+        }
+    }
+
+    /// Split the operations in a range into units that can be removed on their own: a
+    /// block from its start to its end, like `q ... Q`, or a single operation.
+    pub fn units(&self, range: Range<usize>) -> Vec<Range<usize>> {
+        let ends = self.block_ends();
+        let mut units = vec![];
+        let mut i = range.start;
+
+        while i < range.end {
+            let end = match ends.get(&i) {
+                Some(&end) if end < range.end => end + 1,
+                _ => i + 1,
+            };
+
+            units.push(i..end);
+            i = end;
+        }
+
+        units
+    }
+
+    /// The index of the operation that ends each block, by the index of the operation
+    /// that starts it. Unbalanced operators don't start or end blocks.
+    fn block_ends(&self) -> HashMap<usize, usize> {
+        let mut ends = HashMap::new();
+        let mut open: Vec<(usize, &[u8])> = vec![];
+
+        for (i, operation) in self.operations.iter().enumerate() {
+            let operator = operation.operator.as_slice();
+
+            if let Some((_, end)) = BLOCKS.iter().find(|(start, _)| *start == operator) {
+                open.push((i, end));
+            } else if open.last().is_some_and(|(_, end)| *end == operator) {
+                let (start, _) = open.pop().unwrap();
+                ends.insert(start, i);
+            }
+        }
+
+        ends
+    }
+
+    /// Write the content stream, with one operation per line.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = vec![];
+        let numbers = HashMap::new();
+
+        for operation in &self.operations {
+            for operand in &operation.operands {
+                write_value(&mut out, operand, &numbers);
+                out.push(b' ');
+            }
+
+            out.extend(&operation.operator);
+
+            if let Some((dict, data)) = &operation.image {
+                for (key, value) in dict {
+                    out.push(b' ');
+                    write_value(&mut out, &Value::Name(key.clone()), &numbers);
+                    out.push(b' ');
+                    write_value(&mut out, value, &numbers);
+                }
+
+                out.extend(b" ID ");
+                out.extend(data);
+                out.extend(b"\nEI");
+            }
+
+            out.push(b'\n');
+        }
+
+        out
+    }
+}
@@ -0,0 +1,569 @@
+use super::content::Content;
+use hayro::hayro_syntax::object::{
+    Dict as RawDict, MaybeRef, ObjRef, Object as RawObject, ObjectIdentifier, Rect,
+};
+use hayro::hayro_syntax::xref::XRef;
+use hayro::hayro_syntax::Pdf;
+use std::collections::hash_map::Entry;
+use std::collections::{HashMap, VecDeque};
+use std::io::Write;
+use std::sync::Arc;
+
+/// A PDF object that owns its data.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum Value {
+    Null,
+    Bool(bool),
+    Integer(i64),
+    Real(f64),
+    Name(Vec<u8>),
+    String(Vec<u8>),
+    Array(Vec<Value>),
+    Dict(Dict),
+    /// A reference to the object with the given index in [`Document::objects`].
+    Ref(usize),
+}
+
+/// The entries of a dictionary, in the order they are written in.
+pub(crate) type Dict = Vec<(Vec<u8>, Value)>;
+
+/// An indirect object.
+#[derive(Clone, Debug)]
+pub(crate) struct Object {
+    pub value: Value,
+    /// The raw data if the object is a stream, in which case the value is its dictionary.
+    pub stream: Option<Vec<u8>>,
+}
+
+impl Object {
+    const NULL: Object = Object {
+        value: Value::Null,
+        stream: None,
+    };
+}
+
+/// A page whose inherited attributes were resolved.
+#[derive(Clone, Debug)]
+pub(crate) struct Page {
+    /// The index of the page in the original document.
+    pub number: usize,
+    /// The index of the page object, whose dictionary has no parent and no contents.
+    pub object: usize,
+    /// The decoded content stream.
+    pub content: Content,
+}
+
+/// The parts of a PDF that affect how it renders, with a flat page tree and a single
+/// content stream per page, so that they can be removed piece by piece.
+#[derive(Clone, Debug)]
+pub(crate) struct Document {
+    pub objects: Vec<Object>,
+    pub pages: Vec<Page>,
+    /// The entries of the catalog other than the page tree.
+    pub catalog: Dict,
+}
+
+/// The catalog entries that are kept, because they change how pages are rendered.
+const CATALOG_KEYS: [&[u8]; 2] = [b"AcroForm", b"OCProperties"];
+
+/// The page entries that are resolved or rewritten when loading a document.
+const PAGE_KEYS: [&[u8]; 8] = [
+    b"Type",
+    b"Parent",
+    b"Contents",
+    b"Resources",
+    b"MediaBox",
+    b"CropBox",
+    b"Rotate",
+    b"Annots",
+];
+
+impl Document {
+    /// Parse a PDF.
+    ///
+    /// Only the pages and the objects they use are kept, along with the form and optional
+    /// content of the document. Streams keep their filters, except the content streams of
+    /// pages, which are decoded. Encrypted documents are decrypted.
+    pub fn load(buf: &[u8]) -> Result<Self, String> {
+        let pdf =
+            Pdf::new(Arc::new(buf.to_vec())).map_err(|e| format!("failed to read PDF: {e:?}"))?;
+        let mut importer = Importer::new(pdf.xref());
+
+        // Reserve the page objects before importing anything, so that references to pages,
+        // like the destinations of links, resolve to the new page objects.
+        let objects = pdf
+            .pages()
+            .iter()
+            .map(|page| importer.reserve_page(page.raw().obj_id()))
+            .collect::<Vec<_>>();
+
+        let mut pages = vec![];
+
+        for (number, (page, object)) in pdf.pages().iter().zip(objects).enumerate() {
+            let raw = page.raw();
+            let mut dict = vec![(b"Type".to_vec(), Value::Name(b"Page".to_vec()))];
+
+            for (key, value) in raw.entries() {
+                if !PAGE_KEYS.contains(&key.as_ref()) {
+                    dict.push((key.to_vec(), importer.convert_ref(value)));
+                }
+            }
+
+            // Inherited attributes are copied as they are, to keep their exact values.
+            for key in [b"MediaBox".as_slice(), b"CropBox", b"Rotate"] {
+                if let Some(value) = inherited(pdf.xref(), raw, key) {
+                    dict.push((key.to_vec(), importer.inline(value, 1)));
+                } else if key == b"MediaBox" {
+                    dict.push((key.to_vec(), rect(page.media_box())));
+                }
+            }
+
+            // The resource categories and annotations are copied into each page, so that
+            // removing them from one page doesn't affect the others.
+            if let Some(resources) = inherited(pdf.xref(), raw, b"Resources") {
+                dict.push((b"Resources".to_vec(), importer.inline(resources, 2)));
+            }
+
+            if let Some(annots) = raw.get_raw::<RawObject<'_>>(b"Annots") {
+                dict.push((b"Annots".to_vec(), importer.inline(annots, 1)));
+            }
+
+            importer.objects[object].value = Value::Dict(dict);
+            pages.push(Page {
+                number,
+                object,
+                content: Content::parse(page.page_stream().unwrap_or_default()),
+            });
+        }
+
+        if pages.is_empty() {
+            return Err("the PDF has no pages".to_string());
+        }
+
+        let xref = pdf.xref();
+        let catalog = match xref.get::<RawDict<'_>>(xref.root_id()) {
+            Some(root) => CATALOG_KEYS
+                .iter()
+                .filter_map(|key| {
+                    let value = root.get_raw::<RawObject<'_>>(key)?;
+                    Some((key.to_vec(), importer.convert_ref(value)))
+                })
+                .collect(),
+            None => vec![],
+        };
+
+        Ok(Self {
+            objects: importer.finish(),
+            pages,
+            catalog,
+        })
+    }
+
+    /// The dictionary of a page.
+    pub fn page_dict(&self, page: usize) -> &Dict {
+        match &self.objects[self.pages[page].object].value {
+            Value::Dict(dict) => dict,
+            _ => unreachable!("pages are dictionaries"),
+        }
+    }
+
+    /// The dictionary of a page, for modification.
+    pub fn page_dict_mut(&mut self, page: usize) -> &mut Dict {
+        match &mut self.objects[self.pages[page].object].value {
+            Value::Dict(dict) => dict,
+            _ => unreachable!("pages are dictionaries"),
+        }
+    }
+
+    /// Keep only the pages whose objects are given.
+    pub fn retain_pages(&mut self, objects: &[usize]) {
+        for page in &self.pages {
+            if !objects.contains(&page.object) {
+                // Other objects may still refer to the page, like the destination of a link.
+                self.objects[page.object] = Object::NULL;
+            }
+        }
+
+        self.pages.retain(|page| objects.contains(&page.object));
+    }
+
+    /// Write the document as a PDF, leaving out the objects that are no longer used.
+    pub fn save(&self) -> Vec<u8> {
+        // The catalog, the page tree and the content streams are created on the fly and
+        // the pages get their parent and contents.
+        let count = self.objects.len();
+        let (catalog, tree) = (count, count + 1);
+        let mut created = vec![];
+
+        let mut catalog_dict = vec![
+            (b"Type".to_vec(), Value::Name(b"Catalog".to_vec())),
+            (b"Pages".to_vec(), Value::Ref(tree)),
+        ];
+        catalog_dict.extend(self.catalog.iter().cloned());
+        created.push(Object {
+            value: Value::Dict(catalog_dict),
+            stream: None,
+        });
+
+        created.push(Object {
+            value: Value::Dict(vec![
+                (b"Type".to_vec(), Value::Name(b"Pages".to_vec())),
+                (
+                    b"Kids".to_vec(),
+                    Value::Array(self.pages.iter().map(|p| Value::Ref(p.object)).collect()),
+                ),
+                (b"Count".to_vec(), Value::Integer(self.pages.len() as i64)),
+            ]),
+            stream: None,
+        });
+
+        let mut pages = HashMap::new();
+
+        for (i, page) in self.pages.iter().enumerate() {
+            let contents = count + created.len();
+            created.push(Object {
+                value: Value::Dict(vec![]),
+                stream: Some(page.content.to_bytes()),
+            });
+
+            let mut dict = self.page_dict(i).clone();
+            dict.insert(1, (b"Parent".to_vec(), Value::Ref(tree)));
+            dict.push((b"Contents".to_vec(), Value::Ref(contents)));
+            pages.insert(
+                page.object,
+                Object {
+                    value: Value::Dict(dict),
+                    stream: None,
+                },
+            );
+        }
+
+        let object = |index: usize| match index.checked_sub(count) {
+            Some(i) => &created[i],
+            None => pages.get(&index).unwrap_or(&self.objects[index]),
+        };
+
+        // Number the objects in the order they are reached from the catalog.
+        let mut numbers = HashMap::from([(catalog, 1)]);
+        let mut order = vec![catalog];
+        let mut queue = VecDeque::from([catalog]);
+
+        while let Some(index) = queue.pop_front() {
+            let mut referenced = vec![];
+            collect_refs(&object(index).value, &mut referenced);
+
+            for index in referenced {
+                if let Entry::Vacant(entry) = numbers.entry(index) {
+                    entry.insert(order.len() + 1);
+                    order.push(index);
+                    queue.push_back(index);
+                }
+            }
+        }
+
+        let mut out = b"%PDF-1.7\n%\xE2\xE3\xCF\xD3\n".to_vec();
+        let mut offsets = vec![];
+
+        for (i, index) in order.iter().enumerate() {
+            offsets.push(out.len());
+            writeln!(out, "{} 0 obj", i + 1).unwrap();
+
+            match object(*index) {
+                Object {
+                    value: Value::Dict(dict),
+                    stream: Some(data),
+                } => {
+                    let mut dict = dict.clone();
+                    dict.push((b"Length".to_vec(), Value::Integer(data.len() as i64)));
+                    write_value(&mut out, &Value::Dict(dict), &numbers);
+                    out.extend(b"\nstream\n");
+                    out.extend(data);
+                    out.extend(b"\nendstream\n");
+                }
+                object => {
+                    write_value(&mut out, &object.value, &numbers);
+                    out.push(b'\n');
+                }
+            }
+
+            out.extend(b"endobj\n");
+        }
+
+        let xref = out.len();
+        let size = order.len() + 1;
+        write!(out, "xref\n0 {size}\n0000000000 65535 f \n").unwrap();
+
+        for offset in offsets {
+            writeln!(out, "{offset:010} 00000 n ").unwrap();
+        }
+
+        write!(
+            out,
+            "trailer\n<< /Size {size} /Root 1 0 R >>\nstartxref\n{xref}\n%%EOF\n"
+        )
+        .unwrap();
+
+        out
+    }
+}
+
+/// Imports the objects of a parsed PDF, giving them indices in the document.
+struct Importer<'a> {
+    xref: &'a XRef,
+    ids: HashMap<ObjectIdentifier, usize>,
+    objects: Vec<Object>,
+    /// The objects that were referenced, but not imported yet.
+    pending: Vec<(usize, ObjectIdentifier)>,
+}
+
+impl<'a> Importer<'a> {
+    fn new(xref: &'a XRef) -> Self {
+        Self {
+            xref,
+            ids: HashMap::new(),
+            objects: vec![],
+            pending: vec![],
+        }
+    }
+
+    /// Reserve an index for a page, which is filled in by the caller.
+    fn reserve_page(&mut self, id: Option<ObjectIdentifier>) -> usize {
+        let index = self.objects.len();
+        self.objects.push(Object::NULL);
+
+        if let Some(id) = id {
+            self.ids.entry(id).or_insert(index);
+        }
+
+        index
+    }
+
+    /// The index of an object, which is imported when the importer finishes.
+    fn reserve(&mut self, id: ObjectIdentifier) -> usize {
+        if let Some(index) = self.ids.get(&id) {
+            return *index;
+        }
+
+        let index = self.objects.len();
+        self.objects.push(Object::NULL);
+        self.ids.insert(id, index);
+        self.pending.push((index, id));
+        index
+    }
+
+    fn convert_ref(&mut self, value: MaybeRef<RawObject<'_>>) -> Value {
+        match value {
+            MaybeRef::Ref(r) => Value::Ref(self.reserve(r.into())),
+            MaybeRef::NotRef(object) => self.convert(&object),
+        }
+    }
+
+    fn convert(&mut self, object: &RawObject<'_>) -> Value {
+        convert(object, &mut |r: ObjRef| Value::Ref(self.reserve(r.into())))
+    }
+
+    /// Convert an object, resolving references down to the given depth, so that the
+    /// result can be modified without affecting other objects that refer to the same
+    /// objects.
+    fn inline(&mut self, value: MaybeRef<RawObject<'_>>, depth: usize) -> Value {
+        let object = match value {
+            MaybeRef::Ref(r) if depth > 0 => match self.xref.get::<RawObject<'_>>(r.into()) {
+                Some(RawObject::Stream(_)) => return Value::Ref(self.reserve(r.into())),
+                Some(object) => object,
+                None => return Value::Null,
+            },
+            MaybeRef::Ref(r) => return Value::Ref(self.reserve(r.into())),
+            MaybeRef::NotRef(object) => object,
+        };
+
+        match object {
+            RawObject::Dict(dict) if depth > 0 => Value::Dict(
+                dict.entries()
+                    .map(|(key, value)| (key.to_vec(), self.inline(value, depth - 1)))
+                    .collect(),
+            ),
+            RawObject::Array(array) if depth > 0 => Value::Array(
+                array
+                    .raw_iter()
+                    .map(|value| self.inline(value, depth - 1))
+                    .collect(),
+            ),
+            object => self.convert(&object),
+        }
+    }
+
+    /// Import all referenced objects.
+    fn finish(mut self) -> Vec<Object> {
+        while let Some((index, id)) = self.pending.pop() {
+            self.objects[index] = match self.xref.get::<RawObject<'_>>(id) {
+                Some(RawObject::Stream(stream)) => Object {
+                    value: Value::Dict(
+                        stream
+                            .dict()
+                            .entries()
+                            .filter(|(key, _)| key.as_ref() != b"Length")
+                            .map(|(key, value)| (key.to_vec(), self.convert_ref(value)))
+                            .collect(),
+                    ),
+                    stream: Some(stream.raw_data().to_vec()),
+                },
+                Some(object) => Object {
+                    value: self.convert(&object),
+                    stream: None,
+                },
+                None => Object::NULL,
+            };
+        }
+
+        self.objects
+    }
+}
+
+/// Convert a parsed object, mapping references with the given function.
+pub(crate) fn convert(object: &RawObject<'_>, refs: &mut dyn FnMut(ObjRef) -> Value) -> Value {
+    fn child(value: MaybeRef<RawObject<'_>>, refs: &mut dyn FnMut(ObjRef) -> Value) -> Value {
+        match value {
+            MaybeRef::Ref(r) => refs(r),
+            MaybeRef::NotRef(object) => convert(&object, refs),
+        }
+    }
+
+    match object {
+        RawObject::Null(_) => Value::Null,
+        RawObject::Boolean(b) => Value::Bool(*b),
+        RawObject::Number(n) => number(n.as_f64()),
+        RawObject::String(s) => Value::String(s.as_bytes().to_vec()),
+        RawObject::Name(n) => Value::Name(n.to_vec()),
+        RawObject::Dict(dict) => Value::Dict(
+            dict.entries()
+                .map(|(key, value)| (key.to_vec(), child(value, refs)))
+                .collect(),
+        ),
+        RawObject::Array(array) => {
+            Value::Array(array.raw_iter().map(|value| child(value, refs)).collect())
+        }
+        // Streams are always indirect objects and handled by the importer.
+        RawObject::Stream(_) => Value::Null,
+    }
+}
+
+/// The value of an entry of a page or one of its ancestors in the page tree.
+fn inherited<'a>(
+    xref: &'a XRef,
+    dict: &RawDict<'a>,
+    key: &[u8],
+) -> Option<MaybeRef<RawObject<'a>>> {
+    let mut dict = dict.clone();
+
+    // The depth is limited in case the page tree is cyclic.
+    for _ in 0..64 {
+        if let Some(value) = dict.get_raw::<RawObject<'_>>(key) {
+            return Some(value);
+        }
+
+        // Parents aren't resolved through the dictionary, so resolve them directly.
+        dict = xref.get::<RawDict<'_>>(dict.get_ref(b"Parent")?.into())?;
+    }
+
+    None
+}
+
+fn rect(rect: Rect) -> Value {
+    Value::Array([rect.x0, rect.y0, rect.x1, rect.y1].map(number).to_vec())
+}
+
+/// A number, as an integer if it is one.
+fn number(n: f64) -> Value {
+    if n.fract() == 0.0 && n.abs() < (1u64 << 53) as f64 {
+        Value::Integer(n as i64)
+    } else {
+        Value::Real(n)
+    }
+}
+
+fn collect_refs(value: &Value, refs: &mut Vec<usize>) {
+    match value {
+        Value::Ref(index) => refs.push(*index),
+        Value::Array(items) => items.iter().for_each(|item| collect_refs(item, refs)),
+        Value::Dict(dict) => dict.iter().for_each(|(_, value)| collect_refs(value, refs)),
+        _ => {}
+    }
+}
+
+/// Write an object in PDF syntax, with the given object numbers for references.
+pub(crate) fn write_value(out: &mut Vec<u8>, value: &Value, numbers: &HashMap<usize, usize>) {
+    match value {
+        Value::Null => out.extend(b"null"),
+        Value::Bool(b) => write!(out, "{b}").unwrap(),
+        Value::Integer(n) => write!(out, "{n}").unwrap(),
+        Value::Real(n) if n.is_finite() => write!(out, "{n}").unwrap(),
+        Value::Real(_) => out.push(b'0'),
+        Value::Name(name) => write_name(out, name),
+        Value::String(string) => write_string(out, string),
+        Value::Array(items) => {
+            out.push(b'[');
+
+            for (i, item) in items.iter().enumerate() {
+                if i > 0 {
+                    out.push(b' ');
+                }
+
+                write_value(out, item, numbers);
+            }
+
+            out.push(b']');
+        }
+        Value::Dict(dict) => {
+            out.extend(b"<<");
+
+            for (key, value) in dict {
+                out.push(b' ');
+                write_name(out, key);
+                out.push(b' ');
+                write_value(out, value, numbers);
+            }
+
+            out.extend(b" >>");
+        }
+        Value::Ref(index) => match numbers.get(index) {
+            Some(number) => write!(out, "{number} 0 R").unwrap(),
+            None => out.extend(b"null"),
+        },
+    }
+}
+
+fn write_name(out: &mut Vec<u8>, name: &[u8]) {
+    out.push(b'/');
+
+    for &byte in name {
+        if byte.is_ascii_graphic() && !b"#%()/<>[]{}".contains(&byte) {
+            out.push(byte);
+        } else {
+            write!(out, "#{byte:02X}").unwrap();
+        }
+    }
+}
+
+fn write_string(out: &mut Vec<u8>, string: &[u8]) {
+    if string.iter().all(|b| b.is_ascii_graphic() || *b == b' ') {
+        out.push(b'(');
+
+        for &byte in string {
+            if matches!(byte, b'(' | b')' | b'\\') {
+                out.push(b'\\');
+            }
+
+            out.push(byte);
+        }
+
+        out.push(b')');
+    } else {
+        out.push(b'<');
+
+        for byte in string {
+            write!(out, "{byte:02X}").unwrap();
+        }
+
+        out.push(b'>');
+    }
+}
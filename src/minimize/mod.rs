@@ -0,0 +1,450 @@
+use crate::diff::{diff_pixmaps, DiffOptions};
+use crate::normalize::{normalize, SizePolicy};
+use crate::renderer::{Backend, RenderOptions, Renderer};
+use document::{Document, Value};
+use std::collections::HashSet;
+use tiny_skia::Pixmap;
+
+mod content;
+mod document;
+
+/// The options that should be applied when minimizing a PDF.
+#[derive(Clone)]
+pub struct MinimizeOptions {
+    /// The options used for rendering the PDF.
+    pub render: RenderOptions,
+    /// The options used for comparing the pages of both backends.
+    pub diff: DiffOptions,
+    /// The fraction of pixels above which the backends diverge on a page.
+    pub threshold: f32,
+    /// By how many pixels the width and height of a page may differ between the backends
+    /// before the page counts as completely different.
+    pub size_tolerance: u32,
+}
+
+impl Default for MinimizeOptions {
+    fn default() -> Self {
+        Self {
+            render: RenderOptions::default(),
+            diff: DiffOptions::default(),
+            threshold: 0.01,
+            size_tolerance: 1,
+        }
+    }
+}
+
+/// A change that was kept while minimizing a PDF, because the backends still diverge
+/// without the removed parts.
+#[derive(Clone, Debug)]
+pub struct Reduction {
+    /// What was removed, like `removed 12 operators from page 3`.
+    pub description: String,
+    /// The size of the PDF after the change, in bytes.
+    pub size: usize,
+    /// The divergence of the backends after the change.
+    pub divergence: f32,
+}
+
+/// A minimized PDF.
+pub struct Minimized {
+    /// The minimized PDF.
+    pub pdf: Vec<u8>,
+    /// The divergence of the original PDF, which is the largest fraction of differing
+    /// pixels on any page when minimizing with a renderer.
+    pub original_divergence: f32,
+    /// The divergence of the minimized PDF.
+    pub divergence: f32,
+    /// The changes that were kept, in the order they were made.
+    pub reductions: Vec<Reduction>,
+    /// How often it was checked whether a PDF still diverges.
+    pub attempts: usize,
+}
+
+impl Renderer {
+    /// Shrink a PDF on which two backends diverge to a minimal PDF on which they still
+    /// diverge, for example for a bug report.
+    ///
+    /// The backends diverge if the fraction of differing pixels exceeds the threshold on
+    /// at least one page, or if they render different numbers of pages. After each step
+    /// of [`minimize_pdf`], the PDF is rendered with both backends again and the change
+    /// is kept if they still diverge and both render it successfully.
+    ///
+    /// `progress` is called with each change that is kept. Fails if the PDF can't be
+    /// parsed, either backend fails to render it or the backends don't diverge on it.
+    pub fn minimize(
+        &self,
+        buf: &[u8],
+        backends: [Backend; 2],
+        options: &MinimizeOptions,
+        progress: impl FnMut(&Reduction),
+    ) -> Result<Minimized, String> {
+        let divergence = self.divergence(buf, &backends, options)?;
+
+        if divergence <= options.threshold {
+            return Err(format!(
+                "{} and {} differ by at most {:.2}% of pixels, which doesn't exceed the threshold of {:.2}%",
+                backends[0].name(),
+                backends[1].name(),
+                divergence * 100.0,
+                options.threshold * 100.0
+            ));
+        }
+
+        let check = |pdf: &[u8]| {
+            let divergence = self.divergence(pdf, &backends, options).ok()?;
+            (divergence > options.threshold).then_some(divergence)
+        };
+
+        minimize_pdf(buf, check, progress).map_err(|e| format!("failed to minimize the PDF: {e}"))
+    }
+
+    /// The largest fraction of differing pixels of two backends on any page of a PDF.
+    fn divergence(
+        &self,
+        buf: &[u8],
+        backends: &[Backend; 2],
+        options: &MinimizeOptions,
+    ) -> Result<f32, String> {
+        let documents = self.render_all_as_pixmaps(backends, buf, &options.render)?;
+        Ok(divergence(&documents[0].1, &documents[1].1, options))
+    }
+}
+
+/// Shrink a PDF as long as `check` still returns a divergence for it.
+///
+/// Pages, annotations, content stream operators and the resources of pages are removed,
+/// large chunks first, until nothing more can be removed. Operators that start and end a
+/// block, like `q` and `Q`, are only removed together. Before that, the PDF is rewritten
+/// with a flat page tree and without the parts of the document that don't affect
+/// rendering, like outlines, metadata and encryption.
+///
+/// `progress` is called with each change that is kept. Fails if the PDF can't be parsed
+/// or `check` returns `None` for it, or for the rewritten PDF.
+pub fn minimize_pdf(
+    buf: &[u8],
+    mut check: impl FnMut(&[u8]) -> Option<f32>,
+    progress: impl FnMut(&Reduction),
+) -> Result<Minimized, String> {
+    let original_divergence =
+        check(buf).ok_or_else(|| "the PDF doesn't reproduce the divergence".to_string())?;
+    let document = Document::load(buf)?;
+    let divergence = check(&document.save()).ok_or_else(|| {
+        "the rewritten PDF no longer reproduces the divergence, so it can't be minimized"
+            .to_string()
+    })?;
+
+    let mut minimizer = Minimizer {
+        check,
+        progress,
+        document,
+        divergence,
+        reductions: vec![],
+        attempts: 2,
+    };
+
+    loop {
+        let count = minimizer.reductions.len();
+
+        minimizer.remove_pages();
+        minimizer.remove_annotations();
+        minimizer.remove_operators();
+        minimizer.remove_resources();
+
+        // Removing something can make other parts removable, like the resources of
+        // removed operators.
+        if minimizer.reductions.len() == count {
+            break;
+        }
+    }
+
+    Ok(Minimized {
+        pdf: minimizer.document.save(),
+        original_divergence,
+        divergence: minimizer.divergence,
+        reductions: minimizer.reductions,
+        attempts: minimizer.attempts,
+    })
+}
+
+fn divergence(a: &[Pixmap], b: &[Pixmap], options: &MinimizeOptions) -> f32 {
+    if a.len() != b.len() {
+        return 1.0;
+    }
+
+    a.iter()
+        .zip(b)
+        .map(|(a, b)| {
+            let normalized = normalize(&[a, b], SizePolicy::Crop);
+
+            if normalized.discrepancy.max_difference() > options.size_tolerance {
+                return 1.0;
+            }
+
+            let (a, b) = (&normalized.pixmaps[0], &normalized.pixmaps[1]);
+            diff_pixmaps(a, b, &options.diff).ratio()
+        })
+        .fold(0.0, f32::max)
+}
+
+struct Minimizer<C, F> {
+    check: C,
+    progress: F,
+    /// The smallest document that still diverges so far.
+    document: Document,
+    divergence: f32,
+    reductions: Vec<Reduction>,
+    attempts: usize,
+}
+
+impl<C: FnMut(&[u8]) -> Option<f32>, F: FnMut(&Reduction)> Minimizer<C, F> {
+    /// Check whether a document still diverges.
+    fn test(&mut self, document: &Document) -> Option<f32> {
+        self.attempts += 1;
+        (self.check)(&document.save())
+    }
+
+    /// Remove as many of the items as possible while the backends still diverge, trying
+    /// to remove large chunks first, and return the remaining items.
+    ///
+    /// `build` creates the document with only the given items, if it is valid, and
+    /// `describe` describes the removal of the given items.
+    fn reduce<T: Clone>(
+        &mut self,
+        mut items: Vec<T>,
+        build: impl Fn(&Document, &[T]) -> Option<Document>,
+        describe: impl Fn(&[T]) -> String,
+    ) -> Vec<T> {
+        let mut chunk = items.len();
+
+        while chunk > 0 {
+            let mut start = 0;
+
+            while start < items.len() {
+                let end = (start + chunk).min(items.len());
+                let remaining = [&items[..start], &items[end..]].concat();
+                let candidate = build(&self.document, &remaining);
+
+                match candidate.and_then(|c| Some((self.test(&c)?, c))) {
+                    Some((divergence, candidate)) => {
+                        let description = describe(&items[start..end]);
+                        self.keep(candidate, divergence, description);
+                        items = remaining;
+                    }
+                    None => start = end,
+                }
+            }
+
+            chunk = if chunk == 1 { 0 } else { chunk.div_ceil(2) };
+        }
+
+        items
+    }
+
+    fn keep(&mut self, document: Document, divergence: f32, description: String) {
+        let reduction = Reduction {
+            description,
+            size: document.save().len(),
+            divergence,
+        };
+
+        (self.progress)(&reduction);
+        self.reductions.push(reduction);
+        self.document = document;
+        self.divergence = divergence;
+    }
+
+    fn remove_pages(&mut self) {
+        let pages = self
+            .document
+            .pages
+            .iter()
+            .map(|page| (page.object, page.number))
+            .collect();
+
+        self.reduce(
+            pages,
+            |document, remaining| {
+                // A document needs at least one page.
+                if remaining.is_empty() {
+                    return None;
+                }
+
+                let mut document = document.clone();
+                let objects = remaining
+                    .iter()
+                    .map(|(object, _)| *object)
+                    .collect::<Vec<_>>();
+                document.retain_pages(&objects);
+                Some(document)
+            },
+            |removed| match removed {
+                [(_, number)] => format!("removed page {}", number + 1),
+                _ => format!("removed {} pages", removed.len()),
+            },
+        );
+    }
+
+    fn remove_annotations(&mut self) {
+        for page in 0..self.document.pages.len() {
+            let number = self.document.pages[page].number;
+            let Some(Value::Array(annots)) = get(self.document.page_dict(page), b"Annots") else {
+                continue;
+            };
+
+            let annots = annots.clone();
+
+            self.reduce(
+                (0..annots.len()).collect(),
+                |document, remaining| {
+                    let mut document = document.clone();
+                    let annots = remaining
+                        .iter()
+                        .map(|i| annots[*i].clone())
+                        .collect::<Vec<_>>();
+                    let dict = document.page_dict_mut(page);
+
+                    if annots.is_empty() {
+                        dict.retain(|(key, _)| key != b"Annots");
+                    } else {
+                        set(dict, b"Annots", Value::Array(annots));
+                    }
+
+                    Some(document)
+                },
+                |removed| match removed.len() {
+                    1 => format!("removed an annotation from page {}", number + 1),
+                    n => format!("removed {n} annotations from page {}", number + 1),
+                },
+            );
+        }
+    }
+
+    fn remove_operators(&mut self) {
+        for page in 0..self.document.pages.len() {
+            let number = self.document.pages[page].number;
+            let content = self.document.pages[page].content.clone();
+            let mut kept = vec![true; content.operations.len()];
+            let mut ranges = vec![];
+            ranges.push(0..content.operations.len());
+
+            // Remove whole blocks before the operators inside of them.
+            while let Some(range) = ranges.pop() {
+                let units = content.units(range);
+                let base = kept.clone();
+
+                let remaining = self.reduce(
+                    units.clone(),
+                    |document, remaining| {
+                        let remaining = remaining.iter().map(|u| u.start).collect::<HashSet<_>>();
+                        let mut kept = base.clone();
+
+                        for unit in units.iter().filter(|u| !remaining.contains(&u.start)) {
+                            kept[unit.clone()].fill(false);
+                        }
+
+                        let mut document = document.clone();
+                        document.pages[page].content = content.filter(&kept);
+                        Some(document)
+                    },
+                    |removed| match removed.iter().map(|u| u.len()).sum::<usize>() {
+                        1 => format!("removed an operator from page {}", number + 1),
+                        n => format!("removed {n} operators from page {}", number + 1),
+                    },
+                );
+
+                let remaining_starts = remaining.iter().map(|u| u.start).collect::<HashSet<_>>();
+
+                for unit in &units {
+                    if !remaining_starts.contains(&unit.start) {
+                        kept[unit.clone()].fill(false);
+                    }
+                }
+
+                ranges.extend(
+                    remaining
+                        .into_iter()
+                        .filter(|unit| unit.len() > 2)
+                        .map(|unit| unit.start + 1..unit.end - 1),
+                );
+            }
+        }
+    }
+
+    fn remove_resources(&mut self) {
+        for page in 0..self.document.pages.len() {
+            let number = self.document.pages[page].number;
+            let Some(Value::Dict(resources)) = get(self.document.page_dict(page), b"Resources")
+            else {
+                continue;
+            };
+
+            let resources = resources
+                .iter()
+                .filter_map(|(category, entries)| match entries {
+                    Value::Dict(entries) => Some((category, entries)),
+                    _ => None,
+                })
+                .flat_map(|(category, entries)| {
+                    entries
+                        .iter()
+                        .map(|(name, _)| (category.clone(), name.clone()))
+                })
+                .collect::<Vec<_>>();
+
+            self.reduce(
+                resources,
+                |document, remaining| {
+                    let mut document = document.clone();
+
+                    if let Some(Value::Dict(resources)) =
+                        get_mut(document.page_dict_mut(page), b"Resources")
+                    {
+                        for (category, entries) in resources.iter_mut() {
+                            if let Value::Dict(entries) = entries {
+                                entries.retain(|(name, _)| {
+                                    remaining.iter().any(|(c, n)| c == category && n == name)
+                                });
+                            }
+                        }
+
+                        resources.retain(
+                            |(_, entries)| !matches!(entries, Value::Dict(e) if e.is_empty()),
+                        );
+                    }
+
+                    Some(document)
+                },
+                |removed| match removed {
+                    [(category, name)] => format!(
+                        "removed /{} from the /{} resources of page {}",
+                        String::from_utf8_lossy(name),
+                        String::from_utf8_lossy(category),
+                        number + 1
+                    ),
+                    _ => format!(
+                        "removed {} resources from page {}",
+                        removed.len(),
+                        number + 1
+                    ),
+                },
+            );
+        }
+    }
+}
+
+fn get<'a>(dict: &'a [(Vec<u8>, Value)], key: &[u8]) -> Option<&'a Value> {
+    dict.iter().find(|(k, _)| k == key).map(|(_, value)| value)
+}
+
+fn get_mut<'a>(dict: &'a mut [(Vec<u8>, Value)], key: &[u8]) -> Option<&'a mut Value> {
+    dict.iter_mut()
+        .find(|(k, _)| k == key)
+        .map(|(_, value)| value)
+}
+
+fn set(dict: &mut Vec<(Vec<u8>, Value)>, key: &[u8], value: Value) {
+    match get_mut(dict, key) {
+        Some(existing) => *existing = value,
+        None => dict.push((key.to_vec(), value)),
+    }
+}
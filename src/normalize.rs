@@ -0,0 +1,151 @@
+use tiny_skia::{Color, FilterQuality, IntRect, Pixmap, PixmapPaint, Transform};
+
+/// How pages of different sizes should be brought to a common size before comparing them.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum SizePolicy {
+    /// Pad all pages with white at the right and bottom to the size of the largest page.
+    Pad,
+    /// Crop all pages at the right and bottom to the size of the smallest page.
+    Crop,
+    /// Scale all pages to the most common page size.
+    Resample,
+}
+
+/// How much the sizes of the normalized pages differed.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct SizeDiscrepancy {
+    /// The smallest width of all pages.
+    pub min_width: u32,
+    /// The largest width of all pages.
+    pub max_width: u32,
+    /// The smallest height of all pages.
+    pub min_height: u32,
+    /// The largest height of all pages.
+    pub max_height: u32,
+}
+
+impl SizeDiscrepancy {
+    /// Whether all pages had the same size.
+    pub fn is_empty(&self) -> bool {
+        self.min_width == self.max_width && self.min_height == self.max_height
+    }
+
+    /// The largest difference in width or height, in pixels.
+    pub fn max_difference(&self) -> u32 {
+        (self.max_width - self.min_width).max(self.max_height - self.min_height)
+    }
+}
+
+/// Pages that have been brought to a common size.
+pub struct Normalized {
+    /// The normalized pages, in the order they were given in.
+    pub pixmaps: Vec<Pixmap>,
+    /// The common width of all pages.
+    pub width: u32,
+    /// The common height of all pages.
+    pub height: u32,
+    /// How much the original sizes of the pages differed.
+    pub discrepancy: SizeDiscrepancy,
+}
+
+/// Bring pages to a common size according to the given policy.
+pub fn normalize(pixmaps: &[&Pixmap], policy: SizePolicy) -> Normalized {
+    let widths = pixmaps.iter().map(|p| p.width());
+    let heights = pixmaps.iter().map(|p| p.height());
+
+    let discrepancy = SizeDiscrepancy {
+        min_width: widths.clone().min().unwrap_or(1),
+        max_width: widths.max().unwrap_or(1),
+        min_height: heights.clone().min().unwrap_or(1),
+        max_height: heights.max().unwrap_or(1),
+    };
+
+    let (width, height) = match policy {
+        SizePolicy::Pad => (discrepancy.max_width, discrepancy.max_height),
+        SizePolicy::Crop => (discrepancy.min_width, discrepancy.min_height),
+        SizePolicy::Resample => most_common_size(pixmaps).unwrap_or((1, 1)),
+    };
+
+    let pixmaps = pixmaps
+        .iter()
+        .map(|pixmap| {
+            if (pixmap.width(), pixmap.height()) == (width, height) {
+                return (*pixmap).clone();
+            }
+
+            match policy {
+                SizePolicy::Pad => pad(pixmap, width, height),
+                SizePolicy::Crop => crop(pixmap, width, height),
+                SizePolicy::Resample => resample(pixmap, width, height),
+            }
+        })
+        .collect();
+
+    Normalized {
+        pixmaps,
+        width,
+        height,
+        discrepancy,
+    }
+}
+
+/// Find the most common size, preferring pages that come first in case of a tie.
+fn most_common_size(pixmaps: &[&Pixmap]) -> Option<(u32, u32)> {
+    let sizes = pixmaps
+        .iter()
+        .map(|p| (p.width(), p.height()))
+        .collect::<Vec<_>>();
+
+    sizes
+        .iter()
+        .enumerate()
+        .max_by_key(|(i, size)| {
+            let count = sizes.iter().filter(|s| s == size).count();
+            (count, usize::MAX - i)
+        })
+        .map(|(_, size)| *size)
+}
+
+fn pad(pixmap: &Pixmap, width: u32, height: u32) -> Pixmap {
+    let mut padded = Pixmap::new(width, height).unwrap();
+    padded.fill(Color::WHITE);
+    padded.draw_pixmap(
+        0,
+        0,
+        pixmap.as_ref(),
+        &PixmapPaint::default(),
+        Transform::identity(),
+        None,
+    );
+
+    padded
+}
+
+fn crop(pixmap: &Pixmap, width: u32, height: u32) -> Pixmap {
+    let rect = IntRect::from_xywh(0, 0, width, height).unwrap();
+    pixmap.clone_rect(rect).unwrap()
+}
+
+fn resample(pixmap: &Pixmap, width: u32, height: u32) -> Pixmap {
+    let mut resampled = Pixmap::new(width, height).unwrap();
+    resampled.fill(Color::WHITE);
+
+    let paint = PixmapPaint {
+        quality: FilterQuality::Bicubic,
+        ..PixmapPaint::default()
+    };
+
+    resampled.draw_pixmap(
+        0,
+        0,
+        pixmap.as_ref(),
+        &paint,
+        Transform::from_scale(
+            width as f32 / pixmap.width() as f32,
+            height as f32 / pixmap.height() as f32,
+        ),
+        None,
+    );
+
+    resampled
+}
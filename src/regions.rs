@@ -0,0 +1,174 @@
+use crate::diff::Diff;
+use crate::renderer::RenderOptions;
+use std::collections::VecDeque;
+use tiny_skia::{IntRect, Paint, PathBuilder, Pixmap, Rect, Stroke, Transform};
+
+/// A rectangular area in which two rendered pages differ.
+#[derive(Copy, Clone, Debug)]
+pub struct Region {
+    /// The bounding box in pixels, relative to the top-left corner of the rendered page.
+    pub pixels: IntRect,
+    /// The bounding box in PDF user space units, relative to the bottom-left corner of
+    /// the page, with the y axis pointing up.
+    ///
+    /// Since the y axis is flipped, the `top` of the rectangle is the lower edge of
+    /// the region on the page.
+    pub user_space: Rect,
+    /// The number of differing pixels in the region.
+    pub differing_pixels: usize,
+}
+
+impl Diff {
+    /// Find the regions in which the pages differ.
+    ///
+    /// Connected differing pixels are grouped together, and the bounding boxes of groups
+    /// that are at most `merge_distance` pixels apart are merged. The scale of the render
+    /// options is used to convert the bounding boxes into PDF user space.
+    pub fn regions(&self, merge_distance: u32, options: &RenderOptions) -> Vec<Region> {
+        let mut components = self.components();
+
+        // Merge until no two boxes are close enough anymore.
+        let mut merged = true;
+        while merged {
+            merged = false;
+
+            for i in 0..components.len() {
+                let mut j = i + 1;
+
+                while j < components.len() {
+                    if are_close(&components[i].0, &components[j].0, merge_distance) {
+                        let (rect, count) = components.swap_remove(j);
+                        components[i].0 = union(&components[i].0, &rect);
+                        components[i].1 += count;
+                        merged = true;
+                    } else {
+                        j += 1;
+                    }
+                }
+            }
+        }
+
+        components.sort_by_key(|(rect, _)| (rect.y(), rect.x()));
+
+        components
+            .into_iter()
+            .map(|(pixels, differing_pixels)| Region {
+                pixels,
+                user_space: to_user_space(&pixels, self.height(), options.scale),
+                differing_pixels,
+            })
+            .collect()
+    }
+
+    /// Find the bounding boxes and pixel counts of all 8-connected groups of differing pixels.
+    fn components(&self) -> Vec<(IntRect, usize)> {
+        let (width, height) = (self.width(), self.height());
+        let mut visited = vec![false; width as usize * height as usize];
+        let mut components = vec![];
+        let mut queue = VecDeque::new();
+
+        for y in 0..height {
+            for x in 0..width {
+                if visited[(y * width + x) as usize] || !self.is_different(x, y) {
+                    continue;
+                }
+
+                let (mut min_x, mut min_y, mut max_x, mut max_y) = (x, y, x, y);
+                let mut count = 0;
+                visited[(y * width + x) as usize] = true;
+                queue.push_back((x, y));
+
+                while let Some((cx, cy)) = queue.pop_front() {
+                    count += 1;
+                    min_x = min_x.min(cx);
+                    min_y = min_y.min(cy);
+                    max_x = max_x.max(cx);
+                    max_y = max_y.max(cy);
+
+                    for ny in cy.saturating_sub(1)..=(cy + 1).min(height - 1) {
+                        for nx in cx.saturating_sub(1)..=(cx + 1).min(width - 1) {
+                            let idx = (ny * width + nx) as usize;
+
+                            if !visited[idx] && self.is_different(nx, ny) {
+                                visited[idx] = true;
+                                queue.push_back((nx, ny));
+                            }
+                        }
+                    }
+                }
+
+                let rect = IntRect::from_ltrb(
+                    min_x as i32,
+                    min_y as i32,
+                    max_x as i32 + 1,
+                    max_y as i32 + 1,
+                )
+                .unwrap();
+                components.push((rect, count));
+            }
+        }
+
+        components
+    }
+}
+
+/// Draw the outlines of regions onto a pixmap.
+///
+/// The transform maps from the pixel coordinates of the regions to the coordinates
+/// of the pixmap, for example to account for the position of a page within a composite.
+pub fn draw_regions(pixmap: &mut Pixmap, regions: &[Region], transform: Transform) {
+    let mut paint = Paint::default();
+    paint.set_color_rgba8(255, 0, 0, 255);
+
+    let stroke = Stroke {
+        width: 2.0,
+        ..Stroke::default()
+    };
+
+    for region in regions {
+        let rect = region.pixels.to_rect();
+        // Draw the outline around the region instead of on top of it.
+        let Some(rect) = Rect::from_ltrb(
+            rect.left() - 1.0,
+            rect.top() - 1.0,
+            rect.right() + 1.0,
+            rect.bottom() + 1.0,
+        ) else {
+            continue;
+        };
+
+        let path = PathBuilder::from_rect(rect);
+        pixmap.stroke_path(&path, &paint, &stroke, transform, None);
+    }
+}
+
+fn are_close(a: &IntRect, b: &IntRect, distance: u32) -> bool {
+    let distance = distance as i32;
+
+    a.left() - distance <= b.right()
+        && b.left() - distance <= a.right()
+        && a.top() - distance <= b.bottom()
+        && b.top() - distance <= a.bottom()
+}
+
+fn union(a: &IntRect, b: &IntRect) -> IntRect {
+    IntRect::from_ltrb(
+        a.left().min(b.left()),
+        a.top().min(b.top()),
+        a.right().max(b.right()),
+        a.bottom().max(b.bottom()),
+    )
+    .unwrap()
+}
+
+fn to_user_space(rect: &IntRect, page_height: u32, scale: f32) -> Rect {
+    let page_height = page_height as f32;
+
+    Rect::from_ltrb(
+        rect.left() as f32 / scale,
+        (page_height - rect.bottom() as f32) / scale,
+        rect.right() as f32 / scale,
+        (page_height - rect.top() as f32) / scale,
+    )
+    .unwrap()
+}
@@ -0,0 +1,368 @@
+use super::{Backend, RenderOptions, RenderedDocument};
+use sha2::{Digest, Sha256};
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::SystemTime;
+use std::{env, io};
+
+/// The version of the layout of cache entries, part of every key.
+const CACHE_VERSION: &str = "sitro-render-cache-1";
+/// The file that describes a cache entry. Its modification time is the last time the
+/// entry was used.
+const ENTRY_FILE: &str = "entry.txt";
+
+/// The options of a [`DiskCache`].
+#[derive(Copy, Clone, Debug)]
+pub struct CacheOptions {
+    /// The maximum size of the cache in bytes, or `None` for no limit.
+    ///
+    /// When the cache grows larger, the least recently used entries are evicted.
+    pub max_size: Option<u64>,
+}
+
+impl Default for CacheOptions {
+    fn default() -> Self {
+        Self {
+            max_size: Some(4 * 1024 * 1024 * 1024),
+        }
+    }
+}
+
+impl CacheOptions {
+    /// The default options, with the maximum size taken from `SITRO_CACHE_MAX_SIZE` in
+    /// megabytes if it is set.
+    pub fn from_env() -> Result<Self, String> {
+        let mut options = Self::default();
+
+        if let Ok(max_size) = env::var("SITRO_CACHE_MAX_SIZE") {
+            let megabytes = max_size.trim().parse::<u64>().map_err(|_| {
+                format!("invalid SITRO_CACHE_MAX_SIZE `{max_size}`, expected a number of megabytes")
+            })?;
+            options.max_size = Some(megabytes * 1024 * 1024);
+        }
+
+        Ok(options)
+    }
+}
+
+/// Identifies a render in a [`DiskCache`].
+///
+/// The key is a hash of the PDF, the backend, the engine the backend runs in and the
+/// render options, so a render is only reused if all of them are the same.
+#[derive(Clone, Debug, Eq, PartialEq, Hash)]
+pub struct CacheKey(String);
+
+impl CacheKey {
+    /// Create the key of a render.
+    ///
+    /// `engine` identifies the exact version of the code that renders with the
+    /// backend, like the digest of the Docker image or the version of hayro.
+    pub fn new(engine: &str, backend: &Backend, buf: &[u8], options: &RenderOptions) -> Self {
+        let mut hasher = Sha256::new();
+
+        for part in [CACHE_VERSION, engine, &backend.base().name()] {
+            hasher.update(part.as_bytes());
+            hasher.update([0]);
+        }
+
+        hasher.update(options.scale.to_bits().to_le_bytes());
+        hasher.update(Sha256::digest(buf));
+
+        Self(
+            hasher
+                .finalize()
+                .iter()
+                .map(|byte| format!("{byte:02x}"))
+                .collect(),
+        )
+    }
+
+    /// The key as a hexadecimal string.
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+/// A content-addressed cache of rendered documents on disk.
+///
+/// Each entry is a directory with the rendered pages as PNG images and the warnings of
+/// the backend. Only successful renders are cached. The cache can be shared between
+/// processes, as entries are written to a temporary directory and then moved into place.
+pub struct DiskCache {
+    dir: PathBuf,
+    options: CacheOptions,
+    size: Mutex<u64>,
+}
+
+impl DiskCache {
+    /// Open the cache in the given directory, creating the directory if necessary.
+    pub fn open(dir: impl Into<PathBuf>, options: CacheOptions) -> Result<Self, String> {
+        let dir = dir.into();
+        fs::create_dir_all(&dir)
+            .map_err(|e| format!("failed to create cache directory {}: {e}", dir.display()))?;
+
+        let size = entries(&dir).iter().map(|entry| entry.size).sum();
+
+        Ok(Self {
+            dir,
+            options,
+            size: Mutex::new(size),
+        })
+    }
+
+    /// The default directory of the cache.
+    ///
+    /// This is `$SITRO_CACHE_DIR` if it is set, otherwise `sitro` in the platform's cache
+    /// directory (`$XDG_CACHE_HOME`, `~/.cache` or `~/Library/Caches`), or `None` if
+    /// neither can be determined.
+    pub fn default_dir() -> Option<PathBuf> {
+        if let Some(dir) = env::var_os("SITRO_CACHE_DIR").filter(|dir| !dir.is_empty()) {
+            return Some(PathBuf::from(dir));
+        }
+
+        if let Some(dir) = env::var_os("XDG_CACHE_HOME").filter(|dir| !dir.is_empty()) {
+            return Some(PathBuf::from(dir).join("sitro"));
+        }
+
+        let home = PathBuf::from(env::var_os("HOME").filter(|home| !home.is_empty())?);
+
+        Some(if cfg!(target_os = "macos") {
+            home.join("Library/Caches/sitro")
+        } else {
+            home.join(".cache/sitro")
+        })
+    }
+
+    /// The directory of the cache.
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// The total size of the cached entries in bytes.
+    pub fn size(&self) -> u64 {
+        *self.size.lock().unwrap()
+    }
+
+    /// The number of cached entries.
+    pub fn len(&self) -> usize {
+        entries(&self.dir).len()
+    }
+
+    /// Whether the cache has no entries.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Get a cached render and its warnings.
+    ///
+    /// Entries that can't be read are treated as missing.
+    pub fn get(&self, key: &CacheKey) -> Option<(RenderedDocument, Vec<String>)> {
+        let dir = self.entry_dir(key);
+        let entry = fs::read_to_string(dir.join(ENTRY_FILE)).ok()?;
+        let mut lines = entry.lines();
+        let page_count = lines.next()?.parse::<usize>().ok()?;
+        let warnings = lines.map(str::to_string).collect();
+
+        let document = (0..page_count)
+            .map(|page| fs::read(dir.join(format!("{page}.png"))).ok())
+            .collect::<Option<_>>()?;
+
+        // Mark the entry as recently used, so that it is evicted last.
+        let _ = File::options()
+            .append(true)
+            .open(dir.join(ENTRY_FILE))
+            .and_then(|file| file.set_modified(SystemTime::now()));
+
+        Some((document, warnings))
+    }
+
+    /// Store a render and its warnings, evicting old entries if the cache grows too large.
+    pub fn insert(
+        &self,
+        key: &CacheKey,
+        document: &RenderedDocument,
+        warnings: &[String],
+    ) -> Result<(), String> {
+        let dir = self.entry_dir(key);
+
+        if dir.exists() {
+            return Ok(());
+        }
+
+        let tmp = self.dir.join(format!("tmp-{}", uuid::Uuid::new_v4()));
+        let write = || -> io::Result<()> {
+            fs::create_dir_all(&tmp)?;
+
+            for (page, png) in document.iter().enumerate() {
+                fs::write(tmp.join(format!("{page}.png")), png)?;
+            }
+
+            let mut entry = format!("{}\n", document.len());
+
+            for warning in warnings {
+                entry.push_str(&warning.replace('\n', " "));
+                entry.push('\n');
+            }
+
+            fs::write(tmp.join(ENTRY_FILE), entry)?;
+            fs::create_dir_all(dir.parent().unwrap())?;
+            fs::rename(&tmp, &dir)
+        };
+
+        if let Err(e) = write() {
+            let _ = fs::remove_dir_all(&tmp);
+
+            // Another process may have stored the same render in the meantime.
+            return if dir.exists() {
+                Ok(())
+            } else {
+                Err(format!(
+                    "failed to write cache entry {}: {e}",
+                    dir.display()
+                ))
+            };
+        }
+
+        let mut size = self.size.lock().unwrap();
+        *size += dir_size(&dir);
+
+        if let Some(max_size) = self.options.max_size {
+            if *size > max_size {
+                *size = self.evict(max_size);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Remove all entries from the cache.
+    ///
+    /// Only entries and the temporary directories of entries that are being written are
+    /// removed. If the directory contains anything else, it is probably not a cache
+    /// directory, so nothing is removed and an error is returned.
+    pub fn clear(&self) -> Result<(), String> {
+        let mut size = self.size.lock().unwrap();
+
+        for path in owned_dirs(&self.dir)? {
+            match fs::remove_dir_all(&path) {
+                // The temporary directory of an entry may have been moved in the meantime.
+                Err(e) if e.kind() != io::ErrorKind::NotFound => {
+                    return Err(format!("failed to remove {}: {e}", path.display()));
+                }
+                _ => {}
+            }
+        }
+
+        *size = 0;
+        Ok(())
+    }
+
+    /// Remove the least recently used entries until the cache is at most `max_size`
+    /// bytes large and return the remaining size.
+    fn evict(&self, max_size: u64) -> u64 {
+        let mut entries = entries(&self.dir);
+        entries.sort_by_key(|entry| entry.used);
+
+        let mut size = entries.iter().map(|entry| entry.size).sum::<u64>();
+
+        for entry in entries {
+            if size <= max_size {
+                break;
+            }
+
+            if fs::remove_dir_all(&entry.dir).is_ok() {
+                size -= entry.size;
+            }
+        }
+
+        size
+    }
+
+    fn entry_dir(&self, key: &CacheKey) -> PathBuf {
+        self.dir.join(&key.0[..2]).join(&key.0)
+    }
+}
+
+struct Entry {
+    dir: PathBuf,
+    size: u64,
+    used: SystemTime,
+}
+
+/// All complete entries in a cache directory.
+fn entries(dir: &Path) -> Vec<Entry> {
+    let Ok(shards) = fs::read_dir(dir) else {
+        return vec![];
+    };
+
+    shards
+        .filter_map(Result::ok)
+        .filter(|shard| shard.file_name().len() == 2)
+        .filter_map(|shard| fs::read_dir(shard.path()).ok())
+        .flatten()
+        .filter_map(Result::ok)
+        .filter_map(|entry| {
+            let dir = entry.path();
+            let used = fs::metadata(dir.join(ENTRY_FILE)).ok()?.modified().ok()?;
+            let size = dir_size(&dir);
+            Some(Entry { dir, size, used })
+        })
+        .collect()
+}
+
+/// The shard and temporary directories in a cache directory, or an error if it contains
+/// anything that doesn't belong to the cache.
+fn owned_dirs(dir: &Path) -> Result<Vec<PathBuf>, String> {
+    let read_dir = |dir: &Path| {
+        fs::read_dir(dir).map_err(|e| format!("failed to read {}: {e}", dir.display()))
+    };
+
+    let mut owned = vec![];
+
+    for child in read_dir(dir)?.filter_map(Result::ok) {
+        let path = child.path();
+        let name = child.file_name().to_string_lossy().into_owned();
+
+        let is_owned = path.is_dir()
+            && (name.starts_with("tmp-")
+                || (is_hex(&name, 2)
+                    && read_dir(&path)?
+                        .filter_map(Result::ok)
+                        .all(|entry| is_entry(&entry.path()))));
+
+        if !is_owned {
+            return Err(format!(
+                "refusing to clear {}, {} doesn't belong to the render cache",
+                dir.display(),
+                path.display()
+            ));
+        }
+
+        owned.push(path);
+    }
+
+    Ok(owned)
+}
+
+/// Whether a path is the directory of a cache entry.
+fn is_entry(path: &Path) -> bool {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    is_hex(&name, 64) && path.join(ENTRY_FILE).is_file()
+}
+
+fn is_hex(name: &str, len: usize) -> bool {
+    name.len() == len && name.bytes().all(|b| b.is_ascii_hexdigit())
+}
+
+fn dir_size(dir: &Path) -> u64 {
+    fs::read_dir(dir)
+        .map(|files| {
+            files
+                .filter_map(Result::ok)
+                .filter_map(|file| file.metadata().ok())
+                .map(|metadata| metadata.len())
+                .sum()
+        })
+        .unwrap_or(0)
+}
@@ -8,12 +8,11 @@ use hayro::vello_cpu::color::AlphaColor;
 use hayro::RenderCache;
 #[cfg(feature = "hayro")]
 use hayro::RenderSettings;
+use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
 use std::cmp::min;
 use std::path::{Path, PathBuf};
 use std::process::{Child, Command, Stdio};
-#[cfg(feature = "hayro")]
-use std::sync::Arc;
-use std::sync::LazyLock;
+use std::sync::{Arc, LazyLock, Mutex, OnceLock};
 use std::{env, fs};
 use std::{
     thread,
@@ -22,19 +21,75 @@ use std::{
 use tempdir::TempDir;
 use tiny_skia::{Paint, PathBuilder, Pixmap, PixmapPaint, Stroke, Transform};
 
+mod cache;
 #[cfg(target_os = "macos")]
 mod quartz;
 
+pub use cache::{CacheKey, CacheOptions, DiskCache};
+
 const DOCKER_IMAGE: &str = concat!("vallaris/sitro-backends:", env!("CARGO_PKG_VERSION"));
 const DOCKER_START_TIMEOUT: Duration = Duration::from_secs(10);
 const DOCKER_POLL_INTERVAL: Duration = Duration::from_millis(100);
 
 /// The global render instance.
-pub static RENDER_INSTANCE: LazyLock<Result<Renderer, String>> = LazyLock::new(|| Renderer::new());
+pub static RENDER_INSTANCE: LazyLock<Result<Renderer, String>> = LazyLock::new(Renderer::new);
+
+/// The cache of the global render instance, until the instance is created.
+static GLOBAL_CACHE: Mutex<GlobalCache> = Mutex::new(GlobalCache::FromEnv);
+
+enum GlobalCache {
+    /// Open the cache configured by `SITRO_CACHE_DIR` and `SITRO_CACHE_MAX_SIZE`.
+    FromEnv,
+    /// Use the cache set with [`set_render_cache`].
+    Set(Option<DiskCache>),
+    /// The render instance has been created.
+    Taken,
+}
+
+/// Set the cache of [`RENDER_INSTANCE`], instead of the one configured by
+/// `SITRO_CACHE_DIR` and `SITRO_CACHE_MAX_SIZE`, or disable caching with `None`.
+///
+/// Fails if the render instance has already been created.
+pub fn set_render_cache(cache: Option<DiskCache>) -> Result<(), String> {
+    let mut global = GLOBAL_CACHE.lock().unwrap();
+
+    if matches!(*global, GlobalCache::Taken) {
+        return Err("the render instance has already been created".to_string());
+    }
+
+    *global = GlobalCache::Set(cache);
+    Ok(())
+}
 
 /// The renderer used to render PDFs with different backends.
+///
+/// If `SITRO_CACHE_DIR` is set, renders are cached in that directory and reused as long
+/// as the PDF, the backend, the Docker image (or the versions of the hayro crates) and the render options
+/// are the same. `SITRO_CACHE_MAX_SIZE` limits the size of the cache in megabytes.
+///
+/// Besides the default Docker image, a renderer can run additional images side by side,
+/// each with a label. Docker-based backends tagged with a label, like `pdfium@old`, render
+/// in the image with that label. The global render instance runs the images listed in
+/// `SITRO_DOCKER_IMAGES` as comma-separated `<LABEL>=<IMAGE>` pairs.
+///
+/// The container of an image is started when a backend first renders in it and misses
+/// the cache, so a renderer that only uses the native backends works without Docker.
 pub struct Renderer {
-    container_id: String,
+    image: Image,
+    images: Vec<(String, Image)>,
+    cache: Option<DiskCache>,
+}
+
+/// A Docker image with the backends, whose container is started on first use.
+struct Image {
+    name: String,
+    id: OnceLock<Result<String, String>>,
+    container: OnceLock<Result<Container, String>>,
+}
+
+/// A running Docker container with the backends.
+struct Container {
+    id: String,
     work_dir: TempDir,
     #[allow(dead_code)]
     child: Child, // Kept alive to maintain stdin pipe; container dies when this drops
@@ -42,82 +97,129 @@ pub struct Renderer {
 
 impl Renderer {
     fn new() -> Result<Self, String> {
-        let docker_image =
-            env::var("SITRO_DOCKER_IMAGE").unwrap_or_else(|_| DOCKER_IMAGE.to_string());
-        ensure_docker_image(&docker_image)?;
-        let work_dir = TempDir::new("sitro").map_err(|e| e.to_string())?;
+        let cache = match std::mem::replace(&mut *GLOBAL_CACHE.lock().unwrap(), GlobalCache::Taken)
+        {
+            GlobalCache::Set(cache) => cache,
+            _ => open_cache()?,
+        };
 
-        // Start container attached to stdin - when our process dies, stdin closes,
-        // cat exits, and --rm cleans up the container
-        let mut child = Command::new("docker")
-            .args(["run", "--rm", "-i", "--entrypoint", "cat", "-v"])
-            .arg(format!("{}:/work", work_dir.path().to_string_lossy()))
-            .arg(&docker_image)
-            .stdin(Stdio::piped())
-            .stdout(Stdio::null())
-            .stderr(Stdio::null())
-            .spawn()
-            .map_err(|e| format!("failed to start docker: {e}"))?;
+        let images = match env::var("SITRO_DOCKER_IMAGES") {
+            Ok(images) => parse_images(&images)?,
+            Err(_) => vec![],
+        };
 
-        // Poll until the container is running
-        let start = Instant::now();
-        let container_id = loop {
-            let output = Command::new("docker")
-                .args([
-                    "ps",
-                    "-q",
-                    "-l",
-                    "--filter",
-                    &format!("ancestor={docker_image}"),
-                ])
-                .output()
-                .map_err(|e| format!("failed to get container id: {e}"))?;
+        let images = images
+            .iter()
+            .map(|(label, image)| (label.as_str(), image.as_str()))
+            .collect::<Vec<_>>();
 
-            if !output.status.success() {
-                let error = String::from_utf8_lossy(&output.stderr);
-                let error = error.trim();
-                let _ = child.kill();
-                let _ = child.wait();
-                return Err(if error.is_empty() {
-                    format!("failed to query Docker: {}", output.status)
-                } else {
-                    format!("failed to query Docker: {error}")
-                });
-            }
+        Self::create(&images, cache)
+    }
 
-            let id = String::from_utf8_lossy(&output.stdout).trim().to_string();
-            if !id.is_empty() {
-                break id;
-            }
+    /// Create a renderer that runs the given Docker images next to the default one.
+    ///
+    /// Each image is given as a label and the name of the image, like
+    /// `("old", "vallaris/sitro-backends:0.1.0")`. Backends tagged with a label using
+    /// [`Backend::in_image`] render in the image with that label, so two versions of the
+    /// image can be compared like any two backends. The labels are validated right away,
+    /// but the images are only pulled and started when they are first used.
+    pub fn with_images(images: &[(&str, &str)]) -> Result<Self, String> {
+        Self::create(images, open_cache()?)
+    }
 
-            if let Some(status) = child
-                .try_wait()
-                .map_err(|e| format!("failed to check Docker container process: {e}"))?
-            {
+    fn create(images: &[(&str, &str)], cache: Option<DiskCache>) -> Result<Self, String> {
+        for (i, (label, _)) in images.iter().enumerate() {
+            if !is_valid_label(label) {
                 return Err(format!(
-                    "Docker container exited before it started: {status}"
+                    "invalid image label `{label}`, labels can't be empty or contain `@`, `,` or `=`"
                 ));
             }
 
-            if start.elapsed() >= DOCKER_START_TIMEOUT {
-                let _ = child.kill();
-                let _ = child.wait();
-                return Err(format!(
-                    "timed out waiting for Docker container after {} seconds",
-                    DOCKER_START_TIMEOUT.as_secs()
-                ));
+            if images[..i].iter().any(|(other, _)| other == label) {
+                return Err(format!("duplicate image label `{label}`"));
             }
+        }
 
-            thread::sleep(DOCKER_POLL_INTERVAL);
-        };
+        let docker_image =
+            env::var("SITRO_DOCKER_IMAGE").unwrap_or_else(|_| DOCKER_IMAGE.to_string());
+        let images = images
+            .iter()
+            .map(|(label, image)| (label.to_string(), Image::new(image)))
+            .collect();
 
         Ok(Self {
-            container_id,
-            work_dir,
-            child,
+            image: Image::new(&docker_image),
+            images,
+            cache,
         })
     }
 
+    /// The labels of the additional images the renderer runs.
+    pub fn image_labels(&self) -> Vec<&str> {
+        self.images
+            .iter()
+            .map(|(label, _)| label.as_str())
+            .collect()
+    }
+
+    /// The Docker image a Docker-based backend renders in.
+    fn image(&self, backend: &Backend) -> Result<&Image, String> {
+        let Some(label) = backend.image_label() else {
+            return Ok(&self.image);
+        };
+
+        self.images
+            .iter()
+            .find(|(l, _)| l == label)
+            .map(|(_, image)| image)
+            .ok_or_else(|| {
+                format!("there is no Docker image with the label `{label}`, add it to SITRO_DOCKER_IMAGES")
+            })
+    }
+
+    /// The container a Docker-based backend renders in.
+    fn container(&self, backend: &Backend) -> Result<&Container, String> {
+        let container = self.image(backend)?.container();
+
+        match backend.image_label() {
+            Some(label) => container.map_err(|e| format!("failed to start image `{label}`: {e}")),
+            None => container,
+        }
+    }
+
+    /// Check whether a backend can render with this renderer.
+    ///
+    /// Besides [`Backend::is_available`], this checks that the Docker image of a
+    /// Docker-based backend can be found or pulled, without starting its container. The
+    /// result for an image is cached, so it is only checked once.
+    pub fn check_available(&self, backend: &Backend) -> Result<(), String> {
+        match backend {
+            _ if !backend.is_available() => Err("not available on this platform".to_string()),
+            Backend::Quartz | Backend::Hayro => Ok(()),
+            _ => self.image(backend)?.id().map(|_| ()),
+        }
+    }
+
+    /// The cache renders are served from, if caching is enabled.
+    pub fn cache(&self) -> Option<&DiskCache> {
+        self.cache.as_ref()
+    }
+
+    /// Identifies the code that renders with a backend, or `None` if renders of the
+    /// backend shouldn't be cached.
+    fn engine(&self, backend: &Backend) -> Option<String> {
+        match backend {
+            // Depends on the version of macOS.
+            Backend::Quartz => None,
+            #[cfg(feature = "hayro")]
+            Backend::Hayro => Some(env!("SITRO_HAYRO_ENGINE").to_string()),
+            #[cfg(not(feature = "hayro"))]
+            Backend::Hayro => None,
+            // Resolving the image doesn't start its container, so cache hits don't either.
+            _ => self.image(backend).ok()?.id().ok().map(str::to_string),
+        }
+    }
+
     /// Render a PDF using the specified backend.
     pub fn render(
         &self,
@@ -125,11 +227,51 @@ impl Renderer {
         buf: &[u8],
         options: &RenderOptions,
     ) -> Result<RenderedDocument, String> {
+        self.render_with_warnings(backend, buf, options)
+            .map(|(document, _)| document)
+    }
+
+    /// Render a PDF using the specified backend and also return the warnings it reported.
+    ///
+    /// For Docker-based backends, the warnings are the lines the backend wrote to stderr.
+    pub fn render_with_warnings(
+        &self,
+        backend: &Backend,
+        buf: &[u8],
+        options: &RenderOptions,
+    ) -> Result<(RenderedDocument, Vec<String>), String> {
+        let cached = self.cache.as_ref().and_then(|cache| {
+            let engine = self.engine(backend)?;
+            Some((cache, CacheKey::new(&engine, backend, buf, options)))
+        });
+
+        if let Some((cache, key)) = &cached {
+            if let Some(hit) = cache.get(key) {
+                return Ok(hit);
+            }
+        }
+
+        let (document, warnings) = self.render_uncached(backend, buf, options)?;
+
+        if let Some((cache, key)) = &cached {
+            // A failure to write the cache shouldn't fail the render.
+            let _ = cache.insert(key, &document, &warnings);
+        }
+
+        Ok((document, warnings))
+    }
+
+    fn render_uncached(
+        &self,
+        backend: &Backend,
+        buf: &[u8],
+        options: &RenderOptions,
+    ) -> Result<(RenderedDocument, Vec<String>), String> {
         // For native backends, handle directly without Docker
         match backend {
             Backend::Quartz => {
                 #[cfg(target_os = "macos")]
-                return quartz::render(buf, options);
+                return quartz::render(buf, options).map(|document| (document, vec![]));
                 #[cfg(not(target_os = "macos"))]
                 panic!("Quartz backend is only available on macOS");
             }
@@ -140,9 +282,11 @@ impl Renderer {
             _ => {}
         }
 
+        let container = self.container(backend)?;
+
         // Create a unique subdirectory for this render to allow parallel execution
         let render_id = uuid::Uuid::new_v4().to_string();
-        let render_dir = self.work_dir.path().join(&render_id);
+        let render_dir = container.work_dir.path().join(&render_id);
         fs::create_dir_all(&render_dir).map_err(|e| e.to_string())?;
 
         // Write input PDF
@@ -150,9 +294,9 @@ impl Renderer {
 
         // Execute render command
         let output = Command::new("docker")
-            .args(["exec", &self.container_id, "/opt/bin/entrypoint.sh"])
+            .args(["exec", &container.id, "/opt/bin/entrypoint.sh"])
             .args([
-                &backend.name(),
+                &backend.base().name(),
                 &options.scale.to_string(),
                 &format!("/work/{render_id}"),
             ])
@@ -167,9 +311,16 @@ impl Renderer {
             ));
         }
 
+        let warnings = String::from_utf8_lossy(&output.stderr)
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .map(str::to_string)
+            .collect();
+
         let result = read_output_files(&render_dir, r"(?m)out-(\d+).png");
         let _ = fs::remove_dir_all(&render_dir);
-        result
+        result.map(|document| (document, warnings))
     }
 
     /// Render a PDF and return pixmaps with optional border.
@@ -183,6 +334,156 @@ impl Renderer {
         let pages = self.render(backend, buf, options)?;
         render_pages_to_pixmaps(&pages, backend.color(), border_width)
     }
+
+    /// Render a PDF with multiple backends in parallel and return the pixmaps without border.
+    ///
+    /// Fails if any of the backends fails, prefixing the error with the backend name.
+    pub(crate) fn render_all_as_pixmaps(
+        &self,
+        backends: &[Backend],
+        buf: &[u8],
+        options: &RenderOptions,
+    ) -> Result<Vec<(Backend, Vec<Pixmap>)>, String> {
+        backends
+            .par_iter()
+            .map(|backend| {
+                self.render_as_pixmap(backend, buf, options, None)
+                    .map(|pixmaps| (backend.clone(), pixmaps))
+                    .map_err(|e| format!("{}: {e}", backend.name()))
+            })
+            .collect()
+    }
+}
+
+impl Image {
+    fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            id: OnceLock::new(),
+            container: OnceLock::new(),
+        }
+    }
+
+    /// The ID of the image, which is pulled if it isn't available locally. Like the
+    /// container, it is only resolved once.
+    fn id(&self) -> Result<&str, String> {
+        self.id
+            .get_or_init(|| {
+                ensure_docker_image(&self.name)?;
+                docker_image_id(&self.name)
+            })
+            .as_deref()
+            .map_err(Clone::clone)
+    }
+
+    /// The container of the image, which is started if it isn't running yet. If it
+    /// fails to start, the error is returned for all later uses as well.
+    fn container(&self) -> Result<&Container, String> {
+        self.container
+            .get_or_init(|| {
+                self.id()?;
+                Container::start(&self.name)
+            })
+            .as_ref()
+            .map_err(Clone::clone)
+    }
+}
+
+impl Container {
+    /// Start a container of an image that is available locally.
+    fn start(docker_image: &str) -> Result<Self, String> {
+        let work_dir = TempDir::new("sitro").map_err(|e| e.to_string())?;
+
+        // Start container attached to stdin - when our process dies, stdin closes,
+        // cat exits, and --rm cleans up the container
+        let mut child = Command::new("docker")
+            .args(["run", "--rm", "-i", "--entrypoint", "cat", "-v"])
+            .arg(format!("{}:/work", work_dir.path().to_string_lossy()))
+            .arg(docker_image)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+            .map_err(|e| format!("failed to start docker: {e}"))?;
+
+        // Poll until the container is running
+        let start = Instant::now();
+        let id = loop {
+            let output = Command::new("docker")
+                .args([
+                    "ps",
+                    "-q",
+                    "-l",
+                    "--filter",
+                    &format!("ancestor={docker_image}"),
+                ])
+                .output()
+                .map_err(|e| format!("failed to get container id: {e}"))?;
+
+            if !output.status.success() {
+                let error = String::from_utf8_lossy(&output.stderr);
+                let error = error.trim();
+                let _ = child.kill();
+                let _ = child.wait();
+                return Err(if error.is_empty() {
+                    format!("failed to query Docker: {}", output.status)
+                } else {
+                    format!("failed to query Docker: {error}")
+                });
+            }
+
+            let id = String::from_utf8_lossy(&output.stdout).trim().to_string();
+            if !id.is_empty() {
+                break id;
+            }
+
+            if let Some(status) = child
+                .try_wait()
+                .map_err(|e| format!("failed to check Docker container process: {e}"))?
+            {
+                return Err(format!(
+                    "Docker container exited before it started: {status}"
+                ));
+            }
+
+            if start.elapsed() >= DOCKER_START_TIMEOUT {
+                let _ = child.kill();
+                let _ = child.wait();
+                return Err(format!(
+                    "timed out waiting for Docker container after {} seconds",
+                    DOCKER_START_TIMEOUT.as_secs()
+                ));
+            }
+
+            thread::sleep(DOCKER_POLL_INTERVAL);
+        };
+
+        Ok(Self {
+            id,
+            work_dir,
+            child,
+        })
+    }
+}
+
+/// Parse the additional images of the global render instance, given as comma-separated
+/// `<LABEL>=<IMAGE>` pairs.
+fn parse_images(value: &str) -> Result<Vec<(String, String)>, String> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let (label, image) = pair.split_once('=').ok_or_else(|| {
+                format!("invalid SITRO_DOCKER_IMAGES entry `{pair}`, expected <LABEL>=<IMAGE>")
+            })?;
+            Ok((label.trim().to_string(), image.trim().to_string()))
+        })
+        .collect()
+}
+
+fn is_valid_label(label: &str) -> bool {
+    !label.is_empty() && !label.contains(['@', ',', '=']) && !label.contains(char::is_whitespace)
 }
 
 fn ensure_docker_image(docker_image: &str) -> Result<(), String> {
@@ -213,6 +514,33 @@ fn ensure_docker_image(docker_image: &str) -> Result<(), String> {
     }
 }
 
+/// The ID of a Docker image, which changes whenever the contents of the image change,
+/// even if the tag stays the same.
+fn docker_image_id(docker_image: &str) -> Result<String, String> {
+    let output = Command::new("docker")
+        .args(["image", "inspect", "--format", "{{.Id}}", docker_image])
+        .output()
+        .map_err(|e| format!("failed to inspect Docker image: {e}"))?;
+
+    if !output.status.success() {
+        return Err(format!(
+            "failed to inspect Docker image: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// Open the render cache configured by `SITRO_CACHE_DIR` and `SITRO_CACHE_MAX_SIZE`.
+fn open_cache() -> Result<Option<DiskCache>, String> {
+    let Some(dir) = env::var_os("SITRO_CACHE_DIR").filter(|dir| !dir.is_empty()) else {
+        return Ok(None);
+    };
+
+    DiskCache::open(dir, CacheOptions::from_env()?).map(Some)
+}
+
 /// The options that should be applied when rendering a PDF to a pixmap.
 #[derive(Copy, Clone)]
 pub struct RenderOptions {
@@ -235,7 +563,7 @@ pub type RenderedDocument = Vec<RenderedPage>;
 ///
 /// Each backend calls a command-line utility in the background (via Docker),
 /// except for Quartz and Hayro which run natively.
-#[derive(Copy, Clone, Eq, PartialEq)]
+#[derive(Clone, Debug, Eq, PartialEq)]
 #[non_exhaustive]
 pub enum Backend {
     /// The pdfium backend (via Docker).
@@ -256,10 +584,94 @@ pub enum Backend {
     Hayro,
     /// The serenity backend (SerenityOS LibPDF, via Docker).
     Serenity,
+    /// A Docker-based backend running in the additional image with the given label,
+    /// like `pdfium@old`. Created with [`Backend::in_image`].
+    Tagged(&'static Backend, ImageLabel),
+}
+
+/// The label of an additional Docker image that a tagged backend renders in.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ImageLabel(Arc<str>);
+
+impl ImageLabel {
+    /// The label as a string.
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
 }
 
+/// The backends tagged backends refer to.
+static BACKENDS: [Backend; 9] = Backend::ALL;
+
 impl Backend {
+    /// All backends, in the order they are usually displayed in.
+    pub const ALL: [Backend; 9] = [
+        Backend::Mupdf,
+        Backend::Ghostscript,
+        Backend::Pdfium,
+        Backend::Poppler,
+        Backend::Quartz,
+        Backend::Pdfjs,
+        Backend::Pdfbox,
+        Backend::Hayro,
+        Backend::Serenity,
+    ];
+
+    /// Get the backend with the given name, as returned by [`Backend::name`].
+    pub fn from_name(name: &str) -> Option<Self> {
+        if let Some((name, label)) = name.split_once('@') {
+            return Self::from_name(name)?.in_image(label);
+        }
+
+        Self::ALL.into_iter().find(|backend| backend.name() == name)
+    }
+
+    /// The backend running in the additional Docker image with the given label, see
+    /// [`Renderer::with_images`].
+    ///
+    /// Returns `None` for backends that don't run in Docker, backends that are already
+    /// tagged and invalid labels.
+    pub fn in_image(&self, label: &str) -> Option<Self> {
+        if matches!(self, Backend::Quartz | Backend::Hayro | Backend::Tagged(..))
+            || !is_valid_label(label)
+        {
+            return None;
+        }
+
+        let base = BACKENDS.iter().find(|backend| *backend == self)?;
+        Some(Backend::Tagged(base, ImageLabel(label.into())))
+    }
+
+    /// Sort backends in the order of [`Backend::ALL`], with tagged backends following
+    /// their base backend, ordered by label.
+    pub fn sort(backends: &mut [Backend]) {
+        backends.sort_by_key(|backend| {
+            let base = backend.base();
+            let position = Self::ALL.iter().position(|b| *b == base);
+            (position, backend.image_label().map(str::to_string))
+        });
+    }
+
+    /// The backend without the image label.
+    pub fn base(&self) -> Backend {
+        match self {
+            Backend::Tagged(base, _) => (*base).clone(),
+            _ => self.clone(),
+        }
+    }
+
+    /// The label of the Docker image the backend runs in, or `None` for the default
+    /// image and native backends.
+    pub fn image_label(&self) -> Option<&str> {
+        match self {
+            Backend::Tagged(_, label) => Some(label.as_str()),
+            _ => None,
+        }
+    }
+
     /// Get the name of the backend.
+    ///
+    /// Tagged backends are named after the backend and the label, like `pdfium@old`.
     pub fn name(&self) -> String {
         match self {
             Backend::Pdfium => "pdfium".to_string(),
@@ -271,6 +683,20 @@ impl Backend {
             Backend::Ghostscript => "ghostscript".to_string(),
             Backend::Hayro => "hayro".to_string(),
             Backend::Serenity => "serenity".to_string(),
+            Backend::Tagged(base, label) => format!("{}@{}", base.name(), label.as_str()),
+        }
+    }
+
+    /// Whether the backend can be used on the current platform with the enabled features.
+    ///
+    /// Docker-based backends are always considered available, use
+    /// [`Renderer::check_available`] to check whether Docker can run them.
+    pub fn is_available(&self) -> bool {
+        match self {
+            Backend::Quartz => cfg!(target_os = "macos"),
+            Backend::Hayro => cfg!(feature = "hayro"),
+            Backend::Tagged(base, _) => base.is_available(),
+            _ => true,
         }
     }
 
@@ -285,81 +711,127 @@ impl Backend {
             Backend::Ghostscript => (235, 38, 218),
             Backend::Hayro => (57, 212, 116),
             Backend::Serenity => (148, 87, 235),
+            Backend::Tagged(base, label) => {
+                // A darker or lighter shade of the base color, picked by the label, so
+                // that tagged backends can be told apart from each other and from the
+                // base, and keep their color across runs.
+                let (target, amount) = SHADES[label_hash(label.as_str()) % SHADES.len()];
+                let (r, g, b) = base.color();
+                let mix = |c: u8| (c as f32 + (target - c as f32) * amount).round() as u8;
+                (mix(r), mix(g), mix(b))
+            }
         }
     }
 }
 
+/// The shades of tagged backends, as the color that the base color is mixed with and by
+/// how much.
+const SHADES: [(f32, f32); 6] = [
+    (0.0, 0.3),
+    (255.0, 0.35),
+    (0.0, 0.5),
+    (255.0, 0.55),
+    (0.0, 0.7),
+    (255.0, 0.75),
+];
+
+/// A hash of an image label that, unlike the standard library's hasher, is the same
+/// in every process (FNV-1a).
+fn label_hash(label: &str) -> usize {
+    label.bytes().fold(0xcbf29ce484222325u64, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+    }) as usize
+}
+
 /// Helper function to convert rendered PNG pages to pixmaps with optional borders.
-fn render_pages_to_pixmaps(
+pub(crate) fn render_pages_to_pixmaps(
     pages: &[RenderedPage],
     color: (u8, u8, u8),
     border_width: Option<f32>,
 ) -> Result<Vec<Pixmap>, String> {
-    let Some(border_width) = border_width else {
-        return pages
-            .iter()
-            .map(|page| {
-                Pixmap::decode_png(page).map_err(|_| "unable to generate pixmap".to_string())
-            })
-            .collect();
-    };
-
-    let mut pixmaps = vec![];
-
-    for page in pages {
-        let decoded = Pixmap::decode_png(page).unwrap();
-        let width = imagesize::blob_size(page).unwrap().width as f32;
-        let height = imagesize::blob_size(page).unwrap().height as f32;
-        let border_width = min(width as u32, height as u32) as f32 * border_width;
-
-        let actual_width = width + border_width;
-        let actual_height = height + border_width;
-
-        let path = {
-            let mut pb = PathBuilder::new();
-            pb.move_to(0.0, 0.0);
-            pb.line_to(width, 0.0);
-            pb.line_to(width, height);
-            pb.line_to(0.0, height);
-            pb.close();
-            pb.finish().unwrap()
-        };
+    pages
+        .iter()
+        .map(|page| {
+            let decoded =
+                Pixmap::decode_png(page).map_err(|_| "unable to generate pixmap".to_string())?;
 
-        let mut pixmap = Pixmap::new(actual_width as u32, actual_height as u32).unwrap();
+            Ok(match border_width {
+                Some(border_width) => draw_border(&decoded, color, border_width),
+                None => decoded,
+            })
+        })
+        .collect()
+}
 
-        let mut stroke = Stroke::default();
-        stroke.width = border_width;
+/// Draw a border in the color of the backend around a rendered page.
+///
+/// The width of the border is given relative to the smaller side of the page, and the
+/// page is offset by the border width in both directions.
+pub fn add_border(pixmap: &Pixmap, backend: &Backend, border_width: f32) -> Pixmap {
+    draw_border(pixmap, backend.color(), border_width)
+}
 
-        let mut paint = Paint::default();
-        paint.set_color_rgba8(color.0, color.1, color.2, 255);
+pub(crate) fn draw_border(decoded: &Pixmap, color: (u8, u8, u8), border_width: f32) -> Pixmap {
+    let width = decoded.width() as f32;
+    let height = decoded.height() as f32;
+    let border_width = min(width as u32, height as u32) as f32 * border_width;
+
+    let actual_width = width + border_width;
+    let actual_height = height + border_width;
+
+    let path = {
+        let mut pb = PathBuilder::new();
+        pb.move_to(0.0, 0.0);
+        pb.line_to(width, 0.0);
+        pb.line_to(width, height);
+        pb.line_to(0.0, height);
+        pb.close();
+        pb.finish().unwrap()
+    };
 
-        pixmap.draw_pixmap(
-            0,
-            0,
-            decoded.as_ref(),
-            &PixmapPaint::default(),
-            Transform::from_translate(border_width, border_width),
-            None,
-        );
+    let mut pixmap = Pixmap::new(actual_width as u32, actual_height as u32).unwrap();
 
-        pixmap.stroke_path(
-            &path,
-            &paint,
-            &stroke,
-            Transform::from_translate(border_width / 2.0, border_width / 2.0),
-            None,
-        );
-        pixmaps.push(pixmap);
-    }
+    let stroke = Stroke {
+        width: border_width,
+        ..Stroke::default()
+    };
 
-    Ok(pixmaps)
+    let mut paint = Paint::default();
+    paint.set_color_rgba8(color.0, color.1, color.2, 255);
+
+    pixmap.draw_pixmap(
+        0,
+        0,
+        decoded.as_ref(),
+        &PixmapPaint::default(),
+        Transform::from_translate(border_width, border_width),
+        None,
+    );
+
+    pixmap.stroke_path(
+        &path,
+        &paint,
+        &stroke,
+        Transform::from_translate(border_width / 2.0, border_width / 2.0),
+        None,
+    );
+
+    pixmap
 }
 
 /// Render a PDF file using hayro.
 #[cfg(feature = "hayro")]
-fn render_hayro(buf: &[u8], options: &RenderOptions) -> Result<RenderedDocument, String> {
+fn render_hayro(
+    buf: &[u8],
+    options: &RenderOptions,
+) -> Result<(RenderedDocument, Vec<String>), String> {
     let pdf = Pdf::new(Arc::new(buf.to_vec())).map_err(|e| format!("{:?}", e))?;
-    let interpreter_settings = InterpreterSettings::default();
+    let warnings = Arc::new(Mutex::new(vec![]));
+    let sink = warnings.clone();
+    let interpreter_settings = InterpreterSettings {
+        warning_sink: Arc::new(move |warning| sink.lock().unwrap().push(format!("{warning:?}"))),
+        ..InterpreterSettings::default()
+    };
 
     let render_settings = RenderSettings {
         x_scale: options.scale,
@@ -370,14 +842,20 @@ fn render_hayro(buf: &[u8], options: &RenderOptions) -> Result<RenderedDocument,
     };
     let cache = RenderCache::new();
 
-    pdf.pages()
+    let document = pdf
+        .pages()
         .iter()
         .map(|page| {
             hayro::render(page, &cache, &interpreter_settings, &render_settings)
                 .into_png()
                 .map_err(|e| format!("{:?}", e))
         })
-        .collect()
+        .collect::<Result<_, _>>()?;
+
+    let mut warnings = warnings.lock().unwrap().clone();
+    warnings.dedup();
+
+    Ok((document, warnings))
 }
 
 /// Read output PNG files from a directory, matching them against a pattern.
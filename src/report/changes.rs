@@ -0,0 +1,414 @@
+use super::{BackendSummary, JSON_REPORT_VERSION};
+use crate::diff::{diff_pixmaps, DiffOptions};
+use crate::json::Json;
+use crate::renderer::Backend;
+use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
+use std::fmt::{self, Display, Formatter};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tiny_skia::Pixmap;
+
+/// A corpus run that was written to disk by the command-line tool.
+///
+/// The run is read from the JSON report in `report.json` and the pages of each backend
+/// in the HTML report in `report/`.
+pub struct StoredRun {
+    dir: PathBuf,
+    /// The scale the run was rendered at.
+    pub scale: f32,
+    /// The tool that wrote the run, like `sitro 0.2.0`.
+    pub generator: String,
+    /// The files of the run.
+    pub files: Vec<StoredFile>,
+}
+
+/// A file of a [`StoredRun`].
+pub struct StoredFile {
+    /// The name of the file.
+    pub name: String,
+    /// The index of the first page of the run in the document.
+    pub first_page: usize,
+    /// The number of pages that were compared between the backends.
+    pub page_count: usize,
+    /// The summary of each backend.
+    pub backends: Vec<BackendSummary>,
+}
+
+impl StoredFile {
+    /// The indices of the pages of a backend in the document that were stored.
+    fn pages(&self, backend: &BackendSummary) -> std::ops::Range<usize> {
+        self.first_page..self.first_page + backend.pages.len().min(self.page_count)
+    }
+}
+
+impl StoredRun {
+    /// Read a run from the output directory of the command-line tool.
+    pub fn open(dir: impl Into<PathBuf>) -> Result<Self, String> {
+        let dir = dir.into();
+        let path = dir.join("report.json");
+        let text = fs::read_to_string(&path)
+            .map_err(|e| format!("failed to read {}: {e}", path.display()))?;
+        let report = Json::parse(&text).map_err(|e| format!("{}: {e}", path.display()))?;
+        let invalid = |what: &str| format!("{}: invalid report, {what}", path.display());
+
+        let version = report
+            .get("version")
+            .and_then(Json::as_f64)
+            .ok_or_else(|| invalid("missing version"))?;
+
+        if version != JSON_REPORT_VERSION as f64 {
+            return Err(format!(
+                "{}: unsupported report version {version} (expected {JSON_REPORT_VERSION})",
+                path.display()
+            ));
+        }
+
+        let files = report
+            .get("files")
+            .and_then(Json::as_array)
+            .ok_or_else(|| invalid("missing files"))?
+            .iter()
+            .map(|file| parse_file(file).ok_or_else(|| invalid("malformed file")))
+            .collect::<Result<_, _>>()?;
+
+        Ok(Self {
+            dir,
+            scale: report
+                .get("scale")
+                .and_then(Json::as_f64)
+                .ok_or_else(|| invalid("missing scale"))? as f32,
+            generator: report
+                .get("generator")
+                .and_then(Json::as_str)
+                .unwrap_or_default()
+                .to_string(),
+            files,
+        })
+    }
+
+    /// The directory of the run.
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Get a file of the run by name.
+    pub fn file(&self, name: &str) -> Option<&StoredFile> {
+        self.files.iter().find(|file| file.name == name)
+    }
+
+    /// Load the page with the given index in the document rendered by a backend.
+    pub fn page(
+        &self,
+        file: &StoredFile,
+        backend: &Backend,
+        page: usize,
+    ) -> Result<Pixmap, String> {
+        let index = find(Some(file), backend)
+            .filter(|(file, summary)| file.pages(summary).contains(&page))
+            .map(|(file, _)| page - file.first_page)
+            .ok_or_else(|| format!("{} has no page {page} of {}", backend.name(), file.name))?;
+        let path = self
+            .dir
+            .join("report")
+            .join("files")
+            .join(super::html::slug(&file.name))
+            .join(format!("{index}-{}.png", backend.name()));
+
+        Pixmap::load_png(&path).map_err(|e| format!("failed to read {}: {e}", path.display()))
+    }
+}
+
+fn parse_file(file: &Json) -> Option<StoredFile> {
+    let number = |json: &Json, key| json.get(key)?.as_f64();
+    let pages = file.get("pages")?.as_array()?;
+
+    let backends = file
+        .get("backends")?
+        .as_array()?
+        .iter()
+        .map(|backend| {
+            Some(BackendSummary {
+                backend: Backend::from_name(backend.get("backend")?.as_str()?)?,
+                pages: backend
+                    .get("pages")?
+                    .as_array()?
+                    .iter()
+                    .map(|page| {
+                        Some((
+                            number(page, "width")? as u32,
+                            number(page, "height")? as u32,
+                        ))
+                    })
+                    .collect::<Option<_>>()?,
+                error: backend.get("error")?.as_str().map(str::to_string),
+                warnings: backend
+                    .get("warnings")?
+                    .as_array()?
+                    .iter()
+                    .map(|warning| warning.as_str().map(str::to_string))
+                    .collect::<Option<_>>()?,
+                duration: Duration::from_secs_f64(number(backend, "render_time_ms")? / 1000.0),
+            })
+        })
+        .collect::<Option<_>>()?;
+
+    Some(StoredFile {
+        name: file.get("name")?.as_str()?.to_string(),
+        first_page: number(file, "first_page")? as usize,
+        page_count: pages.len(),
+        backends,
+    })
+}
+
+/// The options that should be applied when comparing two runs.
+#[derive(Copy, Clone)]
+pub struct ChangeOptions {
+    /// The options used to compare the pages of a backend in both runs.
+    pub diff: DiffOptions,
+    /// The fraction of pixels that may differ before a page counts as changed.
+    pub threshold: f32,
+}
+
+impl Default for ChangeOptions {
+    fn default() -> Self {
+        Self {
+            diff: DiffOptions::default(),
+            threshold: 0.0,
+        }
+    }
+}
+
+/// How the output of a backend changed between two runs.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ChangeKind {
+    /// The file or page was only rendered in the run after.
+    Added,
+    /// The file or page was only rendered in the run before.
+    Removed,
+    /// The backend failed in one of the runs, or failed with a different error.
+    Status {
+        /// The error in the run before, if any.
+        before: Option<String>,
+        /// The error in the run after, if any.
+        after: Option<String>,
+    },
+    /// The page has a different size.
+    Resized {
+        /// The size in the run before.
+        before: (u32, u32),
+        /// The size in the run after.
+        after: (u32, u32),
+    },
+    /// Pixels of the page changed.
+    Pixels {
+        /// The fraction of pixels that changed.
+        ratio: f32,
+    },
+}
+
+/// A change in the output of a backend between two runs.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Change {
+    /// The name of the file.
+    pub file: String,
+    /// The backend.
+    pub backend: Backend,
+    /// The index of the page in the document, or `None` if the whole file changed.
+    pub page: Option<usize>,
+    /// How it changed.
+    pub kind: ChangeKind,
+}
+
+impl Display for Change {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        f.write_str(&self.file)?;
+
+        if let Some(page) = self.page {
+            write!(f, " page {}", page + 1)?;
+        }
+
+        match &self.kind {
+            ChangeKind::Added => f.write_str(": new"),
+            ChangeKind::Removed => f.write_str(": no longer rendered"),
+            ChangeKind::Status {
+                before: None,
+                after: Some(e),
+            } => write!(f, ": fails now: {e}"),
+            ChangeKind::Status {
+                before: Some(e),
+                after: None,
+            } => write!(f, ": renders now, failed before: {e}"),
+            ChangeKind::Status { before, after } => write!(
+                f,
+                ": fails with a different error: {} (was {})",
+                after.as_deref().unwrap_or_default(),
+                before.as_deref().unwrap_or_default()
+            ),
+            ChangeKind::Resized { before, after } => write!(
+                f,
+                ": size changed from {}x{} to {}x{}",
+                before.0, before.1, after.0, after.1
+            ),
+            ChangeKind::Pixels { ratio } => {
+                write!(f, ": {:.2}% of pixels changed", ratio * 100.0)
+            }
+        }
+    }
+}
+
+/// Compare two runs and return how the output of each backend changed, ordered by file,
+/// backend and page.
+///
+/// Pages are compared pixel by pixel if they were rendered in both runs. Both runs must
+/// have been rendered at the same scale.
+pub fn compare_runs(
+    before: &StoredRun,
+    after: &StoredRun,
+    options: &ChangeOptions,
+) -> Result<Vec<Change>, String> {
+    if before.scale != after.scale {
+        return Err(format!(
+            "the runs were rendered at different scales ({} and {})",
+            before.scale, after.scale
+        ));
+    }
+
+    let mut names = before
+        .files
+        .iter()
+        .chain(&after.files)
+        .map(|file| file.name.as_str())
+        .collect::<Vec<_>>();
+    names.sort_unstable();
+    names.dedup();
+
+    let changes = names
+        .par_iter()
+        .map(|name| compare_file(before, after, name, options))
+        .collect::<Result<Vec<_>, String>>()?;
+
+    Ok(changes.into_iter().flatten().collect())
+}
+
+fn compare_file(
+    before: &StoredRun,
+    after: &StoredRun,
+    name: &str,
+    options: &ChangeOptions,
+) -> Result<Vec<Change>, String> {
+    let (old_file, new_file) = (before.file(name), after.file(name));
+    let mut backends = vec![];
+
+    for file in [new_file, old_file].into_iter().flatten() {
+        for backend in &file.backends {
+            if !backends.contains(&backend.backend) {
+                backends.push(backend.backend.clone());
+            }
+        }
+    }
+
+    let mut changes = vec![];
+
+    for backend in &backends {
+        let change = |page, kind| Change {
+            file: name.to_string(),
+            backend: backend.clone(),
+            page,
+            kind,
+        };
+
+        let (old, new) = match (find(old_file, backend), find(new_file, backend)) {
+            (Some(old), Some(new)) => (old, new),
+            (None, _) => {
+                changes.push(change(None, ChangeKind::Added));
+                continue;
+            }
+            (_, None) => {
+                changes.push(change(None, ChangeKind::Removed));
+                continue;
+            }
+        };
+
+        if old.1.error != new.1.error {
+            changes.push(change(
+                None,
+                ChangeKind::Status {
+                    before: old.1.error.clone(),
+                    after: new.1.error.clone(),
+                },
+            ));
+        }
+
+        if old.1.error.is_some() || new.1.error.is_some() {
+            continue;
+        }
+
+        let (old_pages, new_pages) = (old.0.pages(old.1), new.0.pages(new.1));
+        let first = old_pages.start.min(new_pages.start);
+        let last = old_pages.end.max(new_pages.end);
+
+        for page in first..last {
+            let kind = match (old_pages.contains(&page), new_pages.contains(&page)) {
+                (false, false) => continue,
+                (false, true) => ChangeKind::Added,
+                (true, false) => ChangeKind::Removed,
+                (true, true) => {
+                    let old_size = old.1.pages[page - old.0.first_page];
+                    let new_size = new.1.pages[page - new.0.first_page];
+
+                    if old_size != new_size {
+                        ChangeKind::Resized {
+                            before: old_size,
+                            after: new_size,
+                        }
+                    } else {
+                        let old_pixmap = before.page(old.0, backend, page)?;
+                        let new_pixmap = after.page(new.0, backend, page)?;
+                        let ratio = diff_pixmaps(&old_pixmap, &new_pixmap, &options.diff).ratio();
+
+                        if ratio <= options.threshold {
+                            continue;
+                        }
+
+                        ChangeKind::Pixels { ratio }
+                    }
+                }
+            };
+
+            changes.push(change(Some(page), kind));
+        }
+    }
+
+    Ok(changes)
+}
+
+fn find<'a>(
+    file: Option<&'a StoredFile>,
+    backend: &Backend,
+) -> Option<(&'a StoredFile, &'a BackendSummary)> {
+    let file = file?;
+    let summary = file.backends.iter().find(|b| b.backend == *backend)?;
+    Some((file, summary))
+}
+
+/// Group changes by backend, in the order of [`Backend::sort`].
+pub fn changes_by_backend(changes: &[Change]) -> Vec<(Backend, Vec<&Change>)> {
+    let mut backends = vec![];
+
+    for change in changes {
+        if !backends.contains(&change.backend) {
+            backends.push(change.backend.clone());
+        }
+    }
+
+    Backend::sort(&mut backends);
+
+    backends
+        .into_iter()
+        .map(|backend| {
+            let group = changes.iter().filter(|c| c.backend == backend).collect();
+            (backend, group)
+        })
+        .collect()
+}
@@ -0,0 +1,519 @@
+use super::{
+    changes_by_backend, escape, Change, ChangeKind, ChangeOptions, FileRun, FileSummary, StoredRun,
+};
+use crate::consensus::ConsensusOptions;
+use crate::diff::diff_pixmaps;
+use crate::renderer::Backend;
+use std::fmt::Write;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// The options that should be applied when writing an HTML report.
+#[derive(Copy, Clone, Default)]
+pub struct HtmlReportOptions {
+    /// The options used to compare each backend with the consensus of all backends.
+    pub consensus: ConsensusOptions,
+}
+
+/// A static HTML report of a corpus run.
+///
+/// The report consists of an index with the metrics of each file and backend, and one
+/// page per rendered PDF page showing the renders side by side, their differences from
+/// the consensus of all backends, and a slider to swipe or fade between any two of them.
+/// It only uses relative links and inline scripts, so it can be opened directly from disk.
+///
+/// Files can be added from multiple threads, and the index is written by [`HtmlReport::finish`].
+/// Adding a file again replaces its pages, so the report can be kept up to date while
+/// files change.
+pub struct HtmlReport {
+    dir: PathBuf,
+    options: HtmlReportOptions,
+    files: Mutex<Vec<FileSummary>>,
+}
+
+impl HtmlReport {
+    /// Create a report that will be written to the given directory.
+    pub fn new(dir: impl Into<PathBuf>, options: HtmlReportOptions) -> Result<Self, String> {
+        let dir = dir.into();
+        fs::create_dir_all(&dir).map_err(|e| format!("failed to create {}: {e}", dir.display()))?;
+
+        Ok(Self {
+            dir,
+            options,
+            files: Mutex::new(vec![]),
+        })
+    }
+
+    /// Write the pages of a file to the report and return its summary.
+    pub fn add_file(&self, file: &FileRun) -> Result<FileSummary, String> {
+        let slug = slug(&file.name);
+        let dir = self.dir.join("files").join(&slug);
+        // Remove the pages of a previous version of the file, which may have had more pages.
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).map_err(|e| format!("failed to create {}: {e}", dir.display()))?;
+
+        let page_count = file.page_count();
+        let mut consensus = vec![];
+
+        for page in 0..page_count {
+            // Every page up to the page count was rendered by at least one backend.
+            let Some(page_consensus) = file.consensus(page, &self.options.consensus) else {
+                break;
+            };
+
+            save_png(
+                &page_consensus.image,
+                &dir.join(format!("{page}-consensus.png")),
+            )?;
+
+            for (i, run) in file.backends.iter().enumerate() {
+                let Ok(pixmap) = file.page(i, page) else {
+                    continue;
+                };
+
+                let name = run.backend.name();
+                let diff =
+                    diff_pixmaps(pixmap, &page_consensus.image, &self.options.consensus.diff);
+                save_png(pixmap, &dir.join(format!("{page}-{name}.png")))?;
+                save_png(
+                    &diff.to_pixmap(pixmap),
+                    &dir.join(format!("{page}-{name}.diff.png")),
+                )?;
+            }
+
+            consensus.push(page_consensus);
+        }
+
+        let summary = FileSummary::new(file, &consensus);
+
+        for page in 0..summary.pages.len() {
+            let html = page_html(file, &summary, page);
+            write(&dir.join(format!("{page}.html")), &html)?;
+        }
+
+        let mut files = self.files.lock().unwrap();
+        files.retain(|f| f.name != summary.name);
+        files.push(summary.clone());
+
+        Ok(summary)
+    }
+
+    /// The summaries of all files added so far, sorted by name.
+    pub fn files(&self) -> Vec<FileSummary> {
+        let mut files = self.files.lock().unwrap().clone();
+        files.sort_by(|a, b| a.name.cmp(&b.name));
+        files
+    }
+
+    /// Write the index of the report with the files added so far and return its path.
+    pub fn write_index(&self) -> Result<PathBuf, String> {
+        let path = self.dir.join("index.html");
+        write(&path, &index_html(&self.files()))?;
+
+        Ok(path)
+    }
+
+    /// Write the index of the report and return its path.
+    pub fn finish(self) -> Result<PathBuf, String> {
+        self.write_index()
+    }
+}
+
+fn index_html(files: &[FileSummary]) -> String {
+    let mut backends: Vec<Backend> = vec![];
+
+    for backend in files
+        .iter()
+        .flat_map(|f| f.backends.iter().map(|b| b.backend.clone()))
+    {
+        if !backends.contains(&backend) {
+            backends.push(backend);
+        }
+    }
+
+    let failures = files
+        .iter()
+        .flat_map(|f| &f.backends)
+        .filter(|b| b.error.is_some())
+        .count();
+    let pages = files.iter().map(|f| f.pages.len()).sum::<usize>();
+
+    let mut html = header("sitro report");
+    let _ = writeln!(
+        html,
+        "<h1>sitro report</h1>\n<p>{} files, {pages} pages, {failures} failed renders</p>",
+        files.len()
+    );
+
+    html.push_str("<table>\n<thead><tr><th>File</th><th>Pages</th>");
+    for backend in &backends {
+        let _ = write!(
+            html,
+            "<th style=\"border-color: {}\">{}</th>",
+            css_color(backend),
+            backend.name()
+        );
+    }
+    html.push_str("</tr></thead>\n<tbody>\n");
+
+    for file in files {
+        let slug = slug(&file.name);
+        let class = if file.has_problems() { "problem" } else { "" };
+        let _ = write!(
+            html,
+            "<tr class=\"{class}\"><td>{}</td><td>",
+            escape(&file.name)
+        );
+
+        for (i, page) in file.pages.iter().enumerate() {
+            let class = if page.scores.iter().any(|s| s.outlier) {
+                "outlier"
+            } else {
+                ""
+            };
+            let _ = write!(
+                html,
+                "<a class=\"{class}\" href=\"files/{slug}/{i}.html\">{}</a> ",
+                file.first_page + i + 1
+            );
+        }
+        html.push_str("</td>");
+
+        for backend in &backends {
+            let Some(summary) = file.backends.iter().find(|b| b.backend == *backend) else {
+                html.push_str("<td></td>");
+                continue;
+            };
+
+            let time = format!("{} ms", summary.duration.as_millis());
+
+            if let Some(error) = &summary.error {
+                let _ = write!(
+                    html,
+                    "<td class=\"failed\" title=\"{}\">failed<small>{time}</small></td>",
+                    escape(error)
+                );
+                continue;
+            }
+
+            let distance = file.max_distance(backend).unwrap_or(0.0);
+            let outlier = file
+                .pages
+                .iter()
+                .flat_map(|p| &p.scores)
+                .any(|s| s.backend == *backend && s.outlier);
+            let missing = summary.pages.len() < file.pages.len();
+            let class = if outlier || missing { "outlier" } else { "ok" };
+
+            let _ = write!(
+                html,
+                "<td class=\"{class}\">{:.2}%<small>{} pages, {time}</small></td>",
+                distance * 100.0,
+                summary.pages.len()
+            );
+        }
+
+        html.push_str("</tr>\n");
+    }
+
+    html.push_str("</tbody>\n</table>\n<p class=\"hint\">The percentage is the largest fraction of pixels that differ from the consensus of all backends on any page.</p>\n");
+    html.push_str(FOOTER);
+    html
+}
+
+fn page_html(file: &FileRun, summary: &FileSummary, page: usize) -> String {
+    let page_count = summary.pages.len();
+    let number = file.first_page + page + 1;
+    let mut html = header(&format!("{} - page {number}", file.name));
+
+    let _ = write!(
+        html,
+        "<nav><a href=\"../../index.html\">index</a> &middot; <b>{}</b> &middot; page {number}",
+        escape(&file.name),
+    );
+    if page > 0 {
+        let _ = write!(html, " &middot; <a href=\"{}.html\">previous</a>", page - 1);
+    }
+    if page + 1 < page_count {
+        let _ = write!(html, " &middot; <a href=\"{}.html\">next</a>", page + 1);
+    }
+    html.push_str("</nav>\n");
+
+    // Side-by-side view of all backends.
+    html.push_str("<h2>Renders</h2>\n<label><input type=\"checkbox\" id=\"diffs\"> show differences from the consensus</label>\n<div class=\"tiles\">\n");
+
+    let mut rendered = vec![];
+
+    for (i, run) in file.backends.iter().enumerate() {
+        let name = run.backend.name();
+        let _ = write!(
+            html,
+            "<figure style=\"border-color: {}\"><figcaption style=\"background: {}\">{name}",
+            css_color(&run.backend),
+            css_color(&run.backend)
+        );
+
+        match file.page(i, page) {
+            Ok(_) => {
+                let score = summary.pages[page]
+                    .scores
+                    .iter()
+                    .find(|s| s.backend == run.backend);
+
+                if let Some(score) = score {
+                    let _ = write!(html, " &middot; {:.2}%", score.distance * 100.0);
+                    if score.outlier {
+                        html.push_str(" &middot; outlier");
+                    }
+                }
+
+                let _ = writeln!(
+                    html,
+                    "</figcaption><img src=\"{page}-{name}.png\" data-page=\"{page}-{name}.png\" data-diff=\"{page}-{name}.diff.png\" alt=\"{name}\"></figure>"
+                );
+                rendered.push(name);
+            }
+            Err(e) => {
+                let _ = writeln!(
+                    html,
+                    " &middot; failed</figcaption><div class=\"error\">{}</div></figure>",
+                    escape(&e)
+                );
+            }
+        }
+    }
+
+    html.push_str("</div>\n");
+
+    // Interactive comparison of two renders.
+    let options = std::iter::once(("consensus".to_string(), format!("{page}-consensus.png")))
+        .chain(
+            rendered
+                .iter()
+                .map(|name| (name.clone(), format!("{page}-{name}.png"))),
+        )
+        .collect::<Vec<_>>();
+
+    let select = |id: &str, selected: usize| {
+        let mut select = format!("<select id=\"{id}\">");
+        for (i, (name, src)) in options.iter().enumerate() {
+            let selected = if i == selected { " selected" } else { "" };
+            let _ = write!(select, "<option value=\"{src}\"{selected}>{name}</option>");
+        }
+        select.push_str("</select>");
+        select
+    };
+
+    let _ = write!(
+        html,
+        "<h2>Compare</h2>\n<div class=\"controls\">{} vs {} \
+         <select id=\"mode\"><option value=\"swipe\">swipe</option><option value=\"onion\">onion skin</option>\
+         <option value=\"difference\">difference</option></select> \
+         <input type=\"range\" id=\"slider\" min=\"0\" max=\"100\" value=\"50\"></div>\n\
+         <div class=\"stage\"><img id=\"a\" alt=\"\"><img id=\"b\" alt=\"\"></div>\n",
+        select("select-a", 0),
+        select("select-b", 1.min(options.len() - 1)),
+    );
+
+    html.push_str(SCRIPT);
+    html.push_str(FOOTER);
+    html
+}
+
+/// Write an HTML report of the changes between two runs to a directory and return the
+/// path of its index.
+///
+/// The changes are grouped by backend. Pages whose pixels changed are shown before and
+/// after the change, next to an image that highlights the changed pixels.
+pub fn write_change_report(
+    dir: impl Into<PathBuf>,
+    before: &StoredRun,
+    after: &StoredRun,
+    changes: &[Change],
+    options: &ChangeOptions,
+) -> Result<PathBuf, String> {
+    let dir = dir.into();
+    fs::create_dir_all(&dir).map_err(|e| format!("failed to create {}: {e}", dir.display()))?;
+
+    let mut html = header("Changes");
+
+    let _ = writeln!(
+        html,
+        "<h1>Changes</h1>\n<p class=\"hint\">from {} ({}) to {} ({})</p>",
+        escape(&before.dir().display().to_string()),
+        escape(&before.generator),
+        escape(&after.dir().display().to_string()),
+        escape(&after.generator)
+    );
+
+    if changes.is_empty() {
+        html.push_str("<p>No backend's output changed.</p>\n");
+    }
+
+    for (backend, group) in changes_by_backend(changes) {
+        let name = backend.name();
+        let color = css_color(&backend);
+        let backend_dir = dir.join(&name);
+        fs::create_dir_all(&backend_dir)
+            .map_err(|e| format!("failed to create {}: {e}", backend_dir.display()))?;
+
+        let _ = writeln!(
+            html,
+            "<h2 style=\"border-bottom: 4px solid {color}\">{name} &middot; {} change{}</h2>",
+            group.len(),
+            if group.len() == 1 { "" } else { "s" }
+        );
+
+        for change in group {
+            let _ = writeln!(html, "<h3>{}</h3>", escape(&change.to_string()));
+
+            let (Some(page), ChangeKind::Pixels { .. } | ChangeKind::Resized { .. }) =
+                (change.page, &change.kind)
+            else {
+                continue;
+            };
+
+            let (Some(old_file), Some(new_file)) =
+                (before.file(&change.file), after.file(&change.file))
+            else {
+                continue;
+            };
+
+            let old = before.page(old_file, &backend, page)?;
+            let new = after.page(new_file, &backend, page)?;
+            let stem = format!("{}-{page}", slug(&change.file));
+            let mut images = vec![("before", old.clone()), ("after", new.clone())];
+
+            if matches!(change.kind, ChangeKind::Pixels { .. }) {
+                images.push((
+                    "diff",
+                    diff_pixmaps(&old, &new, &options.diff).to_pixmap(&new),
+                ));
+            }
+
+            html.push_str("<div class=\"tiles\">\n");
+
+            for (label, pixmap) in images {
+                let file = format!("{stem}.{label}.png");
+                save_png(&pixmap, &backend_dir.join(&file))?;
+                let _ = writeln!(
+                    html,
+                    "<figure style=\"border-color: {color}\"><figcaption style=\"background: {color}\">{label}</figcaption><img src=\"{name}/{}\" alt=\"{label}\"></figure>",
+                    escape(&file)
+                );
+            }
+
+            html.push_str("</div>\n");
+        }
+    }
+
+    html.push_str(FOOTER);
+
+    let path = dir.join("index.html");
+    write(&path, &html)?;
+
+    Ok(path)
+}
+
+fn header(title: &str) -> String {
+    format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n<style>{STYLE}</style>\n</head>\n<body>\n",
+        escape(title)
+    )
+}
+
+const FOOTER: &str = "</body>\n</html>\n";
+
+const STYLE: &str = "
+body { font-family: sans-serif; margin: 2em; color: #222; }
+a { color: #0645ad; }
+table { border-collapse: collapse; }
+th, td { padding: 0.3em 0.6em; border: 1px solid #ddd; text-align: left; vertical-align: top; }
+th { border-bottom: 4px solid; }
+td small { display: block; color: #777; }
+tr.problem td:first-child { font-weight: bold; }
+td.ok { background: #eaf7e6; }
+td.outlier, a.outlier { background: #fdf1d8; }
+td.failed { background: #fbe3e3; color: #a00; }
+.hint { color: #777; }
+.tiles { display: flex; flex-wrap: wrap; gap: 1em; align-items: flex-start; }
+figure { margin: 0; border: 4px solid; }
+figcaption { padding: 0.2em 0.4em; color: #fff; text-shadow: 0 0 2px #000; }
+figure img { display: block; max-width: 40vw; }
+.error { width: 20em; padding: 1em; white-space: pre-wrap; background: repeating-linear-gradient(45deg, #f4f4f4, #f4f4f4 10px, #e4e4e4 10px, #e4e4e4 20px); }
+.controls { margin-bottom: 1em; }
+.stage { position: relative; display: inline-block; border: 1px solid #ddd; }
+.stage img { display: block; }
+.stage #b { position: absolute; top: 0; left: 0; }
+";
+
+const SCRIPT: &str = "<script>
+const a = document.getElementById('a');
+const b = document.getElementById('b');
+const selectA = document.getElementById('select-a');
+const selectB = document.getElementById('select-b');
+const mode = document.getElementById('mode');
+const slider = document.getElementById('slider');
+
+function update() {
+  a.src = selectA.value;
+  b.src = selectB.value;
+  const value = slider.value;
+  b.style.clipPath = mode.value === 'swipe' ? `inset(0 0 0 ${value}%)` : 'none';
+  b.style.opacity = mode.value === 'onion' ? value / 100 : 1;
+  b.style.mixBlendMode = mode.value === 'difference' ? 'difference' : 'normal';
+  slider.disabled = mode.value === 'difference';
+}
+
+for (const element of [selectA, selectB, mode, slider]) {
+  element.addEventListener('input', update);
+}
+
+document.getElementById('diffs').addEventListener('change', (event) => {
+  for (const img of document.querySelectorAll('figure img')) {
+    img.src = event.target.checked ? img.dataset.diff : img.dataset.page;
+  }
+});
+
+update();
+</script>
+";
+
+fn css_color(backend: &Backend) -> String {
+    let (r, g, b) = backend.color();
+    format!("rgb({r}, {g}, {b})")
+}
+
+/// Turn a file name into a name that can be used as a directory in the report.
+///
+/// Other characters than ASCII letters, digits, `-` and `.` are replaced by `_` and
+/// the hexadecimal value of their UTF-8 bytes, so different names get different slugs.
+/// A leading `.` is escaped as well, so that the slug is never `.` or `..`.
+pub(crate) fn slug(name: &str) -> String {
+    let mut slug = String::with_capacity(name.len());
+
+    for (i, c) in name.char_indices() {
+        match c {
+            'a'..='z' | 'A'..='Z' | '0'..='9' | '-' => slug.push(c),
+            '.' if i > 0 => slug.push(c),
+            _ => {
+                for byte in c.encode_utf8(&mut [0; 4]).bytes() {
+                    write!(slug, "_{byte:02x}").unwrap();
+                }
+            }
+        }
+    }
+
+    slug
+}
+
+fn save_png(pixmap: &tiny_skia::Pixmap, path: &Path) -> Result<(), String> {
+    pixmap
+        .save_png(path)
+        .map_err(|e| format!("failed to write {}: {e}", path.display()))
+}
+
+fn write(path: &Path, content: &str) -> Result<(), String> {
+    fs::write(path, content).map_err(|e| format!("failed to write {}: {e}", path.display()))
+}
@@ -0,0 +1,162 @@
+use super::{BackendSummary, FileSummary, PageSummary};
+use crate::json::Json;
+use crate::renderer::RenderOptions;
+use std::fs;
+use std::path::Path;
+
+/// The version of the schema of [`json_report`].
+///
+/// It is increased whenever a field is removed or changes its meaning, so tooling
+/// can reject reports it doesn't understand. Adding fields doesn't change the version.
+pub const JSON_REPORT_VERSION: u32 = 1;
+
+/// Create a machine-readable report of a corpus run.
+///
+/// The report is a JSON object with the following structure, where backends are
+/// identified by [`Backend::name`](crate::Backend::name), distances are fractions of
+/// differing pixels between 0.0 and 1.0 and similarities are one minus the distance:
+///
+/// ```text
+/// {
+///   "version": 1,
+///   "generator": "sitro <version>",
+///   "scale": 1.0,
+///   "files": [{
+///     "name": "dir/file.pdf",
+///     "first_page": 0,
+///     "page_count": 2,
+///     "backends": [{
+///       "backend": "pdfium",
+///       "status": "ok" | "failed",
+///       "error": null | "...",
+///       "warnings": ["..."],
+///       "render_time_ms": 120.5,
+///       "page_count": 2,
+///       "pages": [{ "width": 595, "height": 842 }]
+///     }],
+///     "pages": [{
+///       "index": 0,
+///       "size_discrepancy": { "width": 0, "height": 1 },
+///       "scores": [{ "backend": "pdfium", "distance": 0.0, "similarity": 1.0, "outlier": false }]
+///     }]
+///   }]
+/// }
+/// ```
+///
+/// Pages only list the backends that didn't fail. Backends that rendered fewer pages
+/// have a distance of 1.0 on the missing ones.
+pub fn json_report(files: &[FileSummary], options: &RenderOptions) -> String {
+    let report = Json::object([
+        ("version", JSON_REPORT_VERSION.into()),
+        (
+            "generator",
+            concat!("sitro ", env!("CARGO_PKG_VERSION")).into(),
+        ),
+        ("scale", options.scale.into()),
+        ("files", Json::Array(files.iter().map(file_json).collect())),
+    ]);
+
+    format!("{report}\n")
+}
+
+/// Write a machine-readable report of a corpus run to a file, see [`json_report`].
+pub fn write_json_report(
+    path: impl AsRef<Path>,
+    files: &[FileSummary],
+    options: &RenderOptions,
+) -> Result<(), String> {
+    let path = path.as_ref();
+    fs::write(path, json_report(files, options))
+        .map_err(|e| format!("failed to write {}: {e}", path.display()))
+}
+
+fn file_json(file: &FileSummary) -> Json {
+    Json::object([
+        ("name", file.name.as_str().into()),
+        ("first_page", file.first_page.into()),
+        ("page_count", file.pages.len().into()),
+        (
+            "backends",
+            Json::Array(file.backends.iter().map(backend_json).collect()),
+        ),
+        (
+            "pages",
+            Json::Array(
+                file.pages
+                    .iter()
+                    .enumerate()
+                    .map(|(i, page)| page_json(file.first_page + i, page))
+                    .collect(),
+            ),
+        ),
+    ])
+}
+
+fn backend_json(backend: &BackendSummary) -> Json {
+    let status = if backend.error.is_some() {
+        "failed"
+    } else {
+        "ok"
+    };
+
+    Json::object([
+        ("backend", backend.backend.name().into()),
+        ("status", status.into()),
+        ("error", backend.error.clone().into()),
+        ("warnings", backend.warnings.clone().into()),
+        (
+            "render_time_ms",
+            (backend.duration.as_secs_f64() * 1000.0).into(),
+        ),
+        ("page_count", backend.pages.len().into()),
+        (
+            "pages",
+            Json::Array(
+                backend
+                    .pages
+                    .iter()
+                    .map(|(width, height)| {
+                        Json::object([("width", (*width).into()), ("height", (*height).into())])
+                    })
+                    .collect(),
+            ),
+        ),
+    ])
+}
+
+fn page_json(index: usize, page: &PageSummary) -> Json {
+    let discrepancy = &page.size_discrepancy;
+
+    Json::object([
+        ("index", index.into()),
+        (
+            "size_discrepancy",
+            Json::object([
+                (
+                    "width",
+                    (discrepancy.max_width - discrepancy.min_width).into(),
+                ),
+                (
+                    "height",
+                    (discrepancy.max_height - discrepancy.min_height).into(),
+                ),
+            ]),
+        ),
+        (
+            "scores",
+            Json::Array(
+                page.scores
+                    .iter()
+                    .map(|score| {
+                        Json::object([
+                            ("backend", score.backend.name().into()),
+                            ("distance", score.distance.into()),
+                            ("similarity", (1.0 - score.distance).into()),
+                            ("outlier", score.outlier.into()),
+                        ])
+                    })
+                    .collect(),
+            ),
+        ),
+    ])
+}
@@ -0,0 +1,269 @@
+use super::{escape, BackendSummary, FileSummary};
+use crate::expectations::{Expectation, Expectations, KnownDivergence, Verdict};
+use crate::renderer::Backend;
+use std::fmt::Write;
+use std::fs;
+use std::path::Path;
+
+/// The options that should be applied when writing a JUnit report.
+#[derive(Clone, Debug)]
+pub struct JunitOptions {
+    /// By how much a page may differ from the consensus of all backends before its
+    /// test case fails, as a fraction of pixels between 0.0 and 1.0.
+    pub threshold: f32,
+    /// Thresholds that replace `threshold` for specific backends.
+    pub backend_thresholds: Vec<(Backend, f32)>,
+    /// Known divergences of backends. Their test cases are skipped instead of failing,
+    /// and fail if the backend no longer diverges.
+    pub expectations: Expectations,
+}
+
+impl Default for JunitOptions {
+    fn default() -> Self {
+        Self {
+            threshold: 0.01,
+            backend_thresholds: vec![],
+            expectations: Expectations::default(),
+        }
+    }
+}
+
+impl JunitOptions {
+    /// The threshold for the given backend.
+    pub fn threshold(&self, backend: &Backend) -> f32 {
+        self.backend_thresholds
+            .iter()
+            .find(|(b, _)| b == backend)
+            .map_or(self.threshold, |(_, threshold)| *threshold)
+    }
+}
+
+/// Create a JUnit XML report of a corpus run.
+///
+/// Each file is a test suite and each pair of backend and page a test case. A case
+/// fails if the backend failed to render the file, didn't produce the page, or
+/// differs from the consensus of all backends by more than the threshold. Cases of
+/// known divergences are skipped, and fail with the type `stale-expectation` if the
+/// backend no longer diverges. Errors and warnings of the backend are included as the
+/// output of each of its cases.
+pub fn junit_report(files: &[FileSummary], options: &JunitOptions) -> String {
+    let suites = files
+        .iter()
+        .map(|file| suite(file, options))
+        .collect::<Vec<_>>();
+
+    let tests = suites.iter().map(|s| s.tests).sum::<usize>();
+    let failures = suites.iter().map(|s| s.failures).sum::<usize>();
+    let skipped = suites.iter().map(|s| s.skipped).sum::<usize>();
+    let time = suites.iter().map(|s| s.time).sum::<f64>();
+
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    let _ = writeln!(
+        xml,
+        "<testsuites name=\"sitro\" tests=\"{tests}\" failures=\"{failures}\" errors=\"0\" skipped=\"{skipped}\" time=\"{time:.3}\">"
+    );
+
+    for suite in suites {
+        xml.push_str(&suite.xml);
+    }
+
+    xml.push_str("</testsuites>\n");
+    xml
+}
+
+/// Write a JUnit XML report of a corpus run to a file, see [`junit_report`].
+pub fn write_junit_report(
+    path: impl AsRef<Path>,
+    files: &[FileSummary],
+    options: &JunitOptions,
+) -> Result<(), String> {
+    let path = path.as_ref();
+    fs::write(path, junit_report(files, options))
+        .map_err(|e| format!("failed to write {}: {e}", path.display()))
+}
+
+struct Suite {
+    xml: String,
+    tests: usize,
+    failures: usize,
+    skipped: usize,
+    time: f64,
+}
+
+fn suite(file: &FileSummary, options: &JunitOptions) -> Suite {
+    let mut cases = String::new();
+    let mut tests = 0;
+    let mut failures = 0;
+    let mut skipped = 0;
+    let mut time = 0.0;
+    let name = escape(&file.name);
+
+    for backend in &file.backends {
+        let page_count = file.pages.len().max(1);
+        // The render time is only known for the whole file.
+        let case_time = backend.duration.as_secs_f64() / page_count as f64;
+        time += backend.duration.as_secs_f64();
+
+        for page in 0..page_count {
+            tests += 1;
+            let outcome = outcome(file, backend, page, options);
+
+            let _ = write!(
+                cases,
+                "    <testcase name=\"{} page {}\" classname=\"{name}\" time=\"{case_time:.3}\"",
+                backend.backend.name(),
+                file.first_page + page + 1
+            );
+
+            let output = output(backend);
+
+            if matches!(outcome, Outcome::Pass) && output.is_empty() {
+                cases.push_str("/>\n");
+                continue;
+            }
+
+            cases.push_str(">\n");
+
+            match outcome {
+                Outcome::Pass => {}
+                Outcome::Skipped(message) => {
+                    skipped += 1;
+                    let _ = writeln!(cases, "      <skipped message=\"{}\"/>", escape(&message));
+                }
+                Outcome::Failure(kind, message) => {
+                    failures += 1;
+                    let _ = writeln!(
+                        cases,
+                        "      <failure type=\"{kind}\" message=\"{}\"/>",
+                        escape(&message)
+                    );
+                }
+            }
+
+            if !output.is_empty() {
+                let _ = writeln!(cases, "      <system-out>{}</system-out>", escape(&output));
+            }
+
+            cases.push_str("    </testcase>\n");
+        }
+    }
+
+    let mut xml = String::new();
+    let _ = writeln!(
+        xml,
+        "  <testsuite name=\"{name}\" tests=\"{tests}\" failures=\"{failures}\" errors=\"0\" skipped=\"{skipped}\" time=\"{time:.3}\">"
+    );
+    xml.push_str(&cases);
+    xml.push_str("  </testsuite>\n");
+
+    Suite {
+        xml,
+        tests,
+        failures,
+        skipped,
+        time,
+    }
+}
+
+/// The outcome of a test case.
+enum Outcome {
+    Pass,
+    Skipped(String),
+    Failure(&'static str, String),
+}
+
+/// Judge a test case, taking known divergences into account.
+fn outcome(
+    file: &FileSummary,
+    backend: &BackendSummary,
+    page: usize,
+    options: &JunitOptions,
+) -> Outcome {
+    let expectations = &options.expectations;
+    let failed = backend.error.is_some();
+
+    let stale = match expectations.judge_render(&file.name, &backend.backend, failed) {
+        Verdict::Pass => None,
+        Verdict::Diverged => {
+            let error = backend.error.as_deref().unwrap_or_default();
+            return Outcome::Failure("render-error", format!("failed to render: {error}"));
+        }
+        Verdict::Expected(d) | Verdict::Skipped(d) => return Outcome::Skipped(d.to_string()),
+        Verdict::Stale(d) => Some(d),
+    };
+
+    let missing = page >= backend.pages.len();
+    let distance = if missing {
+        1.0
+    } else {
+        match file
+            .pages
+            .get(page)
+            .and_then(|p| p.scores.iter().find(|s| s.backend == backend.backend))
+        {
+            Some(score) => score.distance,
+            None => 0.0,
+        }
+    };
+
+    let index = file.first_page + page;
+    let threshold = options.threshold(&backend.backend);
+
+    let outcome =
+        match expectations.judge_page(&file.name, index, &backend.backend, distance, threshold) {
+            Verdict::Pass => Outcome::Pass,
+            Verdict::Expected(d) | Verdict::Skipped(d) => Outcome::Skipped(d.to_string()),
+            Verdict::Stale(d) => Outcome::Failure(
+                "stale-expectation",
+                format!("{d}, but only {:.2}% of pixels differ", distance * 100.0),
+            ),
+            Verdict::Diverged if missing => Outcome::Failure(
+                "missing-page",
+                format!(
+                    "only {} of {} pages were rendered",
+                    backend.pages.len(),
+                    file.pages.len()
+                ),
+            ),
+            Verdict::Diverged => {
+                let maximum = match expectations.find(&file.name, index, &backend.backend) {
+                    Some(KnownDivergence {
+                        expectation: Expectation::Difference(max),
+                        ..
+                    }) => *max,
+                    _ => threshold,
+                };
+
+                Outcome::Failure(
+                    "difference",
+                    format!(
+                        "{:.2}% of pixels differ from the consensus (maximum is {:.2}%)",
+                        distance * 100.0,
+                        maximum * 100.0
+                    ),
+                )
+            }
+        };
+
+    match (outcome, stale) {
+        (Outcome::Pass, Some(d)) => Outcome::Failure(
+            "stale-expectation",
+            format!("{d}, but it rendered the file"),
+        ),
+        (outcome, _) => outcome,
+    }
+}
+
+fn output(backend: &BackendSummary) -> String {
+    let mut output = String::new();
+
+    if let Some(error) = &backend.error {
+        let _ = writeln!(output, "error: {error}");
+    }
+
+    for warning in &backend.warnings {
+        let _ = writeln!(output, "warning: {warning}");
+    }
+
+    output
+}
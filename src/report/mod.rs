@@ -0,0 +1,279 @@
+mod changes;
+mod html;
+mod json;
+mod junit;
+
+pub use changes::*;
+pub use html::*;
+pub use json::*;
+pub use junit::*;
+
+use crate::consensus::{page_consensus, BackendScore, ConsensusOptions, PageConsensus};
+use crate::diff::{diff_pixmaps, DiffOptions};
+use crate::normalize::SizeDiscrepancy;
+use crate::renderer::{render_pages_to_pixmaps, Backend, RenderOptions, Renderer};
+use std::time::{Duration, Instant};
+use tiny_skia::Pixmap;
+
+/// The result of rendering a PDF with a single backend.
+pub struct BackendRun {
+    /// The backend.
+    pub backend: Backend,
+    /// The rendered pages, or the error if the backend failed.
+    pub result: Result<Vec<Pixmap>, String>,
+    /// The warnings the backend reported.
+    pub warnings: Vec<String>,
+    /// How long rendering took.
+    pub duration: Duration,
+}
+
+impl BackendRun {
+    /// Render a PDF with a backend, recording how long it took and what it reported.
+    pub fn render(
+        renderer: &Renderer,
+        backend: &Backend,
+        buf: &[u8],
+        options: &RenderOptions,
+    ) -> Self {
+        let start = Instant::now();
+        let (result, warnings) = match renderer.render_with_warnings(backend, buf, options) {
+            Ok((document, warnings)) => (
+                render_pages_to_pixmaps(&document, backend.color(), None),
+                warnings,
+            ),
+            Err(e) => (Err(e), vec![]),
+        };
+
+        Self {
+            backend: backend.clone(),
+            result,
+            warnings,
+            duration: start.elapsed(),
+        }
+    }
+
+    /// Describe how the render changed compared with an earlier render of the same
+    /// file with the same backend, like `page 2 changed (1.25% of pixels)`.
+    ///
+    /// `first_page` is the index of the first rendered page in the document.
+    pub fn describe_change(&self, before: Option<&BackendRun>, first_page: usize) -> String {
+        let pages = |count: usize| match count {
+            1 => "1 page".to_string(),
+            n => format!("{n} pages"),
+        };
+
+        let (old, new) = match (before.map(|b| &b.result), &self.result) {
+            (Some(Err(_)), Err(_)) => return "still failing".to_string(),
+            (_, Err(e)) => return format!("failed: {}", e.lines().next().unwrap_or_default()),
+            (None, Ok(new)) => return format!("rendered {}", pages(new.len())),
+            (Some(Err(_)), Ok(new)) => return format!("renders again, {}", pages(new.len())),
+            (Some(Ok(old)), Ok(new)) => (old, new),
+        };
+
+        let mut changes = vec![];
+
+        if old.len() != new.len() {
+            changes.push(format!("{} instead of {}", pages(new.len()), old.len()));
+        }
+
+        let options = DiffOptions::default();
+
+        for (i, (old, new)) in old.iter().zip(new).enumerate() {
+            let diff = diff_pixmaps(old, new, &options);
+
+            if !diff.is_identical() {
+                changes.push(format!(
+                    "page {} changed ({:.2}% of pixels)",
+                    first_page + i + 1,
+                    diff.ratio() * 100.0
+                ));
+            }
+        }
+
+        if changes.is_empty() {
+            "unchanged".to_string()
+        } else {
+            changes.join(", ")
+        }
+    }
+}
+
+/// The result of rendering a single PDF with multiple backends.
+pub struct FileRun {
+    /// The name of the file, usually its path relative to the input directory.
+    pub name: String,
+    /// The index of the first rendered page in the document, if only a range of
+    /// pages was kept.
+    pub first_page: usize,
+    /// The result of each backend.
+    pub backends: Vec<BackendRun>,
+}
+
+impl FileRun {
+    /// The largest number of pages any of the backends rendered.
+    pub fn page_count(&self) -> usize {
+        self.backends
+            .iter()
+            .filter_map(|run| run.result.as_ref().ok())
+            .map(|pages| pages.len())
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// Get a page rendered by the backend at the given index, or the reason why it's missing.
+    pub fn page(&self, backend: usize, page: usize) -> Result<&Pixmap, String> {
+        match &self.backends[backend].result {
+            Ok(pages) => pages.get(page).ok_or_else(|| {
+                format!(
+                    "only {} of {} pages were rendered",
+                    pages.len(),
+                    self.page_count()
+                )
+            }),
+            Err(e) => Err(e.clone()),
+        }
+    }
+
+    /// Build the consensus of a page across all backends that didn't fail.
+    ///
+    /// Backends that rendered fewer pages get a distance of 1.0. Returns `None` if
+    /// no backend rendered the page.
+    pub fn consensus(&self, page: usize, options: &ConsensusOptions) -> Option<PageConsensus> {
+        let pages = self
+            .backends
+            .iter()
+            .filter_map(|run| {
+                let pages = run.result.as_ref().ok()?;
+                Some((run.backend.clone(), pages.get(page)))
+            })
+            .collect::<Vec<_>>();
+
+        if pages.iter().all(|(_, pixmap)| pixmap.is_none()) {
+            return None;
+        }
+
+        Some(page_consensus(&pages, options))
+    }
+
+    /// Compare the backends on each page and summarize the run.
+    pub fn summarize(&self, options: &ConsensusOptions) -> FileSummary {
+        let consensus = (0..self.page_count())
+            .map_while(|page| self.consensus(page, options))
+            .collect::<Vec<_>>();
+
+        FileSummary::new(self, &consensus)
+    }
+}
+
+/// A summary of rendering a PDF with a single backend.
+#[derive(Clone, Debug)]
+pub struct BackendSummary {
+    /// The backend.
+    pub backend: Backend,
+    /// The size of each rendered page in pixels.
+    pub pages: Vec<(u32, u32)>,
+    /// The error if the backend failed.
+    pub error: Option<String>,
+    /// The warnings the backend reported.
+    pub warnings: Vec<String>,
+    /// How long rendering took.
+    pub duration: Duration,
+}
+
+/// A summary of how the backends compare on a single page.
+#[derive(Clone, Debug)]
+pub struct PageSummary {
+    /// The score of each backend that didn't fail, compared to the consensus.
+    pub scores: Vec<BackendScore>,
+    /// How much the page sizes of the backends differed.
+    pub size_discrepancy: SizeDiscrepancy,
+}
+
+/// A summary of rendering a single PDF with multiple backends, without the images.
+#[derive(Clone, Debug)]
+pub struct FileSummary {
+    /// The name of the file.
+    pub name: String,
+    /// The index of the first summarized page in the document.
+    pub first_page: usize,
+    /// The summary of each backend.
+    pub backends: Vec<BackendSummary>,
+    /// The summary of each page.
+    pub pages: Vec<PageSummary>,
+}
+
+impl FileSummary {
+    /// Summarize a run, given the consensus of each of its pages.
+    pub fn new(file: &FileRun, consensus: &[PageConsensus]) -> Self {
+        let backends = file
+            .backends
+            .iter()
+            .map(|run| BackendSummary {
+                backend: run.backend.clone(),
+                pages: run
+                    .result
+                    .as_ref()
+                    .map(|pages| pages.iter().map(|p| (p.width(), p.height())).collect())
+                    .unwrap_or_default(),
+                error: run.result.as_ref().err().cloned(),
+                warnings: run.warnings.clone(),
+                duration: run.duration,
+            })
+            .collect();
+
+        let pages = consensus
+            .iter()
+            .map(|page| PageSummary {
+                scores: page.scores.clone(),
+                size_discrepancy: page.size_discrepancy,
+            })
+            .collect();
+
+        Self {
+            name: file.name.clone(),
+            first_page: file.first_page,
+            backends,
+            pages,
+        }
+    }
+
+    /// The largest distance of a backend from the consensus across all pages.
+    pub fn max_distance(&self, backend: &Backend) -> Option<f32> {
+        self.pages
+            .iter()
+            .flat_map(|page| &page.scores)
+            .filter(|score| score.backend == *backend)
+            .map(|score| score.distance)
+            .reduce(f32::max)
+    }
+
+    /// Whether any backend failed or was an outlier on any page.
+    pub fn has_problems(&self) -> bool {
+        self.backends.iter().any(|b| b.error.is_some())
+            || self
+                .pages
+                .iter()
+                .any(|page| page.scores.iter().any(|s| s.outlier))
+    }
+}
+
+/// Escape text for HTML and XML, in element content and in quoted attributes.
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            '\n' | '\t' => escaped.push(c),
+            // Other control characters are not allowed in XML 1.0.
+            c if (c as u32) < 0x20 => escaped.push('\u{FFFD}'),
+            c => escaped.push(c),
+        }
+    }
+
+    escaped
+}
@@ -0,0 +1,223 @@
+use crate::json::Json;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::TcpStream;
+
+/// The maximum size of the request line and headers.
+const MAX_HEADER_SIZE: u64 = 64 * 1024;
+
+/// An HTTP request.
+pub(crate) struct Request {
+    pub method: String,
+    pub path: String,
+    pub query: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+impl Request {
+    /// Read a request from a connection, rejecting bodies larger than `max_body_size`.
+    pub fn read(stream: &TcpStream, max_body_size: usize) -> Result<Self, Response> {
+        let bad_request = |message: &str| Response::error(400, message);
+        let mut reader = BufReader::new(stream);
+        let mut head = (&mut reader).take(MAX_HEADER_SIZE);
+
+        let mut request_line = String::new();
+        head.read_line(&mut request_line)
+            .map_err(|_| bad_request("failed to read the request"))?;
+
+        let mut parts = request_line.split_whitespace();
+        let (Some(method), Some(target), Some(_version)) =
+            (parts.next(), parts.next(), parts.next())
+        else {
+            return Err(bad_request("malformed request line"));
+        };
+
+        let (path, query) = target.split_once('?').unwrap_or((target, ""));
+        let mut content_length = 0;
+        let mut expect_continue = false;
+        let mut line = String::new();
+
+        loop {
+            line.clear();
+            if head.read_line(&mut line).unwrap_or(0) == 0 {
+                return Err(bad_request("incomplete request headers"));
+            }
+
+            let header = line.trim_end();
+            if header.is_empty() {
+                break;
+            }
+
+            let Some((name, value)) = header.split_once(':') else {
+                return Err(bad_request("malformed header"));
+            };
+            let value = value.trim();
+
+            if name.eq_ignore_ascii_case("content-length") {
+                content_length = value
+                    .parse::<usize>()
+                    .map_err(|_| bad_request("invalid content length"))?;
+            } else if name.eq_ignore_ascii_case("expect") {
+                expect_continue = value.eq_ignore_ascii_case("100-continue");
+            } else if name.eq_ignore_ascii_case("transfer-encoding") {
+                return Err(Response::error(411, "chunked requests are not supported"));
+            }
+        }
+
+        if content_length > max_body_size {
+            return Err(Response::error(
+                413,
+                &format!("the request body is larger than {max_body_size} bytes"),
+            ));
+        }
+
+        if expect_continue {
+            let _ = (&*stream).write_all(b"HTTP/1.1 100 Continue\r\n\r\n");
+        }
+
+        let mut body = vec![0; content_length];
+        reader
+            .read_exact(&mut body)
+            .map_err(|_| bad_request("incomplete request body"))?;
+
+        Ok(Self {
+            method: method.to_string(),
+            path: percent_decode(path),
+            query: query
+                .split('&')
+                .filter(|pair| !pair.is_empty())
+                .map(|pair| {
+                    let (name, value) = pair.split_once('=').unwrap_or((pair, ""));
+                    (
+                        percent_decode(&name.replace('+', " ")),
+                        percent_decode(&value.replace('+', " ")),
+                    )
+                })
+                .collect(),
+            body,
+        })
+    }
+
+    /// The value of a query parameter.
+    pub fn param(&self, name: &str) -> Option<&str> {
+        self.query
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+/// An HTTP response.
+pub(crate) struct Response {
+    pub status: u16,
+    pub content_type: &'static str,
+    pub body: Vec<u8>,
+}
+
+impl Response {
+    pub fn html(html: &str) -> Self {
+        Self {
+            status: 200,
+            content_type: "text/html; charset=utf-8",
+            body: html.as_bytes().to_vec(),
+        }
+    }
+
+    pub fn json(status: u16, json: &Json) -> Self {
+        Self {
+            status,
+            content_type: "application/json",
+            body: format!("{json}\n").into_bytes(),
+        }
+    }
+
+    pub fn png(png: Vec<u8>) -> Self {
+        Self {
+            status: 200,
+            content_type: "image/png",
+            body: png,
+        }
+    }
+
+    /// A JSON object with an error message.
+    pub fn error(status: u16, message: &str) -> Self {
+        Self::json(status, &Json::object([("error", message.into())]))
+    }
+
+    pub fn write_to(&self, mut writer: impl Write) -> io::Result<()> {
+        write!(
+            writer,
+            "HTTP/1.1 {} {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nCache-Control: no-store\r\nConnection: close\r\n\r\n",
+            self.status,
+            reason(self.status),
+            self.content_type,
+            self.body.len()
+        )?;
+        writer.write_all(&self.body)?;
+        writer.flush()
+    }
+}
+
+fn reason(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        400 => "Bad Request",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        411 => "Length Required",
+        413 => "Payload Too Large",
+        422 => "Unprocessable Entity",
+        503 => "Service Unavailable",
+        _ => "Internal Server Error",
+    }
+}
+
+/// Decode `%XX` escapes, keeping malformed ones as they are.
+fn percent_decode(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+
+    while i < bytes.len() {
+        let hex = bytes
+            .get(i + 1..i + 3)
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+
+        match (bytes[i], hex) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            (byte, _) => {
+                decoded.push(byte);
+                i += 1;
+            }
+        }
+    }
+
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+/// Encode bytes as standard base64 with padding.
+pub(crate) fn base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+    let mut encoded = String::with_capacity(bytes.len().div_ceil(3) * 4);
+
+    for chunk in bytes.chunks(3) {
+        let n = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |n, (i, &byte)| n | (byte as u32) << (16 - 8 * i));
+
+        for i in 0..4 {
+            if i <= chunk.len() {
+                encoded.push(ALPHABET[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+
+    encoded
+}
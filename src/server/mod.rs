@@ -0,0 +1,247 @@
+mod http;
+mod page;
+
+use crate::json::Json;
+use crate::renderer::{Backend, RenderOptions, RENDER_INSTANCE};
+use http::{base64, Request, Response};
+use std::io::{self, Read};
+use std::net::{Shutdown, TcpListener, TcpStream};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// The options of the interactive comparison server.
+#[derive(Copy, Clone, Debug)]
+pub struct ServerOptions {
+    /// The maximum size of an uploaded PDF in bytes.
+    pub max_upload_size: usize,
+    /// The maximum scale pages can be rendered at.
+    pub max_scale: f32,
+}
+
+impl Default for ServerOptions {
+    fn default() -> Self {
+        Self {
+            max_upload_size: 256 * 1024 * 1024,
+            max_scale: 10.0,
+        }
+    }
+}
+
+/// Serve the interactive comparison page and the render API until the listener fails.
+///
+/// The page at `/` lets you upload a PDF, pick backends, a scale and a page, and view
+/// the renders side by side with zoom, a swipe and onion-skin slider and a pixel diff.
+/// All renders go through [`RENDER_INSTANCE`], so they benefit from its cache.
+///
+/// The API consists of two endpoints:
+///
+/// - `GET /backends` returns the available backends as
+///   `[{ "name": "pdfium", "color": "#4fb823" }]`.
+/// - `POST /render?backend=<name>[&scale=<scale>][&page=<page>][&format=json|png]` renders
+///   the PDF in the request body. Pages are numbered from 1 and all pages are rendered if
+///   none is given. The JSON response has the following structure, with PNGs encoded as
+///   base64; with `format=png` and a page, the PNG of that page is returned directly.
+///
+/// ```text
+/// {
+///   "backend": "pdfium",
+///   "scale": 1.0,
+///   "page_count": 3,
+///   "render_time_ms": 120.5,
+///   "warnings": ["..."],
+///   "pages": [{ "index": 0, "width": 595, "height": 842, "png": "iVBORw0KGgo..." }]
+/// }
+/// ```
+///
+/// Errors are returned as `{ "error": "..." }` with status 400 for invalid requests,
+/// 422 if the backend failed to render the PDF and 503 if the renderer couldn't be set up.
+///
+/// Each connection is handled in its own thread. The listener should be bound to a local
+/// address, as the server has no authentication.
+pub fn serve(listener: TcpListener, options: &ServerOptions) -> Result<(), String> {
+    for stream in listener.incoming() {
+        let stream = stream.map_err(|e| format!("failed to accept connection: {e}"))?;
+        let options = *options;
+        thread::spawn(move || handle(stream, &options));
+    }
+
+    Ok(())
+}
+
+fn handle(stream: TcpStream, options: &ServerOptions) {
+    let response = match Request::read(&stream, options.max_upload_size) {
+        Ok(request) => route(&request, options),
+        Err(response) => response,
+    };
+
+    let _ = response.write_to(&stream);
+
+    // Read what the client still sends, like the body of a rejected upload, before
+    // closing the connection. Otherwise, the client may get a reset instead of the response.
+    let _ = stream.shutdown(Shutdown::Write);
+    let _ = stream.set_read_timeout(Some(Duration::from_secs(1)));
+    let _ = io::copy(
+        &mut (&stream).take(options.max_upload_size as u64),
+        &mut io::sink(),
+    );
+}
+
+fn route(request: &Request, options: &ServerOptions) -> Response {
+    match (request.method.as_str(), request.path.as_str()) {
+        ("GET", "/") => Response::html(page::PAGE),
+        ("GET", "/backends") => backends(),
+        ("POST", "/render") => render(request, options).unwrap_or_else(|error| error),
+        (_, "/" | "/backends" | "/render") => Response::error(405, "method not allowed"),
+        _ => Response::error(404, "not found"),
+    }
+}
+
+fn backends() -> Response {
+    let mut backends: Vec<_> = Backend::ALL
+        .into_iter()
+        .filter(Backend::is_available)
+        .collect();
+
+    // Backends in the additional Docker images of the renderer are listed as well.
+    if let Ok(renderer) = RENDER_INSTANCE.as_ref() {
+        for label in renderer.image_labels() {
+            backends.extend(Backend::ALL.iter().filter_map(|b| b.in_image(label)));
+        }
+    }
+
+    Backend::sort(&mut backends);
+
+    let backends = backends
+        .into_iter()
+        .map(|backend| {
+            let (r, g, b) = backend.color();
+            Json::object([
+                ("name", backend.name().into()),
+                ("color", format!("#{r:02x}{g:02x}{b:02x}").into()),
+            ])
+        })
+        .collect();
+
+    Response::json(200, &Json::Array(backends))
+}
+
+fn render(request: &Request, options: &ServerOptions) -> Result<Response, Response> {
+    let bad_request = |message: String| Response::error(400, &message);
+
+    let name = request
+        .param("backend")
+        .ok_or_else(|| bad_request("missing `backend` parameter".to_string()))?;
+    let unknown = || bad_request(format!("unknown or unavailable backend `{name}`"));
+
+    // Backends in images the renderer doesn't run are rejected like unknown backends,
+    // instead of failing to render.
+    if let Some((_, label)) = name.split_once('@') {
+        let known = RENDER_INSTANCE
+            .as_ref()
+            .is_ok_and(|renderer| renderer.image_labels().contains(&label));
+
+        if !known {
+            return Err(unknown());
+        }
+    }
+
+    let backend = Backend::from_name(name)
+        .filter(Backend::is_available)
+        .ok_or_else(unknown)?;
+
+    let scale = match request.param("scale") {
+        Some(scale) => scale
+            .parse::<f32>()
+            .ok()
+            .filter(|scale| *scale > 0.0 && *scale <= options.max_scale)
+            .ok_or_else(|| {
+                bad_request(format!(
+                    "`scale` must be a number greater than 0 and at most {}",
+                    options.max_scale
+                ))
+            })?,
+        None => 1.0,
+    };
+
+    let page = match request.param("page") {
+        Some(page) => Some(
+            page.parse::<usize>()
+                .ok()
+                .filter(|page| *page >= 1)
+                .ok_or_else(|| bad_request("`page` must be a number from 1".to_string()))?,
+        ),
+        None => None,
+    };
+
+    let png = match request.param("format").unwrap_or("json") {
+        "json" => false,
+        "png" if page.is_some() => true,
+        "png" => return Err(bad_request("`format=png` requires a `page`".to_string())),
+        format => return Err(bad_request(format!("unknown format `{format}`"))),
+    };
+
+    if request.body.is_empty() {
+        return Err(bad_request(
+            "expected a PDF in the request body".to_string(),
+        ));
+    }
+
+    let renderer = RENDER_INSTANCE
+        .as_ref()
+        .map_err(|e| Response::error(503, &format!("failed to set up the renderer: {e}")))?;
+
+    let start = Instant::now();
+    let (mut document, warnings) = renderer
+        .render_with_warnings(&backend, &request.body, &RenderOptions { scale })
+        .map_err(|e| Response::error(422, &e))?;
+    let duration = start.elapsed();
+    let page_count = document.len();
+
+    let pages = match page {
+        Some(page) if page > page_count => {
+            return Err(Response::error(
+                422,
+                &format!("page {page} doesn't exist, the document has {page_count} pages"),
+            ))
+        }
+        Some(page) => vec![(page - 1, document.swap_remove(page - 1))],
+        None => document.into_iter().enumerate().collect(),
+    };
+
+    if png {
+        return Ok(Response::png(pages.into_iter().next().unwrap().1));
+    }
+
+    let pages = pages
+        .into_iter()
+        .map(|(index, png)| {
+            let (width, height) = png_size(&png).unwrap_or_default();
+            Json::object([
+                ("index", index.into()),
+                ("width", width.into()),
+                ("height", height.into()),
+                ("png", base64(&png).into()),
+            ])
+        })
+        .collect();
+
+    Ok(Response::json(
+        200,
+        &Json::object([
+            ("backend", backend.name().into()),
+            ("scale", scale.into()),
+            ("page_count", page_count.into()),
+            ("render_time_ms", (duration.as_secs_f64() * 1000.0).into()),
+            ("warnings", warnings.into()),
+            ("pages", Json::Array(pages)),
+        ]),
+    ))
+}
+
+/// The size of a PNG image, read from its header.
+fn png_size(png: &[u8]) -> Option<(u32, u32)> {
+    let header = png.get(16..24)?;
+    let width = u32::from_be_bytes(header[..4].try_into().ok()?);
+    let height = u32::from_be_bytes(header[4..].try_into().ok()?);
+    Some((width, height))
+}
@@ -0,0 +1,218 @@
+/// The interactive comparison page served at `/`.
+pub(crate) const PAGE: &str = r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<title>sitro</title>
+<style>
+body { font-family: sans-serif; margin: 2em; color: #222; }
+header { display: flex; flex-wrap: wrap; gap: 1em; align-items: center; }
+#drop { padding: 1em; border: 2px dashed #aaa; border-radius: 4px; }
+#drop.over { border-color: #0645ad; background: #eef3fb; }
+#backends label { margin-right: 0.6em; padding: 0.1em 0.3em; border-bottom: 4px solid; }
+input[type=number] { width: 4em; }
+#status { color: #777; }
+.controls { margin-bottom: 1em; }
+.tiles { display: flex; flex-wrap: wrap; gap: 1em; align-items: flex-start; }
+figure { margin: 0; border: 4px solid; }
+figcaption { padding: 0.2em 0.4em; color: #fff; text-shadow: 0 0 2px #000; }
+figure img, .stage img, .stage canvas { display: block; }
+.zoomed img, .zoomed canvas { image-rendering: pixelated; }
+.error { width: 20em; padding: 1em; white-space: pre-wrap; background: repeating-linear-gradient(45deg, #f4f4f4, #f4f4f4 10px, #e4e4e4 10px, #e4e4e4 20px); }
+.stage { position: relative; display: inline-block; border: 1px solid #ddd; }
+.stage #b { position: absolute; top: 0; left: 0; }
+.stage canvas { position: absolute; top: 0; left: 0; }
+</style>
+</head>
+<body>
+<header>
+<label id="drop">Drop a PDF here or <input type="file" id="file" accept="application/pdf,.pdf"></label>
+<span id="backends"></span>
+<label>scale <input type="number" id="scale" value="1" min="0.1" max="10" step="0.1"></label>
+<label>page <input type="number" id="page" value="1" min="1"></label>
+<button id="render">render</button>
+<span id="status"></span>
+</header>
+<h2>Renders</h2>
+<div class="controls">zoom <input type="range" id="zoom" min="10" max="400" value="100"> <span id="zoom-value">100%</span></div>
+<div class="tiles" id="tiles"></div>
+<h2>Compare</h2>
+<div class="controls"><select id="select-a"></select> vs <select id="select-b"></select>
+<select id="mode"><option value="swipe">swipe</option><option value="onion">onion skin</option><option value="diff">diff</option></select>
+<input type="range" id="slider" min="0" max="100" value="50"> <span id="diff-info"></span></div>
+<div class="stage" id="stage"><img id="a" alt=""><img id="b" alt=""><canvas id="diff" hidden></canvas></div>
+<script>
+const $ = (id) => document.getElementById(id);
+const renders = new Map();
+let pdf = null;
+
+fetch('/backends').then((response) => response.json()).then((backends) => {
+  for (const backend of backends) {
+    const label = document.createElement('label');
+    label.style.borderColor = backend.color;
+    label.innerHTML = `<input type="checkbox" value="${backend.name}" data-color="${backend.color}" checked> ${backend.name}`;
+    $('backends').append(label);
+  }
+});
+
+function choose(file) {
+  pdf = file;
+  $('status').textContent = file.name;
+  render();
+}
+
+$('file').addEventListener('change', (event) => choose(event.target.files[0]));
+$('drop').addEventListener('dragover', (event) => { event.preventDefault(); $('drop').classList.add('over'); });
+$('drop').addEventListener('dragleave', () => $('drop').classList.remove('over'));
+$('drop').addEventListener('drop', (event) => {
+  event.preventDefault();
+  $('drop').classList.remove('over');
+  if (event.dataTransfer.files.length) choose(event.dataTransfer.files[0]);
+});
+$('render').addEventListener('click', render);
+
+async function render() {
+  if (!pdf) {
+    $('status').textContent = 'choose a PDF first';
+    return;
+  }
+
+  const body = await pdf.arrayBuffer();
+  const selected = [...document.querySelectorAll('#backends input:checked')];
+  $('tiles').innerHTML = '';
+  renders.clear();
+  $('status').textContent = `rendering ${pdf.name} with ${selected.length} backends...`;
+
+  await Promise.all(selected.map(async (input) => {
+    const name = input.value;
+    const figure = document.createElement('figure');
+    figure.style.borderColor = input.dataset.color;
+    figure.innerHTML = `<figcaption style="background: ${input.dataset.color}">${name} &middot; rendering...</figcaption>`;
+    $('tiles').append(figure);
+
+    const params = new URLSearchParams({ backend: name, scale: $('scale').value, page: $('page').value });
+
+    try {
+      const response = await fetch('/render?' + params, { method: 'POST', body });
+      const result = await response.json();
+      if (!response.ok) throw new Error(result.error);
+
+      const page = result.pages[0];
+      const src = 'data:image/png;base64,' + page.png;
+      figure.firstChild.textContent = `${name} · ${Math.round(result.render_time_ms)} ms · ${result.page_count} pages`;
+      const img = document.createElement('img');
+      img.src = src;
+      img.dataset.width = page.width;
+      img.title = result.warnings.join('\n');
+      figure.append(img);
+      renders.set(name, src);
+    } catch (error) {
+      figure.firstChild.textContent = `${name} · failed`;
+      const div = document.createElement('div');
+      div.className = 'error';
+      div.textContent = error.message;
+      figure.append(div);
+    }
+  }));
+
+  $('status').textContent = `rendered ${pdf.name}`;
+
+  for (const [select, index] of [[$('select-a'), 0], [$('select-b'), 1]]) {
+    select.innerHTML = '';
+    for (const name of renders.keys()) select.add(new Option(name, name));
+    select.selectedIndex = Math.min(index, renders.size - 1);
+  }
+
+  zoom();
+  compare();
+}
+
+function zoom() {
+  const factor = $('zoom').value / 100;
+  $('zoom-value').textContent = `${$('zoom').value}%`;
+  document.body.classList.toggle('zoomed', factor > 1);
+  for (const element of document.querySelectorAll('figure img, .stage img, .stage canvas')) {
+    const width = element.dataset.width || element.naturalWidth || element.width;
+    if (width) element.style.width = `${width * factor}px`;
+  }
+}
+
+function load(src) {
+  return new Promise((resolve) => {
+    const img = new Image();
+    img.onload = () => resolve(img);
+    img.src = src;
+  });
+}
+
+function pixels(img, width, height) {
+  const canvas = document.createElement('canvas');
+  canvas.width = width;
+  canvas.height = height;
+  const context = canvas.getContext('2d');
+  context.fillStyle = '#fff';
+  context.fillRect(0, 0, width, height);
+  context.drawImage(img, 0, 0);
+  return context.getImageData(0, 0, width, height).data;
+}
+
+async function compare() {
+  const a = renders.get($('select-a').value);
+  const b = renders.get($('select-b').value);
+  const mode = $('mode').value;
+  const value = $('slider').value;
+
+  $('a').src = a || '';
+  $('b').src = b || '';
+  $('b').hidden = mode === 'diff';
+  $('b').style.clipPath = mode === 'swipe' ? `inset(0 0 0 ${value}%)` : 'none';
+  $('b').style.opacity = mode === 'onion' ? value / 100 : 1;
+  $('slider').disabled = mode === 'diff';
+  $('diff').hidden = mode !== 'diff';
+  $('diff-info').textContent = '';
+
+  if (mode !== 'diff' || !a || !b) return;
+
+  const [imgA, imgB] = await Promise.all([load(a), load(b)]);
+  const width = Math.max(imgA.naturalWidth, imgB.naturalWidth);
+  const height = Math.max(imgA.naturalHeight, imgB.naturalHeight);
+  const pixelsA = pixels(imgA, width, height);
+  const pixelsB = pixels(imgB, width, height);
+
+  const canvas = $('diff');
+  canvas.width = width;
+  canvas.height = height;
+  const context = canvas.getContext('2d');
+  const output = context.createImageData(width, height);
+  let differing = 0;
+
+  for (let i = 0; i < pixelsA.length; i += 4) {
+    const delta = Math.max(
+      Math.abs(pixelsA[i] - pixelsB[i]),
+      Math.abs(pixelsA[i + 1] - pixelsB[i + 1]),
+      Math.abs(pixelsA[i + 2] - pixelsB[i + 2]),
+    );
+
+    if (delta > 8) {
+      differing++;
+      output.data.set([255, 0, 0, 255], i);
+    } else {
+      // Show the page faded, so that the differences stand out.
+      const gray = 192 + (pixelsA[i] + pixelsA[i + 1] + pixelsA[i + 2]) / 12;
+      output.data.set([gray, gray, gray, 255], i);
+    }
+  }
+
+  context.putImageData(output, 0, 0);
+  $('diff-info').textContent = `${(differing / (width * height) * 100).toFixed(2)}% of pixels differ`;
+  zoom();
+}
+
+$('zoom').addEventListener('input', zoom);
+for (const id of ['select-a', 'select-b', 'mode', 'slider']) {
+  $(id).addEventListener('input', compare);
+}
+</script>
+</body>
+</html>
+"#;
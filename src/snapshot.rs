@@ -0,0 +1,232 @@
+use crate::diff::{diff_pixmaps, diff_pixmaps_masked, DiffOptions};
+use crate::mask::{page_mask, IgnoreRegion};
+use crate::normalize::{normalize, SizePolicy};
+use crate::renderer::{Backend, RenderOptions, RenderedPage, Renderer};
+use std::path::{Path, PathBuf};
+use std::{env, fs};
+use tiny_skia::Pixmap;
+
+/// The options that should be applied when checking a PDF against its snapshots.
+#[derive(Clone)]
+pub struct SnapshotOptions {
+    /// The directory the reference images are stored in.
+    pub dir: PathBuf,
+    /// The options used for rendering the PDF.
+    pub render: RenderOptions,
+    /// How a page should be brought to the size of its reference image if the sizes differ.
+    pub size_policy: SizePolicy,
+    /// The options used for comparing a page with its reference image.
+    pub diff: DiffOptions,
+    /// The maximum fraction of pixels that may differ from the reference image.
+    pub max_ratio: f32,
+    /// Areas that should be excluded from the comparison.
+    pub ignore: Vec<IgnoreRegion>,
+    /// Whether the reference images should be replaced with the new renders.
+    ///
+    /// Defaults to `true` if the `SITRO_UPDATE` environment variable is set to `1`.
+    pub update: bool,
+}
+
+impl Default for SnapshotOptions {
+    fn default() -> Self {
+        Self {
+            dir: PathBuf::from("tests/snapshots"),
+            render: RenderOptions::default(),
+            size_policy: SizePolicy::Pad,
+            diff: DiffOptions::default(),
+            max_ratio: 0.0,
+            ignore: vec![],
+            update: env::var("SITRO_UPDATE").is_ok_and(|v| v == "1"),
+        }
+    }
+}
+
+impl Renderer {
+    /// Render a PDF and compare each page with its reference image.
+    ///
+    /// See [`compare_snapshots`] for details.
+    pub fn check_snapshot(
+        &self,
+        name: &str,
+        backend: &Backend,
+        buf: &[u8],
+        options: &SnapshotOptions,
+    ) -> Result<(), String> {
+        let pages = self
+            .render(backend, buf, &options.render)
+            .map_err(|e| format!("failed to render with {}: {e}", backend.name()))?;
+
+        compare_snapshots(name, backend, &pages, options)
+    }
+}
+
+/// Compare rendered pages with their reference images.
+///
+/// The reference image of each page is stored as `<name>-<backend>-<page>.png` in the
+/// snapshot directory. If a page differs from its reference image, a diff image is written
+/// to `<name>-<backend>-<page>.diff.png` and an error describing all failing pages is returned.
+///
+/// If [`SnapshotOptions::update`] is set, the reference images are replaced instead.
+pub fn compare_snapshots(
+    name: &str,
+    backend: &Backend,
+    pages: &[RenderedPage],
+    options: &SnapshotOptions,
+) -> Result<(), String> {
+    let path = |page: usize, suffix: &str| {
+        options
+            .dir
+            .join(format!("{name}-{}-{page}{suffix}.png", backend.name()))
+    };
+
+    if options.update {
+        fs::create_dir_all(&options.dir)
+            .map_err(|e| format!("failed to create snapshot directory: {e}"))?;
+
+        for (i, page) in pages.iter().enumerate() {
+            fs::write(path(i, ""), page)
+                .map_err(|e| format!("failed to write snapshot for page {i}: {e}"))?;
+            let _ = fs::remove_file(path(i, ".diff"));
+        }
+
+        // Remove the snapshots of pages that don't exist anymore.
+        let mut i = pages.len();
+        while path(i, "").exists() {
+            let _ = fs::remove_file(path(i, ""));
+            let _ = fs::remove_file(path(i, ".diff"));
+            i += 1;
+        }
+
+        return Ok(());
+    }
+
+    let mut failures = vec![];
+
+    for (i, page) in pages.iter().enumerate() {
+        let reference_path = path(i, "");
+        let diff_path = path(i, ".diff");
+
+        if let Err(e) = compare_page(page, i, &reference_path, &diff_path, options) {
+            failures.push(format!("page {i}: {e}"));
+        }
+    }
+
+    if path(pages.len(), "").exists() {
+        failures.push(format!(
+            "rendered {} page(s), but there are snapshots for more pages",
+            pages.len()
+        ));
+    }
+
+    if failures.is_empty() {
+        Ok(())
+    } else {
+        failures.push("run with SITRO_UPDATE=1 to accept the new renders".to_string());
+        Err(failures.join("\n"))
+    }
+}
+
+fn compare_page(
+    page: &[u8],
+    index: usize,
+    reference_path: &Path,
+    diff_path: &Path,
+    options: &SnapshotOptions,
+) -> Result<(), String> {
+    if !reference_path.exists() {
+        return Err(format!("missing snapshot {}", reference_path.display()));
+    }
+
+    let actual = Pixmap::decode_png(page).map_err(|_| "unable to decode render".to_string())?;
+    let reference = Pixmap::load_png(reference_path)
+        .map_err(|_| format!("unable to decode snapshot {}", reference_path.display()))?;
+
+    let mut failures = vec![];
+
+    if (actual.width(), actual.height()) != (reference.width(), reference.height()) {
+        failures.push(format!(
+            "size is {}x{}, but snapshot is {}x{}",
+            actual.width(),
+            actual.height(),
+            reference.width(),
+            reference.height()
+        ));
+    }
+
+    let normalized = normalize(&[&actual, &reference], options.size_policy);
+    let (a, b) = (&normalized.pixmaps[0], &normalized.pixmaps[1]);
+    let mask = page_mask(
+        &options.ignore,
+        index,
+        normalized.width,
+        normalized.height,
+        &options.render,
+    );
+    let diff = match mask {
+        Some(mask) => diff_pixmaps_masked(a, b, &options.diff, &mask),
+        None => diff_pixmaps(a, b, &options.diff),
+    };
+
+    if diff.ratio() > options.max_ratio {
+        failures.push(format!(
+            "{:.2}% of pixels differ (maximum is {:.2}%)",
+            diff.ratio() * 100.0,
+            options.max_ratio * 100.0,
+        ));
+    }
+
+    if failures.is_empty() {
+        let _ = fs::remove_file(diff_path);
+        return Ok(());
+    }
+
+    diff.to_pixmap(a)
+        .save_png(diff_path)
+        .map_err(|e| format!("failed to write diff image: {e}"))?;
+
+    Err(format!(
+        "{}, diff written to {}",
+        failures.join(", "),
+        diff_path.display()
+    ))
+}
+
+/// Assert that a PDF renders the same as its stored snapshots.
+///
+/// Renders the PDF with the given backend using the global render instance and
+/// compares each page with its reference image, see [`compare_snapshots`]. Set
+/// `SITRO_UPDATE=1` to accept new renders as the reference.
+///
+/// ```no_run
+/// # use sitro::{assert_snapshot, Backend, SnapshotOptions};
+/// let pdf = std::fs::read("out.pdf").unwrap();
+/// assert_snapshot!("out", &Backend::Hayro, &pdf);
+///
+/// let options = SnapshotOptions {
+///     max_ratio: 0.001,
+///     ..SnapshotOptions::default()
+/// };
+/// assert_snapshot!("out", &Backend::Pdfium, &pdf, &options);
+/// ```
+#[macro_export]
+macro_rules! assert_snapshot {
+    ($name:expr, $backend:expr, $pdf:expr) => {
+        $crate::assert_snapshot!($name, $backend, $pdf, &$crate::SnapshotOptions::default())
+    };
+    ($name:expr, $backend:expr, $pdf:expr, $options:expr) => {{
+        let name: &str = $name;
+        let backend: &$crate::Backend = $backend;
+        let renderer = $crate::RENDER_INSTANCE
+            .as_ref()
+            .unwrap_or_else(|e| panic!("failed to initialize renderer: {e}"));
+
+        if let Err(e) = renderer.check_snapshot(name, backend, $pdf, $options) {
+            panic!(
+                "snapshot `{}` doesn't match for {}:\n{}",
+                name,
+                backend.name(),
+                e
+            );
+        }
+    }};
+}
@@ -0,0 +1,73 @@
+//! Tests for backends and backends tagged with a Docker image label.
+
+use sitro::{add_border, Backend, Renderer};
+use tiny_skia::Pixmap;
+
+#[test]
+fn tagged_backends() {
+    let old = Backend::from_name("pdfium@old").unwrap();
+
+    assert_eq!(old, Backend::Pdfium.in_image("old").unwrap());
+    assert_eq!(old.name(), "pdfium@old");
+    assert_eq!(old.base(), Backend::Pdfium);
+    assert_eq!(old.image_label(), Some("old"));
+    assert!(old.is_available());
+    assert_eq!(Backend::Pdfium.image_label(), None);
+
+    let new = Backend::from_name("pdfium@new").unwrap();
+    assert_ne!(old, new);
+
+    for name in [
+        "hayro@old",
+        "quartz@old",
+        "pdfium@",
+        "pdfium@old@new",
+        "foo@old",
+    ] {
+        assert_eq!(Backend::from_name(name), None, "{name}");
+    }
+
+    let mut backends = vec![new.clone(), Backend::Mupdf, old.clone(), Backend::Pdfium];
+    Backend::sort(&mut backends);
+    assert_eq!(backends, [Backend::Mupdf, Backend::Pdfium, new, old]);
+}
+
+/// The color of the border drawn around pages of a backend.
+fn border_color(backend: &Backend) -> [u8; 4] {
+    let pixmap = Pixmap::new(10, 10).unwrap();
+    let pixel = add_border(&pixmap, backend, 0.2).pixel(0, 0).unwrap();
+    [pixel.red(), pixel.green(), pixel.blue(), pixel.alpha()]
+}
+
+#[test]
+fn tagged_backend_colors() {
+    let colors =
+        ["old", "new", "main"].map(|label| border_color(&Backend::Pdfium.in_image(label).unwrap()));
+
+    assert_ne!(colors[0], colors[1]);
+    assert_ne!(colors[0], colors[2]);
+    assert_ne!(colors[1], colors[2]);
+    assert!(!colors.contains(&border_color(&Backend::Pdfium)));
+
+    // The color only depends on the label, not on the order labels are used in.
+    assert_eq!(
+        colors[2],
+        border_color(&Backend::Pdfium.in_image("main").unwrap())
+    );
+}
+
+#[test]
+fn renderer_image_labels() {
+    for (images, message) in [
+        (&[("", "image")][..], "invalid image label ``"),
+        (&[("a=b", "image")], "invalid image label `a=b`"),
+        (
+            &[("old", "image"), ("old", "other image")],
+            "duplicate image label `old`",
+        ),
+    ] {
+        // Labels are validated before any container is started.
+        let error = Renderer::with_images(images).err().unwrap();
+        assert!(error.contains(message), "{error}");
+    }
+}
@@ -0,0 +1,142 @@
+//! Tests for the render cache.
+
+#[cfg(feature = "hayro")]
+use sitro::{set_render_cache, RENDER_INSTANCE};
+use sitro::{Backend, CacheKey, CacheOptions, DiskCache, RenderOptions};
+use std::thread;
+use std::time::Duration;
+use tempdir::TempDir;
+
+fn key(engine: &str, backend: Backend, pdf: &[u8], scale: f32) -> CacheKey {
+    CacheKey::new(engine, &backend, pdf, &RenderOptions { scale })
+}
+
+#[test]
+fn cache_keys() {
+    let base = key("image", Backend::Pdfium, b"pdf", 1.0);
+
+    assert_eq!(base, key("image", Backend::Pdfium, b"pdf", 1.0));
+    assert_eq!(base.as_str().len(), 64);
+
+    // The image of a tagged backend is part of the engine.
+    let tagged = Backend::Pdfium.in_image("old").unwrap();
+    assert_eq!(base, key("image", tagged, b"pdf", 1.0));
+
+    for other in [
+        key("other image", Backend::Pdfium, b"pdf", 1.0),
+        key("image", Backend::Mupdf, b"pdf", 1.0),
+        key("image", Backend::Pdfium, b"other pdf", 1.0),
+        key("image", Backend::Pdfium, b"pdf", 2.0),
+    ] {
+        assert_ne!(base, other);
+    }
+}
+
+#[test]
+fn cache_roundtrip() {
+    let dir = TempDir::new("sitro-cache").unwrap();
+    let cache = DiskCache::open(dir.path(), CacheOptions::default()).unwrap();
+    let key = key("image", Backend::Pdfium, b"pdf", 1.0);
+    let pages = vec![b"page 1".to_vec(), b"page 2".to_vec()];
+    let warnings = vec!["missing font".to_string()];
+
+    assert!(cache.get(&key).is_none());
+    cache.insert(&key, &pages, &warnings).unwrap();
+    assert_eq!(cache.get(&key), Some((pages, warnings)));
+    assert_eq!(cache.len(), 1);
+    assert!(cache.size() > 0);
+
+    // The size is recomputed when the cache is opened again.
+    let reopened = DiskCache::open(dir.path(), CacheOptions::default()).unwrap();
+    assert_eq!(reopened.size(), cache.size());
+
+    cache.clear().unwrap();
+    assert!(cache.get(&key).is_none());
+    assert!(cache.is_empty());
+    assert_eq!(cache.size(), 0);
+}
+
+#[test]
+fn cache_evicts_least_recently_used() {
+    let dir = TempDir::new("sitro-cache").unwrap();
+    let page = vec![0; 1000];
+    let options = CacheOptions {
+        max_size: Some(2500),
+    };
+    let cache = DiskCache::open(dir.path(), options).unwrap();
+    let keys = [b"a", b"b", b"c"].map(|pdf| key("image", Backend::Mupdf, pdf, 1.0));
+
+    cache.insert(&keys[0], &vec![page.clone()], &[]).unwrap();
+    thread::sleep(Duration::from_millis(20));
+    cache.insert(&keys[1], &vec![page.clone()], &[]).unwrap();
+    thread::sleep(Duration::from_millis(20));
+
+    // Using the first entry makes the second one the least recently used.
+    assert!(cache.get(&keys[0]).is_some());
+    thread::sleep(Duration::from_millis(20));
+    cache.insert(&keys[2], &vec![page.clone()], &[]).unwrap();
+
+    assert!(cache.get(&keys[0]).is_some());
+    assert!(cache.get(&keys[1]).is_none());
+    assert!(cache.get(&keys[2]).is_some());
+    assert!(cache.size() <= 2500);
+}
+
+#[test]
+fn cache_clear_keeps_foreign_files() {
+    let dir = TempDir::new("sitro-cache").unwrap();
+    let cache = DiskCache::open(dir.path(), CacheOptions::default()).unwrap();
+    let key = key("image", Backend::Pdfium, b"pdf", 1.0);
+    cache.insert(&key, &vec![b"page".to_vec()], &[]).unwrap();
+
+    // Directories of entries that are being written belong to the cache.
+    std::fs::create_dir(dir.path().join("tmp-1234")).unwrap();
+
+    // A directory that doesn't belong to the cache makes clearing fail without removing
+    // anything, for example if the cache directory was set to the home directory.
+    let foreign = dir.path().join("documents");
+    std::fs::create_dir(&foreign).unwrap();
+    std::fs::write(foreign.join("thesis.pdf"), b"pdf").unwrap();
+
+    let error = cache.clear().unwrap_err();
+    assert!(
+        error.contains("documents doesn't belong to the render cache"),
+        "{error}"
+    );
+    assert!(foreign.join("thesis.pdf").exists());
+    assert!(cache.get(&key).is_some());
+
+    // Neither does a file in a shard directory.
+    std::fs::remove_dir_all(&foreign).unwrap();
+    let shard = dir.path().join(&key.as_str()[..2]);
+    std::fs::write(shard.join("notes.txt"), b"notes").unwrap();
+    assert!(cache.clear().is_err());
+    assert!(shard.join("notes.txt").exists());
+
+    std::fs::remove_file(shard.join("notes.txt")).unwrap();
+    cache.clear().unwrap();
+    assert!(cache.is_empty());
+    assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 0);
+}
+
+#[test]
+#[cfg(feature = "hayro")]
+fn cache_hayro_renders() {
+    let dir = TempDir::new("sitro-cache").unwrap();
+    let cache = DiskCache::open(dir.path(), CacheOptions::default()).unwrap();
+    set_render_cache(Some(cache)).unwrap();
+
+    let renderer = RENDER_INSTANCE.as_ref().unwrap();
+    let pdf = include_bytes!("../assets/font_cid_1.pdf");
+    let options = RenderOptions::default();
+    let rendered = renderer.render(&Backend::Hayro, pdf, &options).unwrap();
+
+    // The render is cached under the versions of the hayro crates and reused.
+    let cache = renderer.cache().unwrap();
+    assert_eq!(cache.len(), 1);
+    assert_eq!(
+        renderer.render(&Backend::Hayro, pdf, &options).unwrap(),
+        rendered
+    );
+    assert_eq!(cache.len(), 1);
+}
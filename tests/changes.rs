@@ -0,0 +1,154 @@
+//! Tests for comparing two stored corpus runs.
+
+use sitro::{
+    compare_runs, write_change_report, write_json_report, Backend, BackendRun, Change, ChangeKind,
+    ChangeOptions, FileRun, HtmlReport, HtmlReportOptions, RenderOptions, StoredRun,
+};
+use std::path::Path;
+use std::time::Duration;
+use tempdir::TempDir;
+use tiny_skia::{Color, Pixmap};
+
+fn page(color: Color) -> Pixmap {
+    let mut pixmap = Pixmap::new(10, 10).unwrap();
+    pixmap.fill(color);
+    pixmap
+}
+
+fn backend(backend: Backend, result: Result<Vec<Pixmap>, String>) -> BackendRun {
+    BackendRun {
+        backend,
+        result,
+        warnings: vec!["unsupported \"font\" ü".to_string()],
+        duration: Duration::from_millis(5),
+    }
+}
+
+/// Write a run the way the command-line tool does.
+fn write_run(dir: &Path, files: &[FileRun]) -> StoredRun {
+    let report = HtmlReport::new(dir.join("report"), HtmlReportOptions::default()).unwrap();
+
+    for file in files {
+        report.add_file(file).unwrap();
+    }
+
+    write_json_report(
+        dir.join("report.json"),
+        &report.files(),
+        &RenderOptions::default(),
+    )
+    .unwrap();
+
+    StoredRun::open(dir).unwrap()
+}
+
+#[test]
+fn compare_stored_runs() {
+    let dir = TempDir::new("sitro-changes").unwrap();
+    let white = || page(Color::WHITE);
+
+    let before = write_run(
+        &dir.path().join("before"),
+        &[FileRun {
+            name: "dir/doc.pdf".to_string(),
+            first_page: 1,
+            backends: vec![
+                backend(Backend::Pdfium, Ok(vec![white(), white()])),
+                backend(Backend::Mupdf, Ok(vec![white(), page(Color::BLACK)])),
+                backend(Backend::Hayro, Err("syntax error".to_string())),
+            ],
+        }],
+    );
+
+    let after = write_run(
+        &dir.path().join("after"),
+        &[
+            FileRun {
+                name: "dir/doc.pdf".to_string(),
+                first_page: 1,
+                backends: vec![
+                    backend(Backend::Pdfium, Ok(vec![white(), white()])),
+                    backend(Backend::Mupdf, Ok(vec![white(), white()])),
+                    backend(Backend::Hayro, Ok(vec![white(), white()])),
+                ],
+            },
+            FileRun {
+                name: "new.pdf".to_string(),
+                first_page: 0,
+                backends: vec![backend(Backend::Pdfium, Ok(vec![white()]))],
+            },
+        ],
+    );
+
+    let file = before.file("dir/doc.pdf").unwrap();
+    assert_eq!(file.first_page, 1);
+    assert_eq!(file.page_count, 2);
+    assert_eq!(file.backends[0].warnings, ["unsupported \"font\" ü"]);
+    assert_eq!(file.backends[2].error.as_deref(), Some("syntax error"));
+
+    // Pages are looked up by their index in the document.
+    assert!(before.page(file, &Backend::Mupdf, 2).is_ok());
+    for page in [0, 3] {
+        assert!(before.page(file, &Backend::Mupdf, page).is_err(), "{page}");
+    }
+    assert!(before.page(file, &Backend::Hayro, 1).is_err());
+
+    let options = ChangeOptions::default();
+    let changes = compare_runs(&before, &after, &options).unwrap();
+    let change = |file: &str, backend, page, kind| Change {
+        file: file.to_string(),
+        backend,
+        page,
+        kind,
+    };
+
+    assert_eq!(
+        changes,
+        [
+            change(
+                "dir/doc.pdf",
+                Backend::Mupdf,
+                Some(2),
+                ChangeKind::Pixels { ratio: 1.0 }
+            ),
+            change(
+                "dir/doc.pdf",
+                Backend::Hayro,
+                None,
+                ChangeKind::Status {
+                    before: Some("syntax error".to_string()),
+                    after: None
+                }
+            ),
+            change("new.pdf", Backend::Pdfium, None, ChangeKind::Added),
+        ]
+    );
+    assert_eq!(
+        changes[0].to_string(),
+        "dir/doc.pdf page 3: 100.00% of pixels changed"
+    );
+
+    let output = dir.path().join("changes");
+    let index = write_change_report(&output, &before, &after, &changes, &options).unwrap();
+    let html = std::fs::read_to_string(index).unwrap();
+    assert!(html.contains("mupdf &middot; 1 change<"));
+    assert!(html.contains("dir/doc.pdf: renders now, failed before: syntax error"));
+    assert!(output
+        .join("mupdf")
+        .join("dir_2fdoc.pdf-2.diff.png")
+        .exists());
+}
+
+#[test]
+fn compare_runs_at_different_scales() {
+    let dir = TempDir::new("sitro-changes").unwrap();
+    let before = write_run(dir.path(), &[]);
+    let mut after = StoredRun::open(dir.path()).unwrap();
+    after.scale = 2.0;
+
+    let error = compare_runs(&before, &after, &ChangeOptions::default()).err();
+    assert_eq!(
+        error.as_deref(),
+        Some("the runs were rendered at different scales (1 and 2)")
+    );
+}
@@ -0,0 +1,346 @@
+//! Tests for the command-line interface.
+
+use std::process::{Command, Output};
+
+fn sitro(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_sitro"))
+        .args(args)
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn cli_help() {
+    let output = sitro(&["--help"]);
+    assert!(output.status.success());
+    assert!(String::from_utf8_lossy(&output.stdout).contains("Usage: sitro"));
+}
+
+#[test]
+fn cli_usage_errors() {
+    for (args, message) in [
+        (&["-b", "foo", "pdf"][..], "unknown backend `foo`"),
+        (&["-b", "hayro@old", "pdf"], "hayro doesn't run in Docker"),
+        (&["--pages", "0", "pdf"], "pages are numbered from 1"),
+        (&["--scale=-1", "pdf"], "expected a positive number"),
+        (&["--frobnicate", "pdf"], "unknown option `--frobnicate`"),
+        (&["--scale"], "`--scale` requires a value"),
+        (
+            &["--watch", "--poll-interval", "0", "pdf"],
+            "expected a positive number",
+        ),
+        (&["does/not/exist.pdf"], "does/not/exist.pdf does not exist"),
+        (&["serve", "--port", "http"], "expected a port number"),
+        (
+            &["compare", "test"],
+            "expected the output directories of two runs",
+        ),
+        (
+            &["compare", "src", "tests"],
+            "src is not the output of a run",
+        ),
+    ] {
+        let output = sitro(args);
+        assert_eq!(output.status.code(), Some(2), "{args:?}");
+        assert!(
+            String::from_utf8_lossy(&output.stderr).contains(message),
+            "{args:?}"
+        );
+    }
+}
+
+#[test]
+fn cli_diff_usage_errors() {
+    for (args, message) in [
+        (
+            &["diff", "pdf", "pdfium"][..],
+            "expected an input, a backend and",
+        ),
+        (&["diff", "pdf", "foo", "pdfium"], "unknown backend `foo`"),
+        (
+            &["diff", "pdf", "pdfium", "nowhere"],
+            "`nowhere` is neither",
+        ),
+        (
+            &["diff", "--tolerance", "300", "pdf", "pdfium", "mupdf"],
+            "from 0 to 255",
+        ),
+        (
+            &["diff", "does/not/exist.pdf", "pdfium", "mupdf"],
+            "does not exist",
+        ),
+    ] {
+        let output = sitro(args);
+        assert_eq!(output.status.code(), Some(2), "{args:?}");
+        assert!(
+            String::from_utf8_lossy(&output.stderr).contains(message),
+            "{args:?}"
+        );
+    }
+
+    let output = sitro(&["diff", "--help"]);
+    assert!(output.status.success());
+    assert!(String::from_utf8_lossy(&output.stdout).contains("Usage: sitro diff"));
+}
+
+#[test]
+#[cfg(feature = "hayro")]
+fn cli_minimize_usage_errors() {
+    for (args, message) in [
+        (
+            &["minimize", "a.pdf", "pdfium"][..],
+            "expected an input and two backends",
+        ),
+        (
+            &["minimize", "a.pdf", "pdfium", "pdfium"],
+            "expected two different backends",
+        ),
+        (
+            &["minimize", "does/not/exist.pdf", "pdfium", "mupdf"],
+            "failed to read does/not/exist.pdf",
+        ),
+    ] {
+        let output = sitro(args);
+        assert_eq!(output.status.code(), Some(2), "{args:?}");
+        assert!(
+            String::from_utf8_lossy(&output.stderr).contains(message),
+            "{args:?}"
+        );
+    }
+}
+
+#[test]
+fn cli_cache() {
+    let dir = tempdir::TempDir::new("sitro-cache").unwrap();
+    let dir = dir.path().to_str().unwrap();
+
+    let output = sitro(&["cache", "info", "--cache-dir", dir]);
+    assert!(output.status.success());
+    assert!(String::from_utf8_lossy(&output.stdout).contains("entries: 0"));
+
+    let output = sitro(&["cache", "clear", "--cache-dir", dir]);
+    assert!(output.status.success());
+    assert!(String::from_utf8_lossy(&output.stdout).contains("removed 0 entries"));
+
+    let output = sitro(&["cache", "purge", "--cache-dir", dir]);
+    assert_eq!(output.status.code(), Some(2));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("unknown cache command `purge`"));
+}
+
+#[test]
+fn cli_config() {
+    let dir = tempdir::TempDir::new("sitro-config").unwrap();
+    let config = dir.path().join("sitro.toml");
+    std::fs::write(
+        &config,
+        r#"
+inputs = ["corpus/missing.pdf"]
+backends = ["mupdf", "pdfium"]
+
+[backend.pdfium]
+threshold = 0.05
+
+[profile.invalid]
+scale = 0
+
+[profile.typo]
+backend = { mupdf = { treshold = 0.1 } }
+
+[profile.other]
+inputs = ["other.pdf"]
+
+[profile.registry]
+expectations = "expectations.toml"
+"#,
+    )
+    .unwrap();
+    std::fs::write(
+        dir.path().join("expectations.toml"),
+        "[[divergence]]\nbackend = \"pdfjs\"\nfile = \"*.pdf\"\nexpect = \"broken\"\n",
+    )
+    .unwrap();
+    let config = config.to_str().unwrap();
+
+    for (args, message) in [
+        (&["-c", config][..], "corpus/missing.pdf does not exist"),
+        (
+            &["-c", config, "--profile", "other"],
+            "other.pdf does not exist",
+        ),
+        (&["-c", config, "flag.pdf"], "flag.pdf does not exist"),
+        (
+            &["-c", config, "--profile", "invalid"],
+            "invalid value for `scale` in profile `invalid`: expected a positive number",
+        ),
+        (
+            &["-c", config, "--profile", "typo"],
+            "unknown key `treshold` in backend `mupdf` in profile `typo`",
+        ),
+        (
+            &["-c", config, "--profile", "all"],
+            "unknown profile `all` (available profiles are invalid, other, registry, typo)",
+        ),
+        (
+            &["-c", config, "--profile", "registry"],
+            "expectations.toml: divergence 1: unknown expectation `broken`",
+        ),
+        (
+            &["--profile", "all", "pdf"],
+            "`--profile` requires a config",
+        ),
+        (&["-c", "does/not/exist.toml"], "failed to read config"),
+    ] {
+        let output = sitro(args);
+        assert_eq!(output.status.code(), Some(2), "{args:?}");
+        assert!(
+            String::from_utf8_lossy(&output.stderr).contains(message),
+            "{args:?}: {}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+}
+
+#[test]
+#[cfg(feature = "hayro")]
+fn cli_expectations() {
+    let dir = tempdir::TempDir::new("sitro-expectations").unwrap();
+    let path = |name: &str| dir.path().join(name).to_str().unwrap().to_string();
+    std::fs::write(path("broken.pdf"), b"not a PDF").unwrap();
+    std::fs::write(
+        path("expectations.toml"),
+        "[[divergence]]\nbackend = \"hayro\"\nfile = \"broken.pdf\"\nexpect = \"failure\"\n",
+    )
+    .unwrap();
+    std::fs::write(
+        path("sitro.toml"),
+        "[profile.known]\nexpectations = \"expectations.toml\"\n",
+    )
+    .unwrap();
+
+    let run = |extra: &[&str]| {
+        let (input, output, cache) = (path("broken.pdf"), path("out"), path("cache"));
+        let mut args = vec!["-b", "hayro", "-o", &output, "--cache-dir", &cache, &input];
+        args.extend(extra);
+        sitro(&args)
+    };
+
+    let output = run(&[]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("failed to render"));
+
+    let config = path("sitro.toml");
+    let expectations = path("expectations.toml");
+
+    for extra in [
+        &["--expectations", &expectations][..],
+        &["-c", &config, "--profile", "known"],
+    ] {
+        let output = run(extra);
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        assert_eq!(output.status.code(), Some(0), "{extra:?}: {stdout}");
+        assert!(
+            stdout.contains(
+                "hayro is expected to fail on broken.pdf, and failed to render broken.pdf"
+            ),
+            "{extra:?}: {stdout}"
+        );
+    }
+}
+
+/// A PDF with the given number of blank pages.
+#[cfg(feature = "hayro")]
+fn blank_pdf(pages: usize) -> Vec<u8> {
+    let kids = (0..pages)
+        .map(|i| format!("{} 0 R", i + 3))
+        .collect::<Vec<_>>()
+        .join(" ");
+    let mut objects = vec![
+        "<< /Type /Catalog /Pages 2 0 R >>".to_string(),
+        format!("<< /Type /Pages /Kids [{kids}] /Count {pages} >>"),
+    ];
+    objects.extend(
+        (0..pages).map(|_| "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 20 20] >>".to_string()),
+    );
+
+    let mut out = b"%PDF-1.7\n".to_vec();
+    let mut offsets = vec![];
+
+    for (i, object) in objects.iter().enumerate() {
+        offsets.push(out.len());
+        out.extend(format!("{} 0 obj\n{object}\nendobj\n", i + 1).as_bytes());
+    }
+
+    let xref = out.len();
+    out.extend(format!("xref\n0 {}\n0000000000 65535 f \n", objects.len() + 1).as_bytes());
+
+    for offset in offsets {
+        out.extend(format!("{offset:010} 00000 n \n").as_bytes());
+    }
+
+    out.extend(
+        format!(
+            "trailer\n<< /Size {} /Root 1 0 R >>\nstartxref\n{xref}\n%%EOF\n",
+            objects.len() + 1
+        )
+        .as_bytes(),
+    );
+    out
+}
+
+#[test]
+#[cfg(feature = "hayro")]
+fn cli_removes_composites_of_missing_pages() {
+    let dir = tempdir::TempDir::new("sitro-composites").unwrap();
+    let path = |name: &str| dir.path().join(name).to_str().unwrap().to_string();
+    let (input, output, cache) = (path("doc.pdf"), path("out"), path("cache"));
+    let run = || sitro(&["-b", "hayro", "-o", &output, "--cache-dir", &cache, &input]);
+    let composites = || {
+        let mut names = std::fs::read_dir(&output)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .filter(|name| name.ends_with(".png"))
+            .collect::<Vec<_>>();
+        names.sort();
+        names
+    };
+
+    std::fs::write(&input, blank_pdf(3)).unwrap();
+    assert!(run().status.success());
+    assert_eq!(
+        composites(),
+        [
+            "doc-0-flip.png",
+            "doc-0.png",
+            "doc-1-flip.png",
+            "doc-1.png",
+            "doc-2-flip.png",
+            "doc-2.png"
+        ]
+    );
+
+    std::fs::write(&input, blank_pdf(1)).unwrap();
+    assert!(run().status.success());
+    assert_eq!(composites(), ["doc-0-flip.png", "doc-0.png"]);
+
+    // Composites outside of the selected pages are kept.
+    std::fs::write(&input, blank_pdf(3)).unwrap();
+    assert!(run().status.success());
+    std::fs::write(&input, blank_pdf(1)).unwrap();
+    assert!(sitro(&[
+        "-b",
+        "hayro",
+        "-o",
+        &output,
+        "--cache-dir",
+        &cache,
+        "--pages",
+        "1-2",
+        &input
+    ])
+    .status
+    .success());
+    assert_eq!(
+        composites(),
+        ["doc-0-flip.png", "doc-0.png", "doc-2-flip.png", "doc-2.png"]
+    );
+}
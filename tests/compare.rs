@@ -0,0 +1,411 @@
+//! Tests for comparing rendered pages.
+
+use sitro::{
+    agreement_heatmap, composite, consensus, diff_aligned, diff_pixmaps, diff_pixmaps_masked,
+    estimate_offset, flipbook, normalize, page_mask, AlignOptions, Backend, CompositeOptions,
+    ConsensusMode, ConsensusOptions, DiffMode, DiffOptions, FlipbookOptions, HeatmapOptions,
+    IgnoreRegion, Layout, Mask, RenderOptions, SizePolicy, Tile,
+};
+use std::time::Duration;
+use tiny_skia::{Color, Paint, Pixmap, Rect, Transform};
+
+fn page(width: u32, height: u32) -> Pixmap {
+    let mut pixmap = Pixmap::new(width, height).unwrap();
+    pixmap.fill(Color::WHITE);
+    pixmap
+}
+
+fn page_with_square(width: u32, height: u32, square: Rect) -> Pixmap {
+    let mut pixmap = page(width, height);
+    let mut paint = Paint::default();
+    paint.set_color_rgba8(0, 0, 0, 255);
+    pixmap.fill_rect(square, &paint, Transform::identity(), None);
+    pixmap
+}
+
+#[test]
+fn diff_identical() {
+    let diff = diff_pixmaps(&page(20, 20), &page(20, 20), &DiffOptions::default());
+    assert!(diff.is_identical());
+    assert_eq!(diff.ratio(), 0.0);
+}
+
+#[test]
+fn diff_square() {
+    let square = Rect::from_xywh(0.0, 0.0, 10.0, 10.0).unwrap();
+    let diff = diff_pixmaps(
+        &page(20, 20),
+        &page_with_square(20, 20, square),
+        &DiffOptions::default(),
+    );
+    assert_eq!(diff.differing_pixels(), 100);
+    assert_eq!(diff.ratio(), 0.25);
+    assert!(diff.is_different(5, 5));
+    assert!(!diff.is_different(15, 15));
+}
+
+#[test]
+fn diff_different_sizes() {
+    let diff = diff_pixmaps(&page(10, 20), &page(20, 20), &DiffOptions::default());
+    assert_eq!(diff.width(), 20);
+    assert_eq!(diff.differing_pixels(), 200);
+}
+
+fn consensus_flags_outlier(mode: ConsensusMode) {
+    let square = Rect::from_xywh(0.0, 0.0, 10.0, 10.0).unwrap();
+    let pages = vec![
+        (Backend::Pdfium, page(20, 20)),
+        (Backend::Mupdf, page(20, 20)),
+        (Backend::Hayro, page_with_square(20, 20, square)),
+    ];
+
+    let options = ConsensusOptions {
+        mode,
+        ..ConsensusOptions::default()
+    };
+    let result = consensus(&pages, &options);
+
+    assert!(diff_pixmaps(&result.image, &page(20, 20), &options.diff).is_identical());
+    let outliers = result.outliers().map(|s| &s.backend).collect::<Vec<_>>();
+    assert_eq!(outliers, [&Backend::Hayro]);
+    assert_eq!(result.scores[2].distance, 0.25);
+}
+
+#[test]
+fn consensus_median() {
+    consensus_flags_outlier(ConsensusMode::Median);
+}
+
+#[test]
+fn consensus_majority() {
+    consensus_flags_outlier(ConsensusMode::Majority);
+}
+
+#[test]
+fn normalize_policies() {
+    let (a, b) = (page(10, 20), page(12, 18));
+
+    let padded = normalize(&[&a, &b], SizePolicy::Pad);
+    assert_eq!((padded.width, padded.height), (12, 20));
+    assert!(diff_pixmaps(
+        &padded.pixmaps[0],
+        &padded.pixmaps[1],
+        &DiffOptions::default()
+    )
+    .is_identical());
+
+    let cropped = normalize(&[&a, &b], SizePolicy::Crop);
+    assert_eq!((cropped.width, cropped.height), (10, 18));
+
+    let c = page(10, 20);
+    let resampled = normalize(&[&b, &a, &c], SizePolicy::Resample);
+    assert_eq!((resampled.width, resampled.height), (10, 20));
+    assert_eq!(resampled.pixmaps[0].width(), 10);
+
+    assert!(!padded.discrepancy.is_empty());
+    assert_eq!(padded.discrepancy.max_difference(), 2);
+}
+
+#[test]
+fn consensus_reports_size_discrepancy() {
+    let pages = vec![
+        (Backend::Pdfium, page(20, 20)),
+        (Backend::Poppler, page(20, 21)),
+    ];
+
+    let result = consensus(&pages, &ConsensusOptions::default());
+    assert_eq!(result.size_discrepancy.max_difference(), 1);
+    assert_eq!(result.outliers().count(), 0);
+}
+
+#[test]
+fn offset_integer() {
+    let a = page_with_square(30, 30, Rect::from_xywh(10.0, 10.0, 8.0, 8.0).unwrap());
+    let b = page_with_square(30, 30, Rect::from_xywh(12.0, 9.0, 8.0, 8.0).unwrap());
+
+    let aligned = diff_aligned(&a, &b, &AlignOptions::default(), &DiffOptions::default());
+    assert!((aligned.offset.dx - 2.0).abs() < 0.1);
+    assert!((aligned.offset.dy + 1.0).abs() < 0.1);
+    assert!(aligned.offset.correlation > 0.95);
+    assert!(!aligned.before.is_identical());
+    assert!(aligned.after.ratio() < aligned.before.ratio() / 4.0);
+}
+
+#[test]
+fn offset_sub_pixel() {
+    let a = page_with_square(30, 30, Rect::from_xywh(10.0, 10.0, 8.0, 8.0).unwrap());
+    let b = page_with_square(30, 30, Rect::from_xywh(10.5, 10.0, 8.0, 8.0).unwrap());
+
+    let offset = estimate_offset(&a, &b, &AlignOptions::default());
+    assert!((offset.dx - 0.5).abs() < 0.2, "{offset:?}");
+    assert!(offset.dy.abs() < 0.1, "{offset:?}");
+}
+
+#[test]
+fn diff_ignores_anti_aliasing() {
+    let options = DiffOptions {
+        mode: DiffMode::IgnoreAntiAliasing,
+        ..DiffOptions::default()
+    };
+
+    let a = page_with_square(30, 30, Rect::from_xywh(10.0, 10.0, 8.0, 8.0).unwrap());
+    let b = page_with_square(30, 30, Rect::from_xywh(11.0, 10.0, 8.0, 8.0).unwrap());
+    let diff = diff_pixmaps(&a, &b, &options);
+    assert!(diff.is_identical());
+    assert!(diff.anti_aliased_pixels() > 0);
+    assert!(!diff_pixmaps(&a, &b, &DiffOptions::default()).is_identical());
+
+    let c = page_with_square(30, 30, Rect::from_xywh(13.0, 10.0, 8.0, 8.0).unwrap());
+    let diff = diff_pixmaps(&a, &c, &options);
+    assert!(diff.is_different(11, 14));
+    assert!(!diff.is_identical());
+}
+
+#[test]
+fn diff_reports_gradients_with_anti_aliasing_ignored() {
+    let options = DiffOptions {
+        mode: DiffMode::IgnoreAntiAliasing,
+        ..DiffOptions::default()
+    };
+
+    // Horizontal gradients that are shifted by half a step, so that each pixel lies
+    // between its neighbors in the other page, but doesn't match any of them.
+    let gradient = |offset: u8| {
+        let mut pixmap = page(10, 10);
+        let mut paint = Paint::default();
+
+        for x in 0..10u8 {
+            let value = 20 + x * 24 + offset;
+            paint.set_color_rgba8(value, value, value, 255);
+            let column = Rect::from_xywh(x as f32, 0.0, 1.0, 10.0).unwrap();
+            pixmap.fill_rect(column, &paint, Transform::identity(), None);
+        }
+
+        pixmap
+    };
+
+    let diff = diff_pixmaps(&gradient(0), &gradient(12), &options);
+    assert_eq!(diff.anti_aliased_pixels(), 0);
+    assert_eq!(diff.ratio(), 1.0);
+}
+
+#[test]
+fn diff_regions() {
+    let mut b = page_with_square(40, 40, Rect::from_xywh(2.0, 2.0, 4.0, 4.0).unwrap());
+    let mut paint = Paint::default();
+    paint.set_color_rgba8(0, 0, 0, 255);
+    b.fill_rect(
+        Rect::from_xywh(8.0, 2.0, 2.0, 2.0).unwrap(),
+        &paint,
+        Transform::identity(),
+        None,
+    );
+    b.fill_rect(
+        Rect::from_xywh(30.0, 30.0, 6.0, 4.0).unwrap(),
+        &paint,
+        Transform::identity(),
+        None,
+    );
+
+    let diff = diff_pixmaps(&page(40, 40), &b, &DiffOptions::default());
+    let regions = diff.regions(2, &RenderOptions { scale: 2.0 });
+
+    assert_eq!(regions.len(), 2);
+    assert_eq!(
+        (
+            regions[0].pixels.x(),
+            regions[0].pixels.y(),
+            regions[0].pixels.width(),
+            regions[0].pixels.height()
+        ),
+        (2, 2, 8, 4)
+    );
+    assert_eq!(regions[0].differing_pixels, 20);
+    assert_eq!(regions[1].user_space.left(), 15.0);
+    assert_eq!(regions[1].user_space.top(), 3.0);
+    assert_eq!(regions[1].user_space.bottom(), 5.0);
+}
+
+#[test]
+fn diff_with_ignore_regions() {
+    let square = Rect::from_xywh(0.0, 0.0, 10.0, 10.0).unwrap();
+    let a = page(20, 20);
+    let b = page_with_square(20, 20, square);
+    let options = RenderOptions { scale: 2.0 };
+
+    // The square covers the top-left quarter of the page, which is at the
+    // top of the page in PDF user space.
+    let regions = vec![IgnoreRegion::Rect {
+        page: Some(0),
+        rect: Rect::from_xywh(0.0, 5.0, 5.0, 5.0).unwrap(),
+    }];
+    assert!(page_mask(&regions, 1, 20, 20, &options).is_none());
+
+    let mask = page_mask(&regions, 0, 20, 20, &options).unwrap();
+    let diff = diff_pixmaps_masked(&a, &b, &DiffOptions::default(), &mask);
+    assert!(diff.is_identical());
+    assert_eq!(diff.ignored_pixels(), 100);
+
+    let image = page_with_square(10, 10, Rect::from_xywh(0.0, 0.0, 5.0, 5.0).unwrap());
+    let mask = Mask::from_pixmap(&image);
+    let mut scaled = Mask::new(20, 20);
+    scaled.add_mask(&mask);
+    assert!(scaled.is_masked(9, 9));
+    assert!(!scaled.is_masked(10, 10));
+    assert!(diff_pixmaps_masked(&a, &b, &DiffOptions::default(), &scaled).is_identical());
+}
+
+#[test]
+fn heatmap_agreement() {
+    let pages = vec![
+        (Backend::Pdfium, page(20, 20)),
+        (Backend::Mupdf, page(20, 20)),
+        (
+            Backend::Hayro,
+            page_with_square(20, 20, Rect::from_xywh(0.0, 0.0, 10.0, 10.0).unwrap()),
+        ),
+        (
+            Backend::Poppler,
+            page_with_square(20, 20, Rect::from_xywh(0.0, 0.0, 5.0, 5.0).unwrap()),
+        ),
+    ];
+
+    let heatmap = agreement_heatmap(&pages, &HeatmapOptions::default());
+    assert_eq!(heatmap.contested, 0.25);
+
+    // Only hayro differs at (7, 7), so the pixel has the color of hayro.
+    let hayro = heatmap.image.pixel(7, 7).unwrap();
+    assert_eq!((hayro.red(), hayro.green(), hayro.blue()), (57, 212, 116));
+
+    let legend = heatmap.with_legend();
+    assert!(legend.height() > heatmap.image.height());
+}
+
+#[test]
+fn composite_layouts() {
+    let tiles = [Backend::Pdfium, Backend::Mupdf, Backend::Hayro]
+        .iter()
+        .map(|backend| Tile::new(backend, page(10, 20)))
+        .collect::<Vec<_>>();
+
+    let size = |layout| {
+        let options = CompositeOptions {
+            layout,
+            spacing: 2,
+            border_width: None,
+            labels: false,
+            annotate: false,
+        };
+        let pixmap = composite(&tiles, &options);
+        (pixmap.width(), pixmap.height())
+    };
+
+    assert_eq!(size(Layout::Horizontal), (34, 20));
+    assert_eq!(size(Layout::Vertical), (10, 64));
+    assert_eq!(size(Layout::Grid(2)), (22, 42));
+    assert_eq!(size(Layout::Auto), (34, 20));
+
+    // Labels are drawn above each tile in the color of the backend.
+    let labeled = composite(&tiles, &CompositeOptions::default());
+    assert!(labeled.height() > 20);
+    let label = labeled.pixel(0, 0).unwrap();
+    assert_eq!((label.red(), label.green(), label.blue()), (79, 184, 35));
+}
+
+#[test]
+fn composite_failed_tile() {
+    let tiles = vec![
+        Tile::new(&Backend::Pdfium, page(200, 300)),
+        Tile::failed(&Backend::Mupdf, "the backend crashed", 200, 300),
+    ];
+
+    let options = CompositeOptions {
+        labels: false,
+        border_width: None,
+        ..CompositeOptions::default()
+    };
+    let pixmap = composite(&tiles, &options);
+    assert_eq!((pixmap.width(), pixmap.height()), (400, 300));
+
+    // The placeholder isn't blank.
+    let failed = &tiles[1].pixmap;
+    assert_eq!((failed.width(), failed.height()), (200, 300));
+    assert!(!diff_pixmaps(failed, &page(200, 300), &DiffOptions::default()).is_identical());
+}
+
+#[test]
+fn composite_annotations() {
+    let tiles = vec![
+        Tile::new(&Backend::Pdfium, page(20, 20)),
+        Tile::new(&Backend::Mupdf, page(20, 20)),
+        Tile::new(
+            &Backend::Hayro,
+            page_with_square(20, 20, Rect::from_xywh(5.0, 5.0, 5.0, 5.0).unwrap()),
+        ),
+        Tile::failed(&Backend::Poppler, "the backend crashed", 20, 20),
+    ];
+
+    let options = CompositeOptions {
+        labels: false,
+        border_width: None,
+        ..CompositeOptions::default()
+    };
+    let plain = composite(&tiles, &options);
+    let annotated = composite(
+        &tiles,
+        &CompositeOptions {
+            annotate: true,
+            ..options
+        },
+    );
+
+    // Only the page that differs from the consensus is outlined, failed pages are
+    // left alone.
+    let columns = |pixmap: &tiny_skia::Pixmap, x: u32| {
+        (0..20)
+            .map(|y| pixmap.pixel(x, y).unwrap())
+            .collect::<Vec<_>>()
+    };
+
+    for x in (0..40).chain(60..80) {
+        assert_eq!(columns(&plain, x), columns(&annotated, x), "{x}");
+    }
+
+    let outline = annotated.pixel(44, 7).unwrap();
+    assert_eq!(
+        (outline.red(), outline.green(), outline.blue()),
+        (255, 0, 0)
+    );
+
+    // The flipbook is annotated the same way.
+    let flipbook = flipbook(
+        &tiles,
+        &FlipbookOptions {
+            labels: false,
+            border_width: None,
+            annotate: true,
+            ..FlipbookOptions::default()
+        },
+    );
+    assert_eq!(flipbook.frames[2].pixel(4, 7), annotated.pixel(44, 7));
+}
+
+#[test]
+fn flipbook_frames() {
+    let tiles = vec![
+        Tile::new(&Backend::Pdfium, page(20, 30)),
+        Tile::new(&Backend::Mupdf, page(24, 30)),
+    ];
+
+    let options = FlipbookOptions {
+        delay: Duration::from_millis(250),
+        ..FlipbookOptions::default()
+    };
+    let flipbook = flipbook(&tiles, &options);
+    assert_eq!(flipbook.frames.len(), 2);
+    assert_eq!(flipbook.frames[0].width(), flipbook.frames[1].width());
+
+    let data = flipbook.encode_apng().unwrap();
+    assert!(data.starts_with(b"\x89PNG"));
+    assert!(data.windows(4).any(|chunk| chunk == b"acTL"));
+}
@@ -0,0 +1,234 @@
+//! Tests for consistency checks across backends.
+
+use sitro::{
+    check_rendered_consistency, Backend, ConsistencyOptions, Expectation, Expectations,
+    KnownDivergence,
+};
+use std::path::Path;
+use tempdir::TempDir;
+use tiny_skia::{Color, Pixmap};
+
+fn page(width: u32, height: u32, color: Color) -> Vec<u8> {
+    let mut pixmap = Pixmap::new(width, height).unwrap();
+    pixmap.fill(color);
+    pixmap.encode_png().unwrap()
+}
+
+fn options(dir: &TempDir) -> ConsistencyOptions {
+    ConsistencyOptions {
+        output_dir: dir.path().to_path_buf(),
+        name: "doc.pdf".to_string(),
+        ..ConsistencyOptions::default()
+    }
+}
+
+fn divergence(backend: Backend, page: Option<usize>, expectation: Expectation) -> KnownDivergence {
+    KnownDivergence {
+        backend,
+        file: "doc.pdf".to_string(),
+        page,
+        expectation,
+        reason: None,
+    }
+}
+
+/// The paths of the composites listed in the error of a consistency check.
+fn composites(error: &str) -> Vec<&Path> {
+    error
+        .lines()
+        .filter_map(|line| line.strip_prefix("composite written to "))
+        .map(Path::new)
+        .collect()
+}
+
+#[test]
+fn consistency_consistent() {
+    let dir = TempDir::new("sitro-consistency").unwrap();
+    let white = page(10, 10, Color::WHITE);
+    let renders = [
+        (Backend::Pdfium, Ok(vec![white.clone(), white.clone()])),
+        (Backend::Mupdf, Ok(vec![white.clone(), white.clone()])),
+        // Within the size tolerance.
+        (
+            Backend::Poppler,
+            Ok(vec![page(11, 10, Color::WHITE), white]),
+        ),
+    ];
+
+    check_rendered_consistency(&renders, &options(&dir)).unwrap();
+    assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 0);
+}
+
+#[test]
+fn consistency_mismatches() {
+    let dir = TempDir::new("sitro-consistency").unwrap();
+    let white = page(10, 10, Color::WHITE);
+    let renders = [
+        (Backend::Pdfium, Ok(vec![white.clone(), white.clone()])),
+        (Backend::Mupdf, Ok(vec![page(10, 20, Color::WHITE)])),
+        (Backend::Poppler, Ok(vec![page(10, 10, Color::BLACK)])),
+        (Backend::Pdfjs, Err("missing font".to_string())),
+    ];
+
+    let error = check_rendered_consistency(&renders, &options(&dir)).unwrap_err();
+
+    for message in [
+        "pdfjs failed to render: missing font",
+        "pdfium rendered 2 page(s), but mupdf rendered 1",
+        "pdfium rendered 2 page(s), but poppler rendered 1",
+        "page 0: pdfium has size 10x10, but mupdf has size 10x20",
+        "page 0: pdfium and poppler differ in 100.00% of pixels (maximum is 1.00%)",
+    ] {
+        assert!(error.contains(message), "{message}\n{error}");
+    }
+
+    // Only the first page is compared, so there is a single composite.
+    let composites = composites(&error);
+    assert_eq!(composites.len(), 1, "{error}");
+    assert!(composites[0].starts_with(dir.path()));
+    assert!(composites[0].exists());
+}
+
+#[test]
+fn consistency_expectations() {
+    let dir = TempDir::new("sitro-consistency").unwrap();
+    let white = page(10, 10, Color::WHITE);
+    let renders = [
+        (Backend::Pdfium, Ok(vec![white.clone()])),
+        (Backend::Poppler, Ok(vec![page(10, 10, Color::BLACK)])),
+        (Backend::Pdfjs, Err("missing font".to_string())),
+        (Backend::Mupdf, Ok(vec![white])),
+    ];
+
+    let options = ConsistencyOptions {
+        expectations: Expectations {
+            divergences: vec![
+                divergence(Backend::Poppler, Some(0), Expectation::Skip),
+                divergence(Backend::Pdfjs, None, Expectation::Failure),
+            ],
+        },
+        ..options(&dir)
+    };
+
+    check_rendered_consistency(&renders, &options).unwrap();
+
+    // Expectations that no longer hold are reported.
+    let options = ConsistencyOptions {
+        expectations: Expectations {
+            divergences: vec![
+                divergence(Backend::Pdfium, None, Expectation::Failure),
+                divergence(Backend::Mupdf, Some(0), Expectation::Difference(0.5)),
+            ],
+        },
+        ..options
+    };
+    let renders = [&renders[0], &renders[3]].map(Clone::clone);
+    let error = check_rendered_consistency(&renders, &options).unwrap_err();
+
+    assert!(error.contains("but it rendered the file"), "{error}");
+    assert!(error.contains("page 0: stale expectation"), "{error}");
+    assert_eq!(composites(&error).len(), 1, "{error}");
+}
+
+#[cfg(feature = "hayro")]
+mod hayro {
+    use super::*;
+    use sitro::{assert_consistent, assert_consistent_with, Renderer};
+    use std::panic::catch_unwind;
+
+    /// A PDF with a single blank page.
+    fn blank_pdf() -> Vec<u8> {
+        let objects = [
+            "<< /Type /Catalog /Pages 2 0 R >>",
+            "<< /Type /Pages /Kids [3 0 R] /Count 1 >>",
+            "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 20 20] >>",
+        ];
+
+        let mut out = b"%PDF-1.7\n".to_vec();
+        let mut offsets = vec![];
+
+        for (i, object) in objects.iter().enumerate() {
+            offsets.push(out.len());
+            out.extend(format!("{} 0 obj\n{object}\nendobj\n", i + 1).as_bytes());
+        }
+
+        let xref = out.len();
+        out.extend(b"xref\n0 4\n0000000000 65535 f \n");
+
+        for offset in offsets {
+            out.extend(format!("{offset:010} 00000 n \n").as_bytes());
+        }
+
+        out.extend(
+            format!("trailer\n<< /Size 4 /Root 1 0 R >>\nstartxref\n{xref}\n%%EOF\n").as_bytes(),
+        );
+        out
+    }
+
+    #[test]
+    fn assert_consistent_skips_unavailable_backends() {
+        // Quartz is skipped on platforms other than macOS, where it renders the blank
+        // page like hayro.
+        assert_consistent(&blank_pdf(), &[Backend::Hayro, Backend::Quartz], 0.01);
+    }
+
+    #[test]
+    fn check_consistency_skips_backends_without_docker() {
+        // The image name is invalid, so it can't be found or pulled even if Docker runs.
+        let renderer = Renderer::with_images(&[("missing", "sitro/missing:@")]).unwrap();
+        let missing = Backend::Pdfium.in_image("missing").unwrap();
+        let backends = [Backend::Hayro, missing.clone()];
+
+        assert!(renderer.check_available(&Backend::Hayro).is_ok());
+        assert!(renderer.check_available(&missing).is_err());
+        renderer
+            .check_consistency(&blank_pdf(), &backends, &ConsistencyOptions::default())
+            .unwrap();
+
+        // Skipped backends are listed when the check fails.
+        let dir = TempDir::new("sitro-consistency").unwrap();
+        let options = ConsistencyOptions {
+            expectations: Expectations {
+                divergences: vec![divergence(Backend::Hayro, None, Expectation::Failure)],
+            },
+            ..options(&dir)
+        };
+        let error = renderer
+            .check_consistency(&blank_pdf(), &backends, &options)
+            .unwrap_err();
+
+        assert!(error.contains("but it rendered the file"), "{error}");
+        assert!(error.contains("\nskipped pdfium@missing: "), "{error}");
+    }
+
+    #[test]
+    fn assert_consistent_panics_with_composite() {
+        let dir = TempDir::new("sitro-consistency").unwrap();
+        let options = ConsistencyOptions {
+            expectations: Expectations {
+                divergences: vec![divergence(
+                    Backend::Hayro,
+                    Some(0),
+                    Expectation::Difference(0.5),
+                )],
+            },
+            ..options(&dir)
+        };
+
+        let panic =
+            catch_unwind(|| assert_consistent_with(&blank_pdf(), &[Backend::Hayro], &options))
+                .unwrap_err();
+        let message = panic.downcast_ref::<String>().unwrap();
+
+        assert!(
+            message
+                .starts_with("backends render the PDF inconsistently:\npage 0: stale expectation"),
+            "{message}"
+        );
+
+        let composites = composites(message);
+        assert_eq!(composites.len(), 1, "{message}");
+        assert!(composites[0].starts_with(dir.path()));
+        assert!(composites[0].exists());
+    }
+}
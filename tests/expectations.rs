@@ -0,0 +1,127 @@
+//! Tests for the registry of known divergences.
+
+use sitro::{Backend, Expectation, Expectations, Verdict};
+
+const REGISTRY: &str = r#"
+[[divergence]]
+backend = "serenity"
+file = "shading/*.pdf"
+expect = "failure"
+reason = "shading types 4 to 7 are not supported"
+
+[[divergence]]
+backend = "pdfjs"
+file = "blend/**/*.pdf"
+expect = "difference"
+threshold = 0.1
+
+[[divergence]]
+backend = "pdfjs"
+file = "blend/multiply.pdf"
+page = 2
+expect = "skip"
+"#;
+
+#[test]
+fn expectations_parse() {
+    let expectations = Expectations::parse(REGISTRY).unwrap();
+    assert_eq!(expectations.divergences.len(), 3);
+
+    let failure = &expectations.divergences[0];
+    assert_eq!(failure.backend, Backend::Serenity);
+    assert_eq!(failure.page, None);
+    assert_eq!(failure.expectation, Expectation::Failure);
+    assert_eq!(
+        failure.to_string(),
+        "serenity is expected to fail on shading/*.pdf (shading types 4 to 7 are not supported)"
+    );
+
+    let skip = &expectations.divergences[2];
+    assert_eq!(skip.page, Some(1));
+    assert_eq!(skip.expectation, Expectation::Skip);
+}
+
+#[test]
+fn expectations_parse_errors() {
+    for (registry, message) in [
+        ("[divergence]", "`divergence` must be an array of tables"),
+        ("known = 1", "unknown key `known`"),
+        (
+            "[[divergence]]\nbackend = \"foo\"",
+            "divergence 1: unknown backend `foo`",
+        ),
+        (
+            "[[divergence]]\nbackend = \"hayro\"\nfile = \"a.pdf\"\nexpect = \"difference\"",
+            "`difference` requires a `threshold`",
+        ),
+        (
+            "[[divergence]]\nbackend = \"hayro\"\nfile = \"a.pdf\"\nexpect = \"failure\"\npage = 1",
+            "a failure applies to the whole file",
+        ),
+        (
+            "[[divergence]]\nbackend = \"hayro\"\nfile = \"a.pdf\"\nexpect = \"crash\"",
+            "unknown expectation `crash`",
+        ),
+        (
+            "[[divergence]]\nbackend = \"hayro\"\nfile = \"a.pdf\"\npage = 0\nexpect = \"skip\"",
+            "`page` must be a page number, starting at 1",
+        ),
+        (
+            "[[divergence]]\nbackend = \"hayro\"\nexpect = \"skip\"",
+            "missing `file`",
+        ),
+    ] {
+        let error = Expectations::parse(registry).unwrap_err();
+        assert!(error.contains(message), "{registry}: {error}");
+    }
+}
+
+#[test]
+fn expectations_verdicts() {
+    let expectations = Expectations::parse(REGISTRY).unwrap();
+    let serenity = Backend::Serenity;
+    let pdfjs = Backend::Pdfjs;
+
+    assert!(matches!(
+        expectations.judge_render("shading/type4.pdf", &serenity, true),
+        Verdict::Expected(_)
+    ));
+    assert!(matches!(
+        expectations.judge_render("shading/type4.pdf", &serenity, false),
+        Verdict::Stale(_)
+    ));
+    assert!(matches!(
+        expectations.judge_render("text.pdf", &serenity, true),
+        Verdict::Diverged
+    ));
+    assert!(matches!(
+        expectations.judge_render("blend/multiply.pdf", &pdfjs, false),
+        Verdict::Pass
+    ));
+
+    let judge = |file, page, distance| expectations.judge_page(file, page, &pdfjs, distance, 0.01);
+    assert!(matches!(
+        judge("blend/a/screen.pdf", 0, 0.05),
+        Verdict::Expected(_)
+    ));
+    assert!(matches!(
+        judge("blend/a/screen.pdf", 0, 0.2),
+        Verdict::Diverged
+    ));
+    assert!(matches!(
+        judge("blend/a/screen.pdf", 0, 0.0),
+        Verdict::Stale(_)
+    ));
+    assert!(matches!(judge("text.pdf", 0, 0.0), Verdict::Pass));
+    assert!(matches!(judge("text.pdf", 0, 0.05), Verdict::Diverged));
+
+    // The entry for the page takes precedence over the one for the whole file.
+    assert!(matches!(
+        judge("blend/multiply.pdf", 1, 0.5),
+        Verdict::Skipped(_)
+    ));
+    assert!(matches!(
+        judge("blend/multiply.pdf", 0, 0.5),
+        Verdict::Diverged
+    ));
+}
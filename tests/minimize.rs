@@ -0,0 +1,150 @@
+//! Tests for minimizing PDFs.
+
+#![cfg(feature = "hayro")]
+
+use hayro::hayro_syntax::Pdf;
+use sitro::minimize_pdf;
+use std::sync::Arc;
+
+/// Write a PDF with the given objects, numbered from 1, where the first is the catalog.
+fn pdf(objects: &[&str]) -> Vec<u8> {
+    let mut out = b"%PDF-1.7\n".to_vec();
+    let mut offsets = vec![];
+
+    for (i, object) in objects.iter().enumerate() {
+        offsets.push(out.len());
+        out.extend(format!("{} 0 obj\n{object}\nendobj\n", i + 1).as_bytes());
+    }
+
+    let xref = out.len();
+    out.extend(format!("xref\n0 {}\n0000000000 65535 f \n", objects.len() + 1).as_bytes());
+
+    for offset in offsets {
+        out.extend(format!("{offset:010} 00000 n \n").as_bytes());
+    }
+
+    out.extend(
+        format!(
+            "trailer\n<< /Size {} /Root 1 0 R >>\nstartxref\n{xref}\n%%EOF\n",
+            objects.len() + 1
+        )
+        .as_bytes(),
+    );
+
+    out
+}
+
+fn stream(content: &str) -> String {
+    format!(
+        "<< /Length {} >>\nstream\n{content}\nendstream",
+        content.len()
+    )
+}
+
+/// The content streams of the pages of a PDF.
+fn pages(buf: &[u8]) -> Vec<String> {
+    let pdf = Pdf::new(Arc::new(buf.to_vec())).unwrap();
+    pdf.pages()
+        .iter()
+        .map(|page| String::from_utf8_lossy(page.page_stream().unwrap_or_default()).into_owned())
+        .collect()
+}
+
+#[test]
+fn minimize_content() {
+    let content = "BT /F1 12 Tf 10 10 Td (Hello) Tj ET\n\
+                   q 1 0 0 rg 0 0 10 10 re f Q\n\
+                   q 0 0 1 RG 5 5 m 20 20 l S Q\n\
+                   /Im1 Do";
+
+    let original = pdf(&[
+        "<< /Type /Catalog /Pages 2 0 R /Outlines 9 0 R >>",
+        // The resources are inherited from the page tree.
+        "<< /Type /Pages /Kids [3 0 R 4 0 R] /Count 2 /MediaBox [0 0 100 100] \
+         /Resources << /Font << /F1 7 0 R >> /XObject << /Im1 8 0 R >> >> >>",
+        "<< /Type /Page /Parent 2 0 R /Contents 5 0 R >>",
+        "<< /Type /Page /Parent 2 0 R /Contents 6 0 R \
+         /Annots [<< /Type /Annot /Subtype /Square /Rect [0 0 50 50] >>] >>",
+        &stream("0 1 0 rg 0 0 100 100 re f"),
+        &stream(content),
+        "<< /Type /Font /Subtype /Type1 /BaseFont /Helvetica >>",
+        &stream("").replace("<<", "<< /Type /XObject /Subtype /Form /BBox [0 0 1 1]"),
+        "<< /Count 0 >>",
+    ]);
+
+    let mut reductions = vec![];
+
+    // The divergence is reproduced as long as the second page fills something in red.
+    let minimized = minimize_pdf(
+        &original,
+        |pdf| {
+            let pages = pages(pdf);
+            let red = pages
+                .iter()
+                .any(|p| p.contains("1 0 0 rg") && p.contains(" re"));
+            red.then_some(pages.len() as f32)
+        },
+        |reduction| reductions.push(reduction.description.clone()),
+    )
+    .unwrap();
+
+    assert_eq!(minimized.original_divergence, 2.0);
+    assert_eq!(minimized.divergence, 1.0);
+    assert!(minimized.attempts > minimized.reductions.len());
+    assert_eq!(reductions[0], "removed page 1");
+    assert_eq!(reductions[1], "removed an annotation from page 2");
+    assert_eq!(
+        reductions,
+        minimized
+            .reductions
+            .iter()
+            .map(|r| r.description.clone())
+            .collect::<Vec<_>>()
+    );
+
+    // The block is kept together with the operators inside of it.
+    assert_eq!(pages(&minimized.pdf), ["q\n1 0 0 rg\n0 0 10 10 re\nQ\n"]);
+
+    let text = String::from_utf8_lossy(&minimized.pdf);
+
+    for removed in [
+        "/Outlines",
+        "/Annot",
+        "/Font",
+        "/Helvetica",
+        "/Im1",
+        "/Form",
+    ] {
+        assert!(!text.contains(removed), "{removed}\n{text}");
+    }
+
+    // The media box was inherited from the page tree.
+    assert!(text.contains("/MediaBox [0 0 100 100]"), "{text}");
+    assert!(minimized.pdf.len() < original.len());
+}
+
+#[test]
+fn minimize_errors() {
+    let original = pdf(&[
+        "<< /Type /Catalog /Pages 2 0 R >>",
+        "<< /Type /Pages /Kids [3 0 R] /Count 1 >>",
+        "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 10 10] >>",
+    ]);
+
+    let error = minimize_pdf(&original, |_| None, |_| {}).err().unwrap();
+    assert_eq!(error, "the PDF doesn't reproduce the divergence");
+
+    // Only the original PDF reproduces the divergence.
+    let error = minimize_pdf(&original, |pdf| (pdf == original).then_some(1.0), |_| {})
+        .err()
+        .unwrap();
+    assert!(
+        error.contains("the rewritten PDF no longer reproduces"),
+        "{error}"
+    );
+
+    let error = minimize_pdf(b"not a PDF", |_| Some(1.0), |_| {})
+        .err()
+        .unwrap();
+    assert!(error.contains("failed to read PDF"), "{error}");
+}
@@ -0,0 +1,270 @@
+//! Tests for corpus run reports.
+
+use sitro::{
+    json_report, junit_report, Backend, BackendRun, ConsensusOptions, Expectations, FileRun,
+    HtmlReport, HtmlReportOptions, JunitOptions, RenderOptions, StoredRun, JSON_REPORT_VERSION,
+};
+use std::fs;
+use std::time::Duration;
+use tempdir::TempDir;
+use tiny_skia::{Color, Pixmap};
+
+fn page(color: Color) -> Pixmap {
+    let mut pixmap = Pixmap::new(10, 10).unwrap();
+    pixmap.fill(color);
+    pixmap
+}
+
+fn run() -> FileRun {
+    let backend = |backend, result| BackendRun {
+        backend,
+        result,
+        warnings: vec![],
+        duration: Duration::from_millis(5),
+    };
+
+    FileRun {
+        name: "dir/doc <1>.pdf".to_string(),
+        first_page: 0,
+        backends: vec![
+            backend(
+                Backend::Pdfium,
+                Ok(vec![page(Color::WHITE), page(Color::WHITE)]),
+            ),
+            backend(
+                Backend::Mupdf,
+                Ok(vec![page(Color::WHITE), page(Color::BLACK)]),
+            ),
+            backend(Backend::Hayro, Ok(vec![page(Color::WHITE)])),
+            backend(Backend::Poppler, Err("syntax error".to_string())),
+        ],
+    }
+}
+
+#[test]
+fn html_report() {
+    let dir = TempDir::new("sitro-report").unwrap();
+    let report = HtmlReport::new(dir.path(), HtmlReportOptions::default()).unwrap();
+
+    let summary = report.add_file(&run()).unwrap();
+    assert_eq!(summary.pages.len(), 2);
+    assert_eq!(summary.max_distance(&Backend::Mupdf), Some(1.0));
+    assert_eq!(summary.max_distance(&Backend::Poppler), None);
+    assert!(summary.has_problems());
+
+    let index = fs::read_to_string(report.finish().unwrap()).unwrap();
+    assert!(index.contains("dir/doc &lt;1&gt;.pdf"));
+    assert!(index.contains("title=\"syntax error\""));
+    assert!(index.contains("href=\"files/dir_2fdoc_20_3c1_3e.pdf/1.html\""));
+
+    let files = dir.path().join("files").join("dir_2fdoc_20_3c1_3e.pdf");
+    let page = fs::read_to_string(files.join("1.html")).unwrap();
+    assert!(page.contains("1-mupdf.png"));
+    assert!(page.contains("only 1 of 2 pages were rendered"));
+    assert!(files.join("1-consensus.png").exists());
+    assert!(files.join("1-mupdf.diff.png").exists());
+    assert!(!files.join("1-hayro.png").exists());
+}
+
+#[test]
+fn json_report_schema() {
+    let mut run = run();
+    run.backends[2].warnings = vec!["unsupported \"font\"".to_string()];
+
+    let summary = run.summarize(&ConsensusOptions::default());
+    let json = json_report(&[summary], &RenderOptions { scale: 2.0 });
+
+    // The report is read back like the compare command reads stored runs.
+    let dir = TempDir::new("sitro-report").unwrap();
+    fs::write(dir.path().join("report.json"), &json).unwrap();
+    let stored = StoredRun::open(dir.path()).unwrap();
+
+    assert_eq!(stored.scale, 2.0);
+    assert_eq!(stored.files.len(), 1);
+
+    let file = &stored.files[0];
+    assert_eq!(file.name, "dir/doc <1>.pdf");
+    assert_eq!(file.first_page, 0);
+    assert_eq!(file.page_count, 2);
+
+    let backend = |backend| file.backends.iter().find(|b| b.backend == backend).unwrap();
+
+    let poppler = backend(Backend::Poppler);
+    assert_eq!(poppler.error.as_deref(), Some("syntax error"));
+    assert!(poppler.pages.is_empty());
+
+    let hayro = backend(Backend::Hayro);
+    assert_eq!(hayro.error, None);
+    assert_eq!(hayro.warnings, ["unsupported \"font\""]);
+    assert_eq!(hayro.duration, Duration::from_millis(5));
+    assert_eq!(hayro.pages, [(10, 10)]);
+
+    // The scores of pages aren't read back.
+    let compact = json.split_whitespace().collect::<String>();
+    assert!(compact.contains(&format!("\"version\":{JSON_REPORT_VERSION},")));
+    assert!(compact.contains("\"backend\":\"mupdf\",\"distance\":1,\"similarity\":0,"));
+}
+
+#[test]
+fn junit_report_cases() {
+    let mut run = run();
+    run.backends[2].warnings = vec!["unsupported font".to_string()];
+
+    let summary = run.summarize(&ConsensusOptions::default());
+    let xml = junit_report(&[summary], &JunitOptions::default());
+
+    assert!(xml.contains("<testsuites name=\"sitro\" tests=\"8\" failures=\"4\""));
+    assert!(xml.contains("<testsuite name=\"dir/doc &lt;1&gt;.pdf\" tests=\"8\" failures=\"4\""));
+    assert!(xml.contains(
+        "<testcase name=\"pdfium page 1\" classname=\"dir/doc &lt;1&gt;.pdf\" time=\"0.003\"/>"
+    ));
+    assert!(xml.contains("<failure type=\"difference\" message=\"100.00% of pixels differ"));
+    assert!(xml
+        .contains("<failure type=\"missing-page\" message=\"only 1 of 2 pages were rendered\"/>"));
+    assert!(
+        xml.contains("<failure type=\"render-error\" message=\"failed to render: syntax error\"/>")
+    );
+    assert!(xml.contains("<system-out>warning: unsupported font\n</system-out>"));
+}
+
+#[test]
+fn junit_report_options() {
+    let summary = run().summarize(&ConsensusOptions::default());
+    let expectations = Expectations::parse(
+        r#"
+        [[divergence]]
+        backend = "poppler"
+        file = "dir/*.pdf"
+        expect = "failure"
+        reason = "syntax error"
+
+        [[divergence]]
+        backend = "hayro"
+        file = "dir/*.pdf"
+        page = 2
+        expect = "difference"
+        threshold = 1.0
+
+        [[divergence]]
+        backend = "pdfium"
+        file = "dir/doc <1>.pdf"
+        page = 1
+        expect = "difference"
+        threshold = 0.5
+        "#,
+    )
+    .unwrap();
+    let options = JunitOptions {
+        backend_thresholds: vec![(Backend::Mupdf, 1.0)],
+        expectations,
+        ..JunitOptions::default()
+    };
+    let xml = junit_report(&[summary], &options);
+
+    assert!(xml.contains("tests=\"8\" failures=\"1\" errors=\"0\" skipped=\"3\""));
+    assert!(xml.contains(
+        "<testcase name=\"poppler page 1\" classname=\"dir/doc &lt;1&gt;.pdf\" time=\"0.003\">\n      <skipped message=\"poppler is expected to fail on dir/*.pdf (syntax error)\"/>"
+    ));
+    assert!(xml.contains(
+        "<skipped message=\"hayro is expected to differ by up to 100.00% on dir/*.pdf page 2\"/>"
+    ));
+    assert!(xml.contains(
+        "<failure type=\"stale-expectation\" message=\"pdfium is expected to differ by up to 50.00% on dir/doc &lt;1&gt;.pdf page 1, but only 0.00% of pixels differ\"/>"
+    ));
+}
+
+#[test]
+fn html_report_replaces_files() {
+    let dir = TempDir::new("sitro-report").unwrap();
+    let report = HtmlReport::new(dir.path(), HtmlReportOptions::default()).unwrap();
+    report.add_file(&run()).unwrap();
+
+    // The file changed and now only has a single page.
+    let mut changed = run();
+    for backend in &mut changed.backends {
+        if let Ok(pages) = &mut backend.result {
+            pages.truncate(1);
+        }
+    }
+    report.add_file(&changed).unwrap();
+
+    assert_eq!(report.files().len(), 1);
+    assert_eq!(report.files()[0].pages.len(), 1);
+    report.write_index().unwrap();
+
+    let files = dir.path().join("files").join("dir_2fdoc_20_3c1_3e.pdf");
+    assert!(files.join("0.html").exists());
+    assert!(!files.join("1.html").exists());
+}
+
+#[test]
+fn html_report_file_directories() {
+    let dir = TempDir::new("sitro-report").unwrap();
+    let report = HtmlReport::new(dir.path(), HtmlReportOptions::default()).unwrap();
+    let names = ["a/b.pdf", "a_b.pdf", "a b.pdf", "..", "ü.pdf"];
+
+    for name in names {
+        report
+            .add_file(&FileRun {
+                name: name.to_string(),
+                ..run()
+            })
+            .unwrap();
+    }
+
+    // Names that only differ in characters that are escaped get different directories.
+    let mut dirs = fs::read_dir(dir.path().join("files"))
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+        .collect::<Vec<_>>();
+    dirs.sort();
+
+    assert_eq!(
+        dirs,
+        ["_2e.", "_c3_bc.pdf", "a_20b.pdf", "a_2fb.pdf", "a_5fb.pdf"]
+    );
+}
+
+#[test]
+fn backend_run_changes() {
+    let backend_run = |result| BackendRun {
+        backend: Backend::Mupdf,
+        result,
+        warnings: vec![],
+        duration: Duration::ZERO,
+    };
+    let white = || Ok(vec![page(Color::WHITE), page(Color::WHITE)]);
+    let failed = || Err("syntax error\nat offset 3".to_string());
+
+    let mut changed = page(Color::WHITE);
+    changed.fill_rect(
+        tiny_skia::Rect::from_xywh(0.0, 0.0, 5.0, 2.0).unwrap(),
+        &tiny_skia::Paint::default(),
+        tiny_skia::Transform::identity(),
+        None,
+    );
+
+    for (before, after, change) in [
+        (None, white(), "rendered 2 pages"),
+        (Some(white()), white(), "unchanged"),
+        (
+            Some(white()),
+            Ok(vec![page(Color::WHITE)]),
+            "1 page instead of 2",
+        ),
+        (
+            Some(white()),
+            Ok(vec![page(Color::WHITE), changed]),
+            "page 4 changed (10.00% of pixels)",
+        ),
+        (Some(white()), failed(), "failed: syntax error"),
+        (Some(failed()), failed(), "still failing"),
+        (Some(failed()), white(), "renders again, 2 pages"),
+    ] {
+        let before = before.map(backend_run);
+        assert_eq!(
+            backend_run(after).describe_change(before.as_ref(), 2),
+            change
+        );
+    }
+}
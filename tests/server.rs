@@ -0,0 +1,116 @@
+//! Tests for the interactive comparison server.
+
+use sitro::{serve, ServerOptions};
+use std::io::{Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::OnceLock;
+use std::thread;
+
+fn server() -> SocketAddr {
+    static ADDRESS: OnceLock<SocketAddr> = OnceLock::new();
+
+    *ADDRESS.get_or_init(|| {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let options = ServerOptions {
+            max_upload_size: 1000,
+            ..ServerOptions::default()
+        };
+
+        thread::spawn(move || serve(listener, &options));
+        address
+    })
+}
+
+/// Send a request and return the status code and body of the response.
+fn request(method: &str, target: &str, body: &[u8]) -> (u16, String) {
+    let mut stream = TcpStream::connect(server()).unwrap();
+    write!(
+        stream,
+        "{method} {target} HTTP/1.1\r\nHost: localhost\r\nContent-Length: {}\r\n\r\n",
+        body.len()
+    )
+    .unwrap();
+    stream.write_all(body).unwrap();
+
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+
+    let status = response[9..12].parse().unwrap();
+    let body = response.split_once("\r\n\r\n").unwrap().1.to_string();
+    (status, body)
+}
+
+#[test]
+fn server_page_and_backends() {
+    let (status, body) = request("GET", "/", b"");
+    assert_eq!(status, 200);
+    assert!(body.contains("<title>sitro</title>"));
+
+    let (status, body) = request("GET", "/backends", b"");
+    assert_eq!(status, 200);
+    let backends = body.split_whitespace().collect::<String>();
+    assert!(backends.contains("{\"name\":\"pdfium\",\"color\":\"#4fb823\"}"));
+}
+
+#[test]
+fn server_errors() {
+    for (method, target, body, status, message) in [
+        ("GET", "/nothing", &b""[..], 404, "not found"),
+        ("GET", "/render", b"", 405, "method not allowed"),
+        (
+            "POST",
+            "/render",
+            b"%PDF",
+            400,
+            "missing `backend` parameter",
+        ),
+        (
+            "POST",
+            "/render?backend=foo",
+            b"%PDF",
+            400,
+            "unknown or unavailable backend `foo`",
+        ),
+        (
+            "POST",
+            "/render?backend=pdfium@unknown",
+            b"%PDF",
+            400,
+            "unknown or unavailable backend `pdfium@unknown`",
+        ),
+        (
+            "POST",
+            "/render?backend=pdfium&scale=0",
+            b"%PDF",
+            400,
+            "`scale` must be",
+        ),
+        (
+            "POST",
+            "/render?backend=pdfium&page=0",
+            b"%PDF",
+            400,
+            "`page` must be",
+        ),
+        (
+            "POST",
+            "/render?backend=pdfium&format=png",
+            b"%PDF",
+            400,
+            "requires a `page`",
+        ),
+        ("POST", "/render?backend=pdfium", b"", 400, "expected a PDF"),
+        (
+            "POST",
+            "/render?backend=pdfium",
+            &[0; 2000],
+            413,
+            "larger than 1000 bytes",
+        ),
+    ] {
+        let (actual, response) = request(method, target, body);
+        assert_eq!(actual, status, "{method} {target}");
+        assert!(response.contains(message), "{method} {target}: {response}");
+    }
+}
@@ -0,0 +1,65 @@
+//! Tests for snapshot comparisons.
+
+use sitro::{compare_snapshots, Backend, SnapshotOptions};
+use tempdir::TempDir;
+use tiny_skia::{Color, Pixmap};
+
+fn page(color: Color) -> Vec<u8> {
+    let mut pixmap = Pixmap::new(10, 10).unwrap();
+    pixmap.fill(color);
+    pixmap.encode_png().unwrap()
+}
+
+fn options(dir: &TempDir, update: bool) -> SnapshotOptions {
+    SnapshotOptions {
+        dir: dir.path().to_path_buf(),
+        update,
+        ..SnapshotOptions::default()
+    }
+}
+
+#[test]
+fn snapshot_roundtrip() {
+    let dir = TempDir::new("sitro-snapshot").unwrap();
+    let pages = vec![page(Color::WHITE), page(Color::BLACK)];
+
+    let err = compare_snapshots("doc", &Backend::Hayro, &pages, &options(&dir, false)).unwrap_err();
+    assert!(err.contains("missing snapshot"));
+
+    compare_snapshots("doc", &Backend::Hayro, &pages, &options(&dir, true)).unwrap();
+    assert!(dir.path().join("doc-hayro-0.png").exists());
+    assert!(dir.path().join("doc-hayro-1.png").exists());
+
+    compare_snapshots("doc", &Backend::Hayro, &pages, &options(&dir, false)).unwrap();
+}
+
+#[test]
+fn snapshot_mismatch_writes_diff() {
+    let dir = TempDir::new("sitro-snapshot").unwrap();
+    let pages = vec![page(Color::WHITE)];
+    compare_snapshots("doc", &Backend::Pdfium, &pages, &options(&dir, true)).unwrap();
+
+    let changed = vec![page(Color::BLACK)];
+    let err =
+        compare_snapshots("doc", &Backend::Pdfium, &changed, &options(&dir, false)).unwrap_err();
+    assert!(err.contains("page 0: 100.00% of pixels differ"));
+    assert!(dir.path().join("doc-pdfium-0.diff.png").exists());
+
+    // Accepting the new render removes the diff again.
+    compare_snapshots("doc", &Backend::Pdfium, &changed, &options(&dir, true)).unwrap();
+    assert!(!dir.path().join("doc-pdfium-0.diff.png").exists());
+}
+
+#[test]
+fn snapshot_page_count_changed() {
+    let dir = TempDir::new("sitro-snapshot").unwrap();
+    let pages = vec![page(Color::WHITE), page(Color::WHITE)];
+    compare_snapshots("doc", &Backend::Mupdf, &pages, &options(&dir, true)).unwrap();
+
+    let err =
+        compare_snapshots("doc", &Backend::Mupdf, &pages[..1], &options(&dir, false)).unwrap_err();
+    assert!(err.contains("snapshots for more pages"));
+
+    compare_snapshots("doc", &Backend::Mupdf, &pages[..1], &options(&dir, true)).unwrap();
+    assert!(!dir.path().join("doc-mupdf-1.png").exists());
+}